@@ -5,7 +5,7 @@ use std::{
 
 use ariadne::{Color, Label, Report, ReportKind, Source};
 use pulldown_cmark::{Options, Tag};
-use pulldown_cmark_ast::{Ast, Group, Span, Spanned, Tree};
+use pulldown_cmark_ast::{Ast, BrokenLink, Group, Span, Spanned, Tree};
 
 fn main() {
     let mut txt = String::new();
@@ -77,6 +77,10 @@ fn visit_ast(labels: &mut Vec<Label>, ast: &Ast) {
             Tree::TaskListMarker(Spanned { span, .. }) => ("task-list-marker", span),
             Tree::InlineMath(Spanned { span, .. }) => ("inline-math", span),
             Tree::DisplayMath(Spanned { span, .. }) => ("display-math", span),
+            Tree::BrokenLink(BrokenLink { span, .. }, stream) => {
+                visit_ast(labels, stream);
+                ("broken-link", span)
+            }
         };
         labels.push(Label::new(span.clone()).with_message(text))
     }
@@ -0,0 +1,507 @@
+//! Inspect and format Markdown from the command line.
+
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, IsTerminal as _, Read as _},
+    path::PathBuf,
+    process::ExitCode,
+};
+
+use ariadne::{Color, Label, Report, ReportKind, Source};
+use clap::{Parser, Subcommand, ValueEnum};
+use pulldown_cmark::{Options, Tag};
+use pulldown_cmark_ast::{
+    fix::Fixer,
+    fmt::Formatter,
+    line_index::LineIndex,
+    lint::{Config, Diagnostic, Linter},
+    merge::merge,
+    report, Ast, Group, Span, Spanned, Tree,
+};
+
+#[derive(Parser)]
+#[command(version, about)]
+struct Args {
+    #[command(subcommand)]
+    command: Command,
+    /// Markdown extensions to enable.
+    #[arg(short, long = "extension", value_enum, global = true)]
+    extensions: Vec<Extension>,
+    /// Enable all Markdown extensions.
+    #[arg(long, global = true)]
+    all_extensions: bool,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Print the tree of each file.
+    Dump {
+        #[arg(short, long, value_enum, default_value_t = Format::Tree)]
+        format: Format,
+        /// Include spans in `sexpr` output.
+        #[arg(long)]
+        spans: bool,
+        /// Don't show nodes deeper than this in `tree` output.
+        #[arg(long)]
+        max_depth: Option<usize>,
+        files: Vec<PathBuf>,
+    },
+    /// Label every node in the source.
+    Overlay {
+        /// Only label nodes of this kind, e.g `link` or `code-block`.
+        #[arg(short, long)]
+        kind: Vec<String>,
+        files: Vec<PathBuf>,
+    },
+    /// Print the nodes matching a selector.
+    Query {
+        /// See `pulldown_cmark_ast::query`.
+        selector: String,
+        files: Vec<PathBuf>,
+    },
+    /// Print a table of contents of the top-level headings,
+    /// leaving out those in block quotes, lists and footnotes.
+    Toc {
+        /// Leave out deeper headings.
+        #[arg(long, default_value_t = 6)]
+        max_level: usize,
+        files: Vec<PathBuf>,
+    },
+    /// Print the destination of each link.
+    Links {
+        /// Print images instead.
+        #[arg(long)]
+        images: bool,
+        files: Vec<PathBuf>,
+    },
+    /// Check each file for style problems, and broken links.
+    Lint {
+        #[arg(short, long, value_enum, default_value_t = Output::Pretty)]
+        format: Output,
+        /// Don't run this rule.
+        #[arg(long)]
+        disable: Vec<String>,
+        /// Only run these rules.
+        #[arg(long)]
+        only: Vec<String>,
+        /// Apply fixes to the files before reporting what's left.
+        /// Fixed stdin is printed, and the report goes to stderr.
+        #[arg(long)]
+        fix: bool,
+        files: Vec<PathBuf>,
+    },
+    /// Reformat each file.
+    Fmt {
+        /// Overwrite the files instead of printing.
+        #[arg(short, long)]
+        write: bool,
+        /// Print the files which aren't formatted, and fail if there are any.
+        #[arg(long, conflicts_with = "write")]
+        check: bool,
+        /// Reflow paragraphs to fit within this many columns.
+        #[arg(long)]
+        wrap: Option<usize>,
+        files: Vec<PathBuf>,
+    },
+    /// Merge the changes from a common ancestor to two versions of a file,
+    /// and print the result.
+    Merge {
+        base: PathBuf,
+        ours: PathBuf,
+        theirs: PathBuf,
+        /// Overwrite `ours` instead of printing.
+        #[arg(short, long)]
+        write: bool,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    Tree,
+    Json,
+    Sexpr,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Output {
+    Pretty,
+    Plain,
+    Github,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Extension {
+    Tables,
+    Footnotes,
+    OldFootnotes,
+    Strikethrough,
+    Tasklists,
+    SmartPunctuation,
+    HeadingAttributes,
+    YamlMetadata,
+    PlusesMetadata,
+    Math,
+    Gfm,
+}
+
+impl From<Extension> for Options {
+    fn from(value: Extension) -> Self {
+        match value {
+            Extension::Tables => Options::ENABLE_TABLES,
+            Extension::Footnotes => Options::ENABLE_FOOTNOTES,
+            Extension::OldFootnotes => Options::ENABLE_OLD_FOOTNOTES,
+            Extension::Strikethrough => Options::ENABLE_STRIKETHROUGH,
+            Extension::Tasklists => Options::ENABLE_TASKLISTS,
+            Extension::SmartPunctuation => Options::ENABLE_SMART_PUNCTUATION,
+            Extension::HeadingAttributes => Options::ENABLE_HEADING_ATTRIBUTES,
+            Extension::YamlMetadata => Options::ENABLE_YAML_STYLE_METADATA_BLOCKS,
+            Extension::PlusesMetadata => Options::ENABLE_PLUSES_DELIMITED_METADATA_BLOCKS,
+            Extension::Math => Options::ENABLE_MATH,
+            Extension::Gfm => Options::ENABLE_GFM,
+        }
+    }
+}
+
+/// A file, or stdin if `path` is [`None`].
+struct Input {
+    path: Option<PathBuf>,
+    text: String,
+}
+
+impl Input {
+    fn name(&self) -> String {
+        match &self.path {
+            Some(path) => path.display().to_string(),
+            None => String::from("<stdin>"),
+        }
+    }
+}
+
+fn read(files: &[PathBuf]) -> io::Result<Vec<Input>> {
+    let stdin = || {
+        let mut text = String::new();
+        io::stdin().read_to_string(&mut text)?;
+        Ok(Input { path: None, text })
+    };
+    if files.is_empty() {
+        return Ok(vec![stdin()?]);
+    }
+    files
+        .iter()
+        .map(|path| match path.as_os_str() == "-" {
+            true => stdin(),
+            false => Ok(Input {
+                text: fs::read_to_string(path)
+                    .map_err(|e| io::Error::new(e.kind(), format!("{}: {e}", path.display())))?,
+                path: Some(path.clone()),
+            }),
+        })
+        .collect()
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    let mut options = match args.all_extensions {
+        true => Options::all(),
+        false => Options::empty(),
+    };
+    for extension in &args.extensions {
+        options |= Options::from(*extension)
+    }
+    match run(args.command, options) {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(command: Command, options: Options) -> Result<ExitCode, Box<dyn std::error::Error>> {
+    match command {
+        Command::Dump {
+            format,
+            spans,
+            max_depth,
+            files,
+        } => {
+            for input in read(&files)? {
+                let ast = Ast::new_ext(&input.text, options);
+                match format {
+                    Format::Tree => {
+                        let mut tree = ast
+                            .display_tree(&input.text)
+                            .colors(io::stdout().is_terminal());
+                        if let Some(depth) = max_depth {
+                            tree = tree.max_depth(depth)
+                        }
+                        print!("{tree}")
+                    }
+                    Format::Json => println!("{}", serde_json::to_string_pretty(&ast)?),
+                    Format::Sexpr => match spans {
+                        true => print!("{}", ast.to_sexpr_with_spans()),
+                        false => print!("{}", ast.to_sexpr()),
+                    },
+                }
+            }
+        }
+        Command::Overlay { kind, files } => {
+            let normalize = |s: &str| s.replace('-', "").to_lowercase();
+            let kinds = kind.iter().map(|it| normalize(it)).collect::<Vec<_>>();
+            for input in read(&files)? {
+                let name = input.name();
+                let ast = Ast::new_ext(&input.text, options);
+                let mut labels = vec![];
+                visit_ast(&mut labels, &ast, &|tree| {
+                    kinds.is_empty() || kinds.contains(&normalize(tree.kind()))
+                });
+                // ariadne counts chars, not bytes
+                let chars = |offset: usize| input.text[..offset].chars().count();
+                Report::build(ReportKind::Custom("info", Color::Blue), name.clone(), 0)
+                    .with_labels(labels.into_iter().map(|(kind, Span(span))| {
+                        Label::new((name.clone(), chars(span.start)..chars(span.end)))
+                            .with_message(kind)
+                    }))
+                    .finish()
+                    .print((name.clone(), Source::from(&input.text)))?
+            }
+        }
+        Command::Query { selector, files } => {
+            for input in read(&files)? {
+                let ast = Ast::new_ext(&input.text, options);
+                let lines = LineIndex::new(&input.text);
+                for found in ast.query(&selector)? {
+                    let start = lines.line_col(found.span.0.start);
+                    let text = found.tree.plain_text();
+                    print!(
+                        "{}:{}:{}: {}",
+                        input.name(),
+                        start.line,
+                        start.column,
+                        found.tree.kind()
+                    );
+                    match text.is_empty() {
+                        true => println!(),
+                        false => println!(" {text:?}"),
+                    }
+                }
+            }
+        }
+        Command::Toc { max_level, files } => {
+            for input in read(&files)? {
+                let ast = Ast::new_ext(&input.text, options);
+                let headings = ast
+                    .0
+                    .iter()
+                    .filter_map(|tree| match tree {
+                        Tree::Group(Group {
+                            tag:
+                                Spanned {
+                                    item: Tag::Heading { level, id, .. },
+                                    ..
+                                },
+                            ..
+                        }) if (*level as usize) <= max_level => {
+                            Some((*level as usize, id.clone(), tree.plain_text()))
+                        }
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>();
+                let top = headings.iter().map(|it| it.0).min().unwrap_or(1);
+                let mut seen = HashMap::<String, usize>::new();
+                for (level, id, text) in headings {
+                    let anchor = match id {
+                        Some(id) => id.to_string(),
+                        None => {
+                            let slug = slug(&text);
+                            let count = seen.entry(slug.clone()).or_default();
+                            *count += 1;
+                            match *count {
+                                1 => slug,
+                                n => format!("{slug}-{}", n - 1),
+                            }
+                        }
+                    };
+                    println!("{}- [{text}](#{anchor})", "  ".repeat(level - top))
+                }
+            }
+        }
+        Command::Links { images, files } => {
+            for input in read(&files)? {
+                let ast = Ast::new_ext(&input.text, options);
+                let lines = LineIndex::new(&input.text);
+                let links = match images {
+                    true => ast.images(&input.text),
+                    false => ast.links(&input.text),
+                };
+                for link in links {
+                    let start = lines.line_col(link.span.0.start);
+                    println!(
+                        "{}:{}:{}: {}",
+                        input.name(),
+                        start.line,
+                        start.column,
+                        link.dest_url
+                    )
+                }
+            }
+        }
+        Command::Lint {
+            format,
+            disable,
+            only,
+            fix,
+            files,
+        } => {
+            let mut config = Config::new().enable_all(only.is_empty());
+            for rule in only {
+                config = config.enable(rule)
+            }
+            for rule in disable {
+                config = config.disable(rule)
+            }
+            let mut found = false;
+            let linter = Linter::new().config(config.clone());
+            for mut input in read(&files)? {
+                if fix {
+                    let fixed = Fixer::new(&linter).options(options).fix(&input.text);
+                    match &input.path {
+                        Some(path) if fixed.text != input.text => fs::write(path, &fixed.text)?,
+                        Some(_) => {}
+                        None => print!("{}", fixed.text),
+                    }
+                    input.text = fixed.text
+                }
+                // keep the report out of the fixed text
+                let emit = |s: String| match fix && input.path.is_none() {
+                    true => eprint!("{s}"),
+                    false => print!("{s}"),
+                };
+                let (ast, broken) = Ast::new_with_broken_links(&input.text, options);
+                let mut diagnostics = linter.lint(&ast, &input.text);
+                if config.is_enabled("broken-link") {
+                    diagnostics.extend(broken.iter().map(Diagnostic::from));
+                    diagnostics.sort_by_key(|it| it.span.0.start)
+                }
+                found |= !diagnostics.is_empty();
+                let name = input.name();
+                match format {
+                    Output::Pretty => {
+                        for diagnostic in diagnostics {
+                            diagnostic
+                                .to_report(&name, &input.text)
+                                .eprint((name.clone(), Source::from(&input.text)))?
+                        }
+                    }
+                    Output::Plain => emit(report::plain(&diagnostics, &name, &input.text)),
+                    Output::Github => emit(report::github(&diagnostics, &name, &input.text)),
+                }
+            }
+            if found {
+                return Ok(ExitCode::FAILURE);
+            }
+        }
+        Command::Fmt {
+            write,
+            check,
+            wrap,
+            files,
+        } => {
+            let mut formatter = Formatter::new().options(options);
+            if let Some(width) = wrap {
+                formatter = formatter.wrap(width)
+            }
+            let mut failed = false;
+            for input in read(&files)? {
+                let formatted = match formatter.format(&input.text) {
+                    Ok(it) => it,
+                    Err(e) => {
+                        failed = true;
+                        match &e.span {
+                            Some(span) => {
+                                let start = LineIndex::new(&input.text).line_col(span.0.start);
+                                eprintln!("{}:{}:{}: {e}", input.name(), start.line, start.column)
+                            }
+                            None => eprintln!("{}: {e}", input.name()),
+                        }
+                        continue;
+                    }
+                };
+                match (&input.path, write) {
+                    _ if check => {
+                        if formatted != input.text {
+                            println!("{}", input.name());
+                            failed = true
+                        }
+                    }
+                    (Some(path), true) => {
+                        if formatted != input.text {
+                            fs::write(path, formatted)?
+                        }
+                    }
+                    _ => print!("{formatted}"),
+                }
+            }
+            if failed {
+                return Ok(ExitCode::FAILURE);
+            }
+        }
+        Command::Merge {
+            base,
+            ours,
+            theirs,
+            write,
+        } => {
+            let [base, ours, theirs] = <[Input; 3]>::try_from(read(&[base, ours, theirs])?)
+                .map_err(|_| "expected three inputs")?;
+            let merged = merge(
+                &Ast::new_ext(&base.text, options),
+                &Ast::new_ext(&ours.text, options),
+                &Ast::new_ext(&theirs.text, options),
+            );
+            let lines = LineIndex::new(&ours.text);
+            for conflict in merged.conflicts() {
+                let start = lines.line_col(conflict.ours.0.start);
+                eprintln!("{}:{}:{}: conflict", ours.name(), start.line, start.column)
+            }
+            let text = merged.render(&Formatter::new().options(options))?;
+            match (&ours.path, write) {
+                (Some(path), true) => fs::write(path, text)?,
+                _ => print!("{text}"),
+            }
+            if !merged.conflicts().is_empty() {
+                return Ok(ExitCode::FAILURE);
+            }
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// An anchor for `heading`, as GitHub generates them.
+fn slug(heading: &str) -> String {
+    heading
+        .to_lowercase()
+        .chars()
+        .filter_map(|c| match c {
+            ' ' => Some('-'),
+            c if c.is_alphanumeric() || c == '-' || c == '_' => Some(c),
+            _ => None,
+        })
+        .collect()
+}
+
+fn visit_ast(
+    labels: &mut Vec<(&'static str, Span)>,
+    ast: &Ast<'_>,
+    filter: &dyn Fn(&Tree<'_>) -> bool,
+) {
+    for tree in &ast.0 {
+        if filter(tree) {
+            labels.push((tree.kind(), tree.span()))
+        }
+        if let Some(stream) = tree.stream() {
+            visit_ast(labels, stream, filter)
+        }
+    }
+}
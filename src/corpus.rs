@@ -0,0 +1,51 @@
+//! The documents in `tests/corpus`, taken from [`pulldown_cmark`]'s spec tests,
+//! with the options its test suite parses them with.
+
+use pulldown_cmark::Options;
+use serde_json::Value;
+
+pub(crate) struct Case {
+    pub name: String,
+    pub markdown: String,
+    pub options: Options,
+}
+
+pub(crate) fn cases() -> Vec<Case> {
+    let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/corpus");
+    let mut paths = std::fs::read_dir(dir)
+        .unwrap()
+        .map(|it| it.unwrap().path())
+        .collect::<Vec<_>>();
+    paths.sort();
+    let mut out = vec![];
+    for path in paths {
+        let cases =
+            serde_json::from_str::<Vec<Value>>(&std::fs::read_to_string(path).unwrap()).unwrap();
+        for case in cases {
+            let flag = |key: &str| case[key].as_bool() == Some(true);
+            let mut options = Options::ENABLE_MATH
+                | Options::ENABLE_TABLES
+                | Options::ENABLE_STRIKETHROUGH
+                | Options::ENABLE_TASKLISTS
+                | Options::ENABLE_GFM
+                | Options::ENABLE_HEADING_ATTRIBUTES;
+            options |= match flag("old_footnotes") {
+                true => Options::ENABLE_OLD_FOOTNOTES,
+                false => Options::ENABLE_FOOTNOTES,
+            };
+            if flag("metadata_blocks") {
+                options |= Options::ENABLE_YAML_STYLE_METADATA_BLOCKS
+                    | Options::ENABLE_PLUSES_DELIMITED_METADATA_BLOCKS
+            }
+            if flag("smart_punctuation") {
+                options |= Options::ENABLE_SMART_PUNCTUATION
+            }
+            out.push(Case {
+                name: case["name"].as_str().unwrap().into(),
+                markdown: case["markdown"].as_str().unwrap().into(),
+                options,
+            })
+        }
+    }
+    out
+}
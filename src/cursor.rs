@@ -0,0 +1,544 @@
+//! Navigate (and edit) an [`Ast`] without recursion.
+//!
+//! A cursor starts at the root of the [`Ast`], above the top-level nodes,
+//! where [`Cursor::current`] is [`None`].
+//! Movement methods return `false` (and leave the cursor where it was) if the
+//! requested node doesn't exist.
+//!
+//! Edits keep the tree valid: blocks only contain blocks, list items or table rows as
+//! appropriate, inline groups only contain inline content, and groups which can't be
+//! empty, like paragraphs, aren't left without children.
+
+use std::{error::Error, fmt, mem};
+
+use pulldown_cmark::Tag;
+
+use crate::{tag_kind, Ast, Group, Spanned, Tree};
+
+fn stream_at<'ast, 'a>(ast: &'ast Ast<'a>, path: &[usize]) -> Option<&'ast Ast<'a>> {
+    match path.is_empty() {
+        true => Some(ast),
+        false => ast.get(path)?.stream(),
+    }
+}
+
+fn stream_at_mut<'ast, 'a>(ast: &'ast mut Ast<'a>, path: &[usize]) -> Option<&'ast mut Ast<'a>> {
+    match path.is_empty() {
+        true => Some(ast),
+        false => ast.get_mut(path)?.stream_mut(),
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Cursor<'ast, 'a> {
+    ast: &'ast Ast<'a>,
+    path: Vec<usize>,
+}
+
+impl<'ast, 'a> Cursor<'ast, 'a> {
+    pub fn new(ast: &'ast Ast<'a>) -> Self {
+        Self { ast, path: vec![] }
+    }
+    /// The path from the root to the current node, suitable for [`Ast::get`].
+    pub fn path(&self) -> &[usize] {
+        &self.path
+    }
+    pub fn is_root(&self) -> bool {
+        self.path.is_empty()
+    }
+    pub fn current(&self) -> Option<&'ast Tree<'a>> {
+        self.ast.get(&self.path)
+    }
+    pub fn parent(&mut self) -> bool {
+        self.path.pop().is_some()
+    }
+    pub fn first_child(&mut self) -> bool {
+        match stream_at(self.ast, &self.path) {
+            Some(Ast(trees)) if !trees.is_empty() => {
+                self.path.push(0);
+                true
+            }
+            _ => false,
+        }
+    }
+    pub fn next_sibling(&mut self) -> bool {
+        move_sibling(
+            &mut self.path,
+            |ix| ix.checked_add(1),
+            |path| self.ast.get(path).is_some(),
+        )
+    }
+    pub fn prev_sibling(&mut self) -> bool {
+        move_sibling(
+            &mut self.path,
+            |ix| ix.checked_sub(1),
+            |path| self.ast.get(path).is_some(),
+        )
+    }
+}
+
+fn move_sibling(
+    path: &mut [usize],
+    step: impl FnOnce(usize) -> Option<usize>,
+    exists: impl FnOnce(&[usize]) -> bool,
+) -> bool {
+    let Some(last) = path.last_mut() else {
+        return false;
+    };
+    let Some(new) = step(*last) else {
+        return false;
+    };
+    let old = mem::replace(last, new);
+    match exists(path) {
+        true => true,
+        false => {
+            *path.last_mut().unwrap() = old;
+            false
+        }
+    }
+}
+
+/// An edit which doesn't make sense where the cursor is, or would make the tree invalid.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EditError<T = ()> {
+    pub message: String,
+    /// The argument to the edit, which wasn't used.
+    pub rejected: T,
+}
+
+impl<T> EditError<T> {
+    fn new(message: impl Into<String>, rejected: T) -> Self {
+        Self {
+            message: message.into(),
+            rejected,
+        }
+    }
+}
+
+impl<T> fmt::Display for EditError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl<T: fmt::Debug> Error for EditError<T> {}
+
+/// A [`Cursor`] which can also edit the tree.
+///
+/// Edits which are refused return an [`EditError`], holding their argument.
+#[derive(Debug)]
+pub struct CursorMut<'ast, 'a> {
+    ast: &'ast mut Ast<'a>,
+    path: Vec<usize>,
+}
+
+impl<'ast, 'a> CursorMut<'ast, 'a> {
+    pub fn new(ast: &'ast mut Ast<'a>) -> Self {
+        Self { ast, path: vec![] }
+    }
+    pub fn as_cursor(&self) -> Cursor<'_, 'a> {
+        Cursor {
+            ast: self.ast,
+            path: self.path.clone(),
+        }
+    }
+    pub fn path(&self) -> &[usize] {
+        &self.path
+    }
+    pub fn is_root(&self) -> bool {
+        self.path.is_empty()
+    }
+    pub fn current(&self) -> Option<&Tree<'a>> {
+        self.ast.get(&self.path)
+    }
+    pub fn current_mut(&mut self) -> Option<&mut Tree<'a>> {
+        self.ast.get_mut(&self.path)
+    }
+    pub fn parent(&mut self) -> bool {
+        self.path.pop().is_some()
+    }
+    pub fn first_child(&mut self) -> bool {
+        match stream_at(self.ast, &self.path) {
+            Some(Ast(trees)) if !trees.is_empty() => {
+                self.path.push(0);
+                true
+            }
+            _ => false,
+        }
+    }
+    pub fn next_sibling(&mut self) -> bool {
+        let ast = &*self.ast;
+        move_sibling(
+            &mut self.path,
+            |ix| ix.checked_add(1),
+            |path| ast.get(path).is_some(),
+        )
+    }
+    pub fn prev_sibling(&mut self) -> bool {
+        let ast = &*self.ast;
+        move_sibling(
+            &mut self.path,
+            |ix| ix.checked_sub(1),
+            |path| ast.get(path).is_some(),
+        )
+    }
+    /// The tag of the group containing the current node, or [`None`] at the top level.
+    fn parent_tag(&self) -> Option<&Tag<'a>> {
+        let (_, parent) = self.path.split_last()?;
+        match self.ast.get(parent)? {
+            Tree::Group(Group { tag, .. }) => Some(&tag.item),
+            _ => None,
+        }
+    }
+    /// Check that `tree` can be a sibling of the current node.
+    fn check_sibling(&self, tree: Tree<'a>) -> Result<Tree<'a>, Box<EditError<Tree<'a>>>> {
+        if self.is_root() {
+            return Err(Box::new(EditError::new("the root has no siblings", tree)));
+        }
+        match allowed(self.parent_tag(), &tree) {
+            true => Ok(tree),
+            false => Err(Box::new(EditError::new(
+                misplaced(self.parent_tag(), &tree),
+                tree,
+            ))),
+        }
+    }
+    /// Check that removing the current node won't leave a group which can't be empty.
+    fn check_not_only_child(&self) -> Result<(), EditError> {
+        let Some(parent) = self.parent_tag() else {
+            return Ok(());
+        };
+        let siblings = stream_at(self.ast, &self.path[..self.path.len() - 1]);
+        match may_be_empty(parent) || siblings.is_some_and(|it| it.0.len() > 1) {
+            true => Ok(()),
+            false => Err(EditError::new(
+                format!("{} can't be empty", tag_kind(parent)),
+                (),
+            )),
+        }
+    }
+    /// The stream containing the current node, and its index in that stream.
+    fn siblings_mut(&mut self) -> Option<(&mut Vec<Tree<'a>>, usize)> {
+        let (ix, parent) = self.path.split_last()?;
+        let Ast(trees) = stream_at_mut(self.ast, parent)?;
+        Some((trees, *ix))
+    }
+    /// Insert `tree` before the current node, staying on the current node.
+    pub fn insert_before(&mut self, tree: Tree<'a>) -> Result<(), Box<EditError<Tree<'a>>>> {
+        let tree = self.check_sibling(tree)?;
+        let (trees, ix) = self.siblings_mut().expect("checked");
+        trees.insert(ix, tree);
+        *self.path.last_mut().unwrap() += 1;
+        Ok(())
+    }
+    /// Insert `tree` after the current node, staying on the current node.
+    pub fn insert_after(&mut self, tree: Tree<'a>) -> Result<(), Box<EditError<Tree<'a>>>> {
+        let tree = self.check_sibling(tree)?;
+        let (trees, ix) = self.siblings_mut().expect("checked");
+        trees.insert(ix + 1, tree);
+        Ok(())
+    }
+    /// Replace the current node with `tree`, returning the old node.
+    pub fn replace(&mut self, tree: Tree<'a>) -> Result<Tree<'a>, Box<EditError<Tree<'a>>>> {
+        let tree = self.check_sibling(tree)?;
+        Ok(mem::replace(self.current_mut().expect("checked"), tree))
+    }
+    /// Remove the current node.
+    ///
+    /// The cursor moves to the next sibling, or the previous sibling if this
+    /// was the last node in its stream, or the parent if it was the only one.
+    /// The only child of a group which can't be empty, like a paragraph, can't be removed.
+    pub fn remove(&mut self) -> Result<Tree<'a>, EditError> {
+        self.check_not_only_child()?;
+        let Some((trees, ix)) = self.siblings_mut() else {
+            return Err(EditError::new("the root can't be removed", ()));
+        };
+        let removed = trees.remove(ix);
+        let len = trees.len();
+        settle(&mut self.path, len);
+        Ok(removed)
+    }
+    /// Replace the current node with a new [`Group`] containing it, and move to that group.
+    ///
+    /// The group's spans are those of the wrapped node.
+    pub fn wrap(&mut self, tag: Tag<'a>) -> Result<(), Box<EditError<Tag<'a>>>> {
+        let Some(current) = self.current() else {
+            return Err(Box::new(EditError::new("the root can't be wrapped", tag)));
+        };
+        if !allowed(Some(&tag), current) {
+            let message = misplaced(Some(&tag), current);
+            return Err(Box::new(EditError::new(message, tag)));
+        }
+        let span = current.span();
+        let group = Tree::Group(Group {
+            tag: Spanned {
+                item: tag,
+                span: span.clone(),
+            },
+            // the wrapped node is moved in below
+            stream: Ast(vec![]),
+            end_span: span,
+        });
+        if !allowed(self.parent_tag(), &group) {
+            let message = misplaced(self.parent_tag(), &group);
+            let Tree::Group(Group { tag, .. }) = group else {
+                unreachable!()
+            };
+            return Err(Box::new(EditError::new(message, tag.item)));
+        }
+        let current = self.current_mut().expect("checked");
+        let inner = mem::replace(current, group);
+        current.stream_mut().expect("a group").0.push(inner);
+        Ok(())
+    }
+    /// Replace the current [`Group`] with its children, returning its tag.
+    ///
+    /// The cursor moves to the first child, or as in [`CursorMut::remove`]
+    /// if there are none.
+    /// Fails if the current node isn't a group,
+    /// or its children can't be children of its parent, like the text of a top-level paragraph.
+    pub fn unwrap(&mut self) -> Result<Spanned<Tag<'a>>, EditError> {
+        let parent = self.parent_tag();
+        match self.current() {
+            Some(Tree::Group(Group { stream, .. })) => {
+                if let Some(child) = stream.0.iter().find(|it| !allowed(parent, it)) {
+                    return Err(EditError::new(misplaced(parent, child), ()));
+                }
+                if stream.0.is_empty() {
+                    self.check_not_only_child()?
+                }
+            }
+            Some(_) => return Err(EditError::new("only groups can be unwrapped", ())),
+            None => return Err(EditError::new("the root can't be unwrapped", ())),
+        }
+        let (trees, ix) = self.siblings_mut().expect("checked");
+        let Tree::Group(Group {
+            tag,
+            stream: Ast(children),
+            ..
+        }) = trees.remove(ix)
+        else {
+            unreachable!()
+        };
+        trees.splice(ix..ix, children);
+        let len = trees.len();
+        settle(&mut self.path, len);
+        Ok(tag)
+    }
+}
+
+/// Whether `child` can be in a group tagged `parent`, or at the top level.
+fn allowed(parent: Option<&Tag<'_>>, child: &Tree<'_>) -> bool {
+    let tag = match child {
+        Tree::Group(Group { tag, .. }) => Some(&tag.item),
+        _ => None,
+    };
+    match parent {
+        Some(Tag::List(_)) => matches!(tag, Some(Tag::Item)),
+        Some(Tag::Table(_)) => matches!(tag, Some(Tag::TableHead | Tag::TableRow)),
+        Some(Tag::TableHead | Tag::TableRow) => matches!(tag, Some(Tag::TableCell)),
+        Some(Tag::CodeBlock(_) | Tag::MetadataBlock(_)) => matches!(child, Tree::Text(_)),
+        Some(Tag::HtmlBlock) => matches!(child, Tree::Html(_)),
+        _ if matches!(
+            tag,
+            Some(Tag::Item | Tag::TableHead | Tag::TableRow | Tag::TableCell)
+        ) =>
+        {
+            false
+        }
+        None | Some(Tag::BlockQuote(_) | Tag::FootnoteDefinition(_)) => child.is_block(),
+        // tight list items contain inline content
+        Some(Tag::Item) => !matches!(child, Tree::Html(_)),
+        Some(_) => !child.is_block() && !matches!(child, Tree::Html(_)),
+    }
+}
+
+fn may_be_empty(tag: &Tag<'_>) -> bool {
+    !matches!(
+        tag,
+        Tag::Paragraph
+            | Tag::HtmlBlock
+            | Tag::List(_)
+            | Tag::Table(_)
+            | Tag::TableHead
+            | Tag::TableRow
+            | Tag::Emphasis
+            | Tag::Strong
+            | Tag::Strikethrough
+    )
+}
+
+fn misplaced(parent: Option<&Tag<'_>>, child: &Tree<'_>) -> String {
+    match parent {
+        Some(parent) => format!("{} can't contain {}", tag_kind(parent), child.kind()),
+        None => format!("{} can't be at the top level", child.kind()),
+    }
+}
+
+/// Fix up `path` after the node it points to was removed from a stream now `len` long.
+fn settle(path: &mut Vec<usize>, len: usize) {
+    let Some(ix) = path.last_mut() else { return };
+    match (*ix < len, len) {
+        (true, _) => {}
+        (false, 0) => {
+            path.pop();
+        }
+        (false, _) => *ix = len - 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(source: &str) -> Tree<'_> {
+        Ast::new(source).0.remove(0)
+    }
+
+    fn text(source: &str) -> Tree<'_> {
+        block(source).stream().unwrap().0[0].clone()
+    }
+
+    #[test]
+    fn navigation() {
+        let ast = Ast::new("a *b*\n\nc\n");
+        let mut cursor = ast.cursor();
+        assert!(cursor.is_root());
+        assert_eq!(cursor.current(), None);
+        assert!(!cursor.next_sibling());
+        assert!(cursor.first_child());
+        assert_eq!(cursor.current().unwrap().kind(), "paragraph");
+        assert!(!cursor.prev_sibling());
+        assert!(cursor.first_child());
+        assert!(cursor.next_sibling());
+        assert_eq!(cursor.path(), [0, 1]);
+        assert_eq!(cursor.current().unwrap().kind(), "emphasis");
+        assert!(!cursor.next_sibling());
+        assert_eq!(cursor.path(), [0, 1]);
+        assert!(cursor.parent());
+        assert!(cursor.next_sibling());
+        assert_eq!(cursor.current().unwrap().plain_text(), "c");
+        assert!(cursor.first_child());
+        assert!(!cursor.first_child());
+        assert!(cursor.parent() && cursor.parent());
+        assert!(!cursor.parent());
+    }
+
+    #[test]
+    fn insert() {
+        let mut ast = Ast::new("b\n");
+        let mut cursor = ast.cursor_mut();
+        assert!(cursor.insert_before(block("a")).is_err());
+        cursor.first_child();
+        cursor.insert_before(block("a")).unwrap();
+        cursor.insert_after(block("c")).unwrap();
+        assert_eq!(cursor.current().unwrap().plain_text(), "b");
+        assert_eq!(cursor.path(), [1]);
+        let err = cursor.insert_after(text("d")).unwrap_err();
+        assert_eq!(err.message, "text can't be at the top level");
+        assert_eq!(err.rejected.kind(), "text");
+        assert_eq!(
+            ast.0.iter().map(Tree::plain_text).collect::<Vec<_>>(),
+            ["a", "b", "c"]
+        );
+    }
+
+    #[test]
+    fn insert_into_list() {
+        let mut ast = Ast::new("- a\n");
+        let mut cursor = ast.cursor_mut();
+        cursor.first_child();
+        cursor.first_child();
+        let item = cursor.current().unwrap().clone();
+        cursor.insert_after(item).unwrap();
+        let err = cursor.insert_after(block("b")).unwrap_err();
+        assert_eq!(err.message, "list can't contain paragraph");
+        assert_eq!(ast.0[0].stream().unwrap().0.len(), 2);
+    }
+
+    #[test]
+    fn replace() {
+        let mut ast = Ast::new("a *b*\n");
+        let mut cursor = ast.cursor_mut();
+        cursor.first_child();
+        cursor.first_child();
+        let old = cursor.replace(text("c")).unwrap();
+        assert_eq!(old.plain_text(), "a ");
+        assert!(cursor.replace(block("d")).is_err());
+        assert_eq!(ast.plain_text(), "cb");
+    }
+
+    #[test]
+    fn remove() {
+        let mut ast = Ast::new("a\n\nb\n\nc\n");
+        let mut cursor = ast.cursor_mut();
+        assert!(cursor.remove().is_err());
+        cursor.first_child();
+        cursor.next_sibling();
+        assert_eq!(cursor.remove().unwrap().plain_text(), "b");
+        assert_eq!(cursor.current().unwrap().plain_text(), "c");
+        cursor.remove().unwrap();
+        assert_eq!(cursor.current().unwrap().plain_text(), "a");
+        cursor.first_child();
+        let err = cursor.remove().unwrap_err();
+        assert_eq!(err.message, "paragraph can't be empty");
+        cursor.parent();
+        cursor.remove().unwrap();
+        assert!(cursor.is_root());
+        assert!(ast.0.is_empty());
+    }
+
+    #[test]
+    fn remove_from_groups_which_may_be_empty() {
+        let mut ast = Ast::new("- a\n");
+        let mut cursor = ast.cursor_mut();
+        cursor.first_child();
+        cursor.first_child();
+        cursor.first_child();
+        cursor.remove().unwrap();
+        assert_eq!(cursor.current().unwrap().kind(), "item");
+        assert_eq!(cursor.remove().unwrap_err().message, "list can't be empty");
+    }
+
+    #[test]
+    fn wrap() {
+        let mut ast = Ast::new("a\n");
+        let mut cursor = ast.cursor_mut();
+        assert!(cursor.wrap(Tag::BlockQuote(None)).is_err());
+        cursor.first_child();
+        let err = cursor.wrap(Tag::Emphasis).unwrap_err();
+        assert_eq!(err.message, "emphasis can't contain paragraph");
+        assert_eq!(err.rejected, Tag::Emphasis);
+        cursor.wrap(Tag::BlockQuote(None)).unwrap();
+        assert_eq!(cursor.current().unwrap().kind(), "block-quote");
+        cursor.first_child();
+        cursor.first_child();
+        let err = cursor.wrap(Tag::Table(vec![])).unwrap_err();
+        assert_eq!(err.message, "table can't contain text");
+        assert!(cursor.wrap(Tag::Paragraph).is_err());
+        cursor.wrap(Tag::Strong).unwrap();
+        assert_eq!(cursor.path(), [0, 0, 0]);
+        assert!(ast.structurally_eq(&Ast::new("> **a**\n")));
+    }
+
+    #[test]
+    fn unwrap() {
+        let mut ast = Ast::new("a *b* c\n");
+        let mut cursor = ast.cursor_mut();
+        cursor.first_child();
+        assert_eq!(
+            cursor.unwrap().unwrap_err().message,
+            "text can't be at the top level"
+        );
+        cursor.first_child();
+        assert_eq!(
+            cursor.unwrap().unwrap_err().message,
+            "only groups can be unwrapped"
+        );
+        cursor.next_sibling();
+        assert_eq!(cursor.unwrap().unwrap().item, Tag::Emphasis);
+        assert_eq!(cursor.path(), [0, 1]);
+        assert_eq!(cursor.current().unwrap().plain_text(), "b");
+        assert_eq!(ast.0[0].stream().unwrap().0.len(), 3);
+    }
+}
@@ -0,0 +1,531 @@
+//! Edit scripts between two [`Ast`]s.
+//!
+//! Children are matched in order, where they are equal ignoring spans,
+//! or are of the same [`Tree::kind`] with similar text, preferring equal nodes.
+//! Similar nodes are diffed recursively.
+//! Unmatched nodes which are equal to an unmatched node on the other side are moves,
+//! and the rest are deletions and insertions.
+//!
+//! Every [`Change`] has a span in both sources, so can be rendered side by side,
+//! or inline.
+
+use std::{
+    collections::HashMap,
+    hash::{DefaultHasher, Hasher},
+    ops::Range,
+};
+
+use crate::{structural::StructuralHash, Ast, Group, Span, Spanned, Tree};
+
+/// Nodes with less similar text than this aren't matched.
+const SIMILARITY: f64 = 0.5;
+
+/// Give up on finding the longest common subsequence of larger inputs.
+const MAX_TABLE: usize = 1 << 22;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ChangeKind {
+    /// A subtree which is only in the new document.
+    Insert,
+    /// A subtree which is only in the old document.
+    Delete,
+    /// A node whose tag or value changed.
+    /// Its children have their own changes.
+    Update,
+    /// An unchanged subtree which moved between siblings.
+    Move,
+    /// A change within a [`Tree::Text`].
+    Text,
+}
+
+/// Returned by [`Ast::diff`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Change<'t, 'a> {
+    pub kind: ChangeKind,
+    /// [`None`] for [`ChangeKind::Insert`].
+    pub old: Option<&'t Tree<'a>>,
+    /// [`None`] for [`ChangeKind::Delete`].
+    pub new: Option<&'t Tree<'a>>,
+    /// The changed range of the old source.
+    ///
+    /// Empty for [`ChangeKind::Insert`], where the node would go.
+    pub old_span: Span,
+    /// The changed range of the new source.
+    ///
+    /// Empty for [`ChangeKind::Delete`], where the node was.
+    pub new_span: Span,
+}
+
+impl<'a> Ast<'a> {
+    /// The changes which turn `self` into `new`, in document order.
+    pub fn diff<'t>(&'t self, new: &'t Ast<'a>) -> Vec<Change<'t, 'a>> {
+        let mut changes = vec![];
+        children(&mut changes, self, new, 0, 0);
+        changes
+    }
+}
+
+fn children<'t, 'a>(
+    changes: &mut Vec<Change<'t, 'a>>,
+    old: &'t Ast<'a>,
+    new: &'t Ast<'a>,
+    mut old_at: usize,
+    mut new_at: usize,
+) {
+    for step in align(&old.0, &new.0) {
+        match step {
+            Step::Equal(o, n) => {
+                old_at = old.0[o].span().0.end;
+                new_at = new.0[n].span().0.end
+            }
+            Step::Similar(o, n) => {
+                node(changes, &old.0[o], &new.0[n]);
+                old_at = old.0[o].span().0.end;
+                new_at = new.0[n].span().0.end
+            }
+            Step::Move(o, n) => {
+                changes.push(Change {
+                    kind: ChangeKind::Move,
+                    old: Some(&old.0[o]),
+                    new: Some(&new.0[n]),
+                    old_span: old.0[o].span(),
+                    new_span: new.0[n].span(),
+                });
+                // moves don't advance, since they may go backwards
+            }
+            Step::Delete(o) => {
+                changes.push(Change {
+                    kind: ChangeKind::Delete,
+                    old: Some(&old.0[o]),
+                    new: None,
+                    old_span: old.0[o].span(),
+                    new_span: Span(new_at..new_at),
+                });
+                old_at = old.0[o].span().0.end
+            }
+            Step::Insert(n) => {
+                changes.push(Change {
+                    kind: ChangeKind::Insert,
+                    old: None,
+                    new: Some(&new.0[n]),
+                    old_span: Span(old_at..old_at),
+                    new_span: new.0[n].span(),
+                });
+                new_at = new.0[n].span().0.end
+            }
+        }
+    }
+}
+
+/// Diff nodes of the same kind.
+fn node<'t, 'a>(changes: &mut Vec<Change<'t, 'a>>, old: &'t Tree<'a>, new: &'t Tree<'a>) {
+    let update = |changes: &mut Vec<Change<'t, 'a>>| {
+        changes.push(Change {
+            kind: ChangeKind::Update,
+            old: Some(old),
+            new: Some(new),
+            old_span: old.span(),
+            new_span: new.span(),
+        })
+    };
+    match (old, new) {
+        (Tree::Text(Spanned { item: o, span: os }), Tree::Text(Spanned { item: n, span: ns })) => {
+            // offsets within the text are only offsets in the source if it is verbatim
+            let source = |text: &str, Span(span): &Span, range: Range<usize>| match span.len()
+                == text.len()
+            {
+                true => Span(span.start + range.start..span.start + range.end),
+                false => Span(span.clone()),
+            };
+            for (o_range, n_range) in text(o, n) {
+                changes.push(Change {
+                    kind: ChangeKind::Text,
+                    old: Some(old),
+                    new: Some(new),
+                    old_span: source(o, os, o_range),
+                    new_span: source(n, ns, n_range),
+                })
+            }
+        }
+        (
+            Tree::Group(Group {
+                tag: o_tag,
+                stream: o_stream,
+                ..
+            }),
+            Tree::Group(Group {
+                tag: n_tag,
+                stream: n_stream,
+                ..
+            }),
+        ) => {
+            if o_tag.item != n_tag.item {
+                update(changes)
+            }
+            children(
+                changes,
+                o_stream,
+                n_stream,
+                o_tag.span.0.start,
+                n_tag.span.0.start,
+            )
+        }
+        (Tree::BrokenLink(o_link, o_stream), Tree::BrokenLink(n_link, n_stream)) => {
+            if (&o_link.reference, o_link.link_type) != (&n_link.reference, n_link.link_type) {
+                update(changes)
+            }
+            children(
+                changes,
+                o_stream,
+                n_stream,
+                o_link.span.0.start,
+                n_link.span.0.start,
+            )
+        }
+        _ => {
+            if !old.structurally_eq(new) {
+                update(changes)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Step {
+    Equal(usize, usize),
+    Similar(usize, usize),
+    Move(usize, usize),
+    Delete(usize),
+    Insert(usize),
+}
+
+/// Cheaply comparable properties of a node.
+struct Summary {
+    kind: &'static str,
+    hash: u64,
+    /// Counts of lowercase words in its text.
+    words: HashMap<String, usize>,
+    total: usize,
+    leaf: bool,
+}
+
+fn count_words(words: &mut HashMap<String, usize>, trees: &[Tree<'_>]) {
+    for tree in trees {
+        match tree {
+            Tree::Text(Spanned { item, .. }) | Tree::Code(Spanned { item, .. }) => {
+                for word in item.split_whitespace() {
+                    *words.entry(word.to_lowercase()).or_default() += 1
+                }
+            }
+            _ => {
+                if let Some(stream) = tree.stream() {
+                    count_words(words, &stream.0)
+                }
+            }
+        }
+    }
+}
+
+impl Summary {
+    fn new(tree: &Tree<'_>) -> Self {
+        let mut hasher = DefaultHasher::new();
+        tree.structural_hash(&mut hasher);
+        let mut words = HashMap::<String, usize>::new();
+        count_words(&mut words, std::slice::from_ref(tree));
+        Self {
+            kind: tree.kind(),
+            hash: hasher.finish(),
+            total: words.values().sum(),
+            words,
+            leaf: tree.stream().is_none(),
+        }
+    }
+    /// Whether the nodes are the same kind, with similar text.
+    ///
+    /// Leaves of the same kind are always similar.
+    fn similar(&self, other: &Self) -> bool {
+        if self.kind != other.kind {
+            return false;
+        }
+        if self.leaf || self.total + other.total == 0 {
+            return true;
+        }
+        let common = self
+            .words
+            .iter()
+            .map(|(word, count)| (*count).min(other.words.get(word).copied().unwrap_or(0)))
+            .sum::<usize>();
+        (2 * common) as f64 / (self.total + other.total) as f64 >= SIMILARITY
+    }
+}
+
+/// Match equal nodes, and similar nodes where that doesn't lose equal ones.
+fn align(old: &[Tree<'_>], new: &[Tree<'_>]) -> Vec<Step> {
+    let old_summaries = old.iter().map(Summary::new).collect::<Vec<_>>();
+    let new_summaries = new.iter().map(Summary::new).collect::<Vec<_>>();
+    let equal = |o: usize, n: usize| {
+        old_summaries[o].hash == new_summaries[n].hash && old[o].structurally_eq(&new[n])
+    };
+    let pairs = lcs(old.len(), new.len(), 2, |o, n| match equal(o, n) {
+        true => 2,
+        false => old_summaries[o].similar(&new_summaries[n]) as u32,
+    });
+    let mut steps = vec![];
+    let (mut o, mut n) = (0, 0);
+    for (o_pair, n_pair) in pairs {
+        steps.extend((o..o_pair).map(Step::Delete));
+        steps.extend((n..n_pair).map(Step::Insert));
+        steps.push(match equal(o_pair, n_pair) {
+            true => Step::Equal(o_pair, n_pair),
+            false => Step::Similar(o_pair, n_pair),
+        });
+        (o, n) = (o_pair + 1, n_pair + 1)
+    }
+    steps.extend((o..old.len()).map(Step::Delete));
+    steps.extend((n..new.len()).map(Step::Insert));
+    moves(&mut steps, equal);
+    steps
+}
+
+/// Replace deletions and insertions of equal nodes with moves.
+fn moves(steps: &mut Vec<Step>, equal: impl Fn(usize, usize) -> bool) {
+    let mut moved = vec![];
+    for ix in 0..steps.len() {
+        let Step::Delete(o) = steps[ix] else {
+            continue;
+        };
+        let found = steps
+            .iter()
+            .position(|it| matches!(it, Step::Insert(n) if equal(o, *n)));
+        if let Some(insert) = found {
+            let Step::Insert(n) = steps[insert] else {
+                unreachable!()
+            };
+            steps[insert] = Step::Move(o, n);
+            moved.push(ix)
+        }
+    }
+    for ix in moved.into_iter().rev() {
+        steps.remove(ix);
+    }
+}
+
+/// The differing ranges of `old` and `new`, by words.
+fn text(old: &str, new: &str) -> Vec<(Range<usize>, Range<usize>)> {
+    let (o_tokens, n_tokens) = (tokens(old), tokens(new));
+    let common = lcs(o_tokens.len(), n_tokens.len(), 1, |o, n| {
+        (old[o_tokens[o].clone()] == new[n_tokens[n].clone()]) as u32
+    });
+    let mut ranges = vec![];
+    let (mut o, mut n) = (0, 0);
+    for (o_anchor, n_anchor) in common.into_iter().chain([(o_tokens.len(), n_tokens.len())]) {
+        if o < o_anchor || n < n_anchor {
+            let at = |tokens: &[Range<usize>], from: usize, to: usize, len: usize| match from < to {
+                true => tokens[from].start..tokens[to - 1].end,
+                false => {
+                    let at = tokens.get(from).map_or(len, |it| it.start);
+                    at..at
+                }
+            };
+            ranges.push((
+                at(&o_tokens, o, o_anchor, old.len()),
+                at(&n_tokens, n, n_anchor, new.len()),
+            ))
+        }
+        (o, n) = (o_anchor + 1, n_anchor + 1)
+    }
+    ranges
+}
+
+/// Runs of alphanumerics, runs of whitespace, and other single characters.
+fn tokens(s: &str) -> Vec<Range<usize>> {
+    let class = |c: char| match c {
+        c if c.is_alphanumeric() => 0,
+        c if c.is_whitespace() => 1,
+        _ => 2,
+    };
+    let mut tokens = Vec::<Range<usize>>::new();
+    let mut previous = None;
+    for (ix, c) in s.char_indices() {
+        let end = ix + c.len_utf8();
+        match (tokens.last_mut(), previous) {
+            (Some(last), Some(previous)) if previous == class(c) && previous != 2 => last.end = end,
+            _ => tokens.push(ix..end),
+        }
+        previous = Some(class(c))
+    }
+    tokens
+}
+
+/// The pairs of a common subsequence with the greatest total `score`,
+/// where a score of zero doesn't match.
+///
+/// Pairs scoring `full` are trimmed from the start and end first,
+/// and only those are found for large inputs.
+pub(crate) fn lcs(
+    old: usize,
+    new: usize,
+    full: u32,
+    score: impl Fn(usize, usize) -> u32,
+) -> Vec<(usize, usize)> {
+    let prefix = (0..old.min(new))
+        .take_while(|ix| score(*ix, *ix) == full)
+        .count();
+    let suffix = (0..old.min(new) - prefix)
+        .take_while(|ix| score(old - 1 - ix, new - 1 - ix) == full)
+        .count();
+    let (rows, columns) = (old - prefix - suffix, new - prefix - suffix);
+    let mut pairs = (0..prefix).map(|ix| (ix, ix)).collect::<Vec<_>>();
+    if rows * columns <= MAX_TABLE {
+        // best total scores of the suffixes
+        let mut table = vec![0u32; (rows + 1) * (columns + 1)];
+        let at = |o: usize, n: usize| o * (columns + 1) + n;
+        for o in (0..rows).rev() {
+            for n in (0..columns).rev() {
+                let matched = match score(prefix + o, prefix + n) {
+                    0 => 0,
+                    score => score + table[at(o + 1, n + 1)],
+                };
+                table[at(o, n)] = matched.max(table[at(o + 1, n)]).max(table[at(o, n + 1)])
+            }
+        }
+        let (mut o, mut n) = (0, 0);
+        while o < rows && n < columns {
+            let score = score(prefix + o, prefix + n);
+            if score > 0 && table[at(o, n)] == score + table[at(o + 1, n + 1)] {
+                pairs.push((prefix + o, prefix + n));
+                (o, n) = (o + 1, n + 1)
+            } else if table[at(o + 1, n)] >= table[at(o, n + 1)] {
+                o += 1
+            } else {
+                n += 1
+            }
+        }
+    }
+    pairs.extend((0..suffix).rev().map(|ix| (old - 1 - ix, new - 1 - ix)));
+    pairs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The kind of each change, with its old and new source.
+    fn diff<'s>(old: &'s str, new: &'s str) -> Vec<(ChangeKind, &'s str, &'s str)> {
+        let (old_ast, new_ast) = (Ast::new(old), Ast::new(new));
+        old_ast
+            .diff(&new_ast)
+            .into_iter()
+            .map(|it| (it.kind, &old[it.old_span.0], &new[it.new_span.0]))
+            .collect()
+    }
+
+    #[test]
+    fn equal() {
+        assert_eq!(diff("# a\n\nb *c*\n", "# a\n\nb *c*\n"), []);
+        // spans are ignored
+        assert_eq!(diff("# a\n\nb *c*\n", "#   a\n\n\n\nb *c*\n"), []);
+    }
+
+    #[test]
+    fn text() {
+        assert_eq!(
+            diff("one two three\n", "one four three\n"),
+            [(ChangeKind::Text, "two", "four")]
+        );
+        assert_eq!(
+            diff("one two three\n", "one two three four\n"),
+            [(ChangeKind::Text, "", " four")]
+        );
+    }
+
+    #[test]
+    fn insert_and_delete() {
+        let (old, new) = ("a\n\nb\n", "a\n\nc d\n\nb\n");
+        assert_eq!(diff(old, new), [(ChangeKind::Insert, "", "c d\n")]);
+        assert_eq!(diff(new, old), [(ChangeKind::Delete, "c d\n", "")]);
+        let Change { old_span, .. } = Ast::new(old).diff(&Ast::new(new)).remove(0);
+        assert_eq!(old_span, Span(2..2));
+    }
+
+    #[test]
+    fn moves() {
+        assert_eq!(
+            diff("a\n\nb\n\nc\n", "c\n\na\n\nb\n"),
+            [(ChangeKind::Move, "c\n", "c\n")]
+        );
+    }
+
+    #[test]
+    fn update() {
+        assert_eq!(
+            diff("# a\n", "## a\n"),
+            [(ChangeKind::Update, "# a\n", "## a\n")]
+        );
+        assert_eq!(
+            diff("[a](b)\n", "[a](c)\n"),
+            [(ChangeKind::Update, "[a](b)", "[a](c)")]
+        );
+    }
+
+    #[test]
+    fn dissimilar_nodes_are_replaced() {
+        assert_eq!(
+            diff("# a\n", "a\n"),
+            [
+                (ChangeKind::Delete, "# a\n", ""),
+                (ChangeKind::Insert, "", "a\n")
+            ]
+        );
+        assert_eq!(
+            diff("one two\n", "three four\n"),
+            [
+                (ChangeKind::Delete, "one two\n", ""),
+                (ChangeKind::Insert, "", "three four\n")
+            ]
+        );
+    }
+
+    #[test]
+    fn nested() {
+        assert_eq!(
+            diff("- a\n- b\n\n> c d\n", "- a\n- x\n- b\n\n> c e\n"),
+            [
+                (ChangeKind::Insert, "", "- x\n"),
+                (ChangeKind::Text, "d", "e")
+            ]
+        );
+    }
+
+    #[test]
+    fn text_spans_are_whole_nodes_unless_verbatim() {
+        let text = |text: &'static str, span| {
+            Ast(vec![Tree::Text(Spanned {
+                item: text.into(),
+                span: Span(span),
+            })])
+        };
+        let (old, new) = (text("a b", 10..13), text("a c", 20..23));
+        assert_eq!(old.diff(&new)[0].old_span, Span(12..13));
+        assert_eq!(old.diff(&new)[0].new_span, Span(22..23));
+        // like text with escapes
+        let (old, new) = (text("a b", 10..14), text("a c", 20..24));
+        assert_eq!(old.diff(&new)[0].old_span, Span(10..14));
+        assert_eq!(old.diff(&new)[0].new_span, Span(20..24));
+    }
+
+    #[test]
+    fn lcs_scores() {
+        assert_eq!(
+            lcs(3, 3, 1, |o, n| (o == n) as u32),
+            [(0, 0), (1, 1), (2, 2)]
+        );
+        // a full match is preferred over two partial ones
+        let score = |o: usize, n: usize| match (o, n) {
+            (1, 0) => 2,
+            (0, 0) | (2, 1) => 1,
+            _ => 0,
+        };
+        assert_eq!(lcs(3, 2, 2, score), [(1, 0), (2, 1)]);
+    }
+}
@@ -0,0 +1,207 @@
+//! Print an [`Ast`] as an indented tree, for the terminal.
+//!
+//! ```text
+//! heading level=1 1:1-2:1
+//! └── text "Hello" 1:3-1:8
+//! list 3:1-5:1
+//! ├── item 3:1-4:1
+//! │   └── text "one" 3:3-3:6
+//! └── item 4:1-5:1
+//!     ├── text "two " 4:3-4:7
+//!     └── link href=https://example.com 4:7-4:31
+//!         └── text "l" 4:8-4:9
+//! ```
+
+use std::fmt;
+
+use crate::{
+    line_index::{LineCol, LineIndex},
+    query::attribute,
+    Ast, Tree,
+};
+
+/// Attributes shown after the kind of a node, as named by [`crate::query`].
+const ATTRIBUTES: &[&str] = &[
+    "level",
+    "id",
+    "lang",
+    "start",
+    "kind",
+    "type",
+    "href",
+    "label",
+    "reference",
+    "checked",
+];
+
+/// Longer content is truncated.
+const MAX_CONTENT: usize = 40;
+
+/// Returned by [`Ast::display_tree`].
+#[derive(Debug, Clone)]
+pub struct DisplayTree<'t, 'a> {
+    ast: &'t Ast<'a>,
+    lines: LineIndex<'t>,
+    colors: bool,
+    max_depth: Option<usize>,
+}
+
+impl<'a> Ast<'a> {
+    /// Display this tree, with `line:col` spans into `source`.
+    pub fn display_tree<'t>(&'t self, source: &'t str) -> DisplayTree<'t, 'a> {
+        DisplayTree {
+            ast: self,
+            lines: LineIndex::new(source),
+            colors: false,
+            max_depth: None,
+        }
+    }
+}
+
+impl DisplayTree<'_, '_> {
+    /// Use ANSI escape codes to color the output.
+    pub fn colors(mut self, colors: bool) -> Self {
+        self.colors = colors;
+        self
+    }
+    /// Don't show the children of nodes deeper than `depth`,
+    /// where top-level nodes have depth `0`.
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = Some(depth);
+        self
+    }
+    fn paint(&self, f: &mut fmt::Formatter<'_>, code: &str, s: &dyn fmt::Display) -> fmt::Result {
+        match self.colors {
+            true => write!(f, "\x1b[{code}m{s}\x1b[0m"),
+            false => write!(f, "{s}"),
+        }
+    }
+    fn write_node(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        tree: &Tree<'_>,
+        prefix: &mut String,
+        depth: usize,
+    ) -> fmt::Result {
+        self.paint(f, "1;34", &tree.kind())?;
+        for name in ATTRIBUTES {
+            let Some(value) = attribute(tree, name) else {
+                continue;
+            };
+            if value.is_empty() || (*name == "type" && value == "inline") {
+                continue;
+            }
+            f.write_str(" ")?;
+            self.paint(f, "33", &format_args!("{name}={value}"))?
+        }
+        if let Some(content) = attribute(tree, "content") {
+            let mut truncated = content.chars().take(MAX_CONTENT).collect::<String>();
+            if truncated.len() < content.len() {
+                truncated.push('…')
+            }
+            f.write_str(" ")?;
+            self.paint(f, "32", &format_args!("{truncated:?}"))?
+        }
+        let span = tree.span();
+        let LineCol { line, column } = self.lines.line_col(span.0.start);
+        let end = self.lines.line_col(span.0.end);
+        f.write_str(" ")?;
+        self.paint(
+            f,
+            "2",
+            &format_args!("{line}:{column}-{}:{}", end.line, end.column),
+        )?;
+        let children = tree.stream().map_or(&[][..], |it| &it.0);
+        if self.max_depth.is_some_and(|max| depth >= max) && !children.is_empty() {
+            f.write_str(" ")?;
+            self.paint(f, "2", &format_args!("(+{})", children.len()))?;
+            return writeln!(f);
+        }
+        writeln!(f)?;
+        for (ix, child) in children.iter().enumerate() {
+            let last = ix + 1 == children.len();
+            f.write_str(prefix)?;
+            f.write_str(if last { "└── " } else { "├── " })?;
+            let len = prefix.len();
+            prefix.push_str(if last { "    " } else { "│   " });
+            self.write_node(f, child, prefix, depth + 1)?;
+            prefix.truncate(len)
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for DisplayTree<'_, '_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for tree in &self.ast.0 {
+            self.write_node(f, tree, &mut String::new(), 0)?
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = "# Hello\n\n- one\n- two [l](https://example.com)\n";
+
+    #[test]
+    fn tree() {
+        let ast = Ast::new(SOURCE);
+        assert_eq!(
+            ast.display_tree(SOURCE).to_string(),
+            "\
+heading level=1 1:1-2:1
+└── text \"Hello\" 1:3-1:8
+list 3:1-5:1
+├── item 3:1-4:1
+│   └── text \"one\" 3:3-3:6
+└── item 4:1-5:1
+    ├── text \"two \" 4:3-4:7
+    └── link href=https://example.com 4:7-4:31
+        └── text \"l\" 4:8-4:9
+"
+        );
+    }
+
+    #[test]
+    fn max_depth() {
+        let ast = Ast::new(SOURCE);
+        assert_eq!(
+            ast.display_tree(SOURCE).max_depth(1).to_string(),
+            "\
+heading level=1 1:1-2:1
+└── text \"Hello\" 1:3-1:8
+list 3:1-5:1
+├── item 3:1-4:1 (+1)
+└── item 4:1-5:1 (+2)
+"
+        );
+        assert_eq!(
+            ast.display_tree(SOURCE).max_depth(0).to_string(),
+            "heading level=1 1:1-2:1 (+1)\nlist 3:1-5:1 (+2)\n"
+        );
+    }
+
+    #[test]
+    fn long_content_is_truncated() {
+        let source = format!("{}é\n", "x".repeat(MAX_CONTENT + 10));
+        let shown = Ast::new(&source).display_tree(&source).to_string();
+        let truncated = format!("\"{}…\" 1:1-1:52", "x".repeat(MAX_CONTENT));
+        assert!(shown.contains(&truncated), "{shown}");
+    }
+
+    #[test]
+    fn colors() {
+        let source = "# a\n";
+        assert_eq!(
+            Ast::new(source)
+                .display_tree(source)
+                .colors(true)
+                .to_string(),
+            "\x1b[1;34mheading\x1b[0m \x1b[33mlevel=1\x1b[0m \x1b[2m1:1-2:1\x1b[0m\n\
+             └── \x1b[1;34mtext\x1b[0m \x1b[32m\"a\"\x1b[0m \x1b[2m1:3-1:4\x1b[0m\n"
+        );
+    }
+}
@@ -0,0 +1,263 @@
+//! Apply [`Edit`]s to source text, such as the fixes of lint [`Diagnostic`]s.
+//!
+//! Overlapping edits are resolved by sorting them by span, then replacement,
+//! and keeping each edit that doesn't overlap one already kept.
+
+use pulldown_cmark::Options;
+
+use crate::{
+    lint::{Diagnostic, Linter},
+    Ast, Edit,
+};
+
+/// Returned by [`apply`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Applied {
+    pub text: String,
+    /// Edits which overlapped another edit, were out of bounds,
+    /// or didn't fall on `char` boundaries.
+    pub skipped: Vec<Edit>,
+}
+
+/// Apply the non-overlapping `edits` to `source`, in a single pass.
+///
+/// Insertions at the same offset as another edit are considered overlapping,
+/// since their order would be ambiguous.
+pub fn apply(source: &str, edits: impl IntoIterator<Item = Edit>) -> Applied {
+    let (accepted, skipped) = resolve(source, edits.into_iter().map(|it| (it, ())).collect());
+    Applied {
+        text: splice(source, accepted.iter().map(|(it, _)| it)),
+        skipped: skipped.into_iter().map(|(it, _)| it).collect(),
+    }
+}
+
+/// Split `edits` into those to apply, in order, and those to skip.
+#[allow(clippy::type_complexity)]
+fn resolve<T>(source: &str, mut edits: Vec<(Edit, T)>) -> (Vec<(Edit, T)>, Vec<(Edit, T)>) {
+    edits.sort_by(|(left, _), (right, _)| {
+        (left.span.0.start, left.span.0.end, &left.replacement).cmp(&(
+            right.span.0.start,
+            right.span.0.end,
+            &right.replacement,
+        ))
+    });
+    let (mut accepted, mut skipped) = (Vec::<(Edit, T)>::new(), vec![]);
+    for (edit, t) in edits {
+        let range = &edit.span.0;
+        let valid = range.start <= range.end
+            && source.is_char_boundary(range.start)
+            && source.is_char_boundary(range.end);
+        let overlaps = accepted.last().is_some_and(|(last, _)| {
+            let last = &last.span.0;
+            range.start < last.end
+                || (range.start == last.start && (range.is_empty() || last.is_empty()))
+        });
+        match valid && !overlaps {
+            true => accepted.push((edit, t)),
+            false => skipped.push((edit, t)),
+        }
+    }
+    (accepted, skipped)
+}
+
+/// `edits` must be sorted and non-overlapping.
+fn splice<'e>(source: &str, edits: impl IntoIterator<Item = &'e Edit>) -> String {
+    let mut text = String::with_capacity(source.len());
+    let mut at = 0;
+    for Edit { span, replacement } in edits {
+        text.push_str(&source[at..span.0.start]);
+        text.push_str(replacement);
+        at = span.0.end
+    }
+    text.push_str(&source[at..]);
+    text
+}
+
+/// Returned by [`Fixer::fix`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fixed {
+    pub text: String,
+    /// The diagnostics whose fixes were applied.
+    ///
+    /// Spans refer to the text at the start of the round in which they were applied.
+    pub applied: Vec<Diagnostic>,
+    /// Fixable diagnostics that remain in [`Fixed::text`],
+    /// because their fixes conflicted or [`Fixer::max_rounds`] was reached.
+    pub skipped: Vec<Diagnostic>,
+    /// The number of rounds of fixes applied.
+    pub rounds: usize,
+}
+
+/// Repeatedly lints and applies fixes, until there are none left to apply.
+#[derive(Debug)]
+pub struct Fixer<'l> {
+    linter: &'l Linter,
+    options: Options,
+    max_rounds: usize,
+}
+
+impl<'l> Fixer<'l> {
+    pub fn new(linter: &'l Linter) -> Self {
+        Self {
+            linter,
+            options: Options::empty(),
+            max_rounds: 10,
+        }
+    }
+    /// The options to parse the text with.
+    pub fn options(mut self, options: Options) -> Self {
+        self.options = options;
+        self
+    }
+    /// Stop after applying fixes `max` times, even if there are fixes left.
+    pub fn max_rounds(mut self, max: usize) -> Self {
+        self.max_rounds = max;
+        self
+    }
+    pub fn fix(&self, source: &str) -> Fixed {
+        let mut text = source.to_owned();
+        let mut applied = vec![];
+        let mut rounds = 0;
+        let skipped = loop {
+            let ast = Ast::new_ext(&text, self.options);
+            let diagnostics = self.linter.lint(&ast, &text);
+            let fixes = diagnostics
+                .into_iter()
+                .filter_map(|it| Some((it.fix.clone()?, it)))
+                // fixes which change nothing would never converge
+                .filter(|(fix, _)| text.get(fix.span.0.clone()) != Some(&fix.replacement))
+                .collect::<Vec<_>>();
+            if rounds == self.max_rounds || fixes.is_empty() {
+                break fixes;
+            }
+            let (accepted, skipped) = resolve(&text, fixes);
+            if accepted.is_empty() {
+                break skipped;
+            }
+            text = splice(&text, accepted.iter().map(|(it, _)| it));
+            rounds += 1;
+            applied.extend(accepted.into_iter().map(|(_, it)| it));
+        };
+        Fixed {
+            text,
+            applied,
+            skipped: skipped.into_iter().map(|(_, it)| it).collect(),
+            rounds,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{line_index::LineIndex, lint::Rule, Span};
+
+    fn edit(span: std::ops::Range<usize>, replacement: &str) -> Edit {
+        Edit {
+            span: Span(span),
+            replacement: replacement.into(),
+        }
+    }
+
+    #[test]
+    fn apply_skips_conflicts() {
+        let source = "hello wörld";
+        let (start, end) = (5, 2);
+        let applied = apply(
+            source,
+            [
+                edit(12..12, "!"),
+                edit(0..5, "goodbye"),
+                edit(4..7, "overlaps"),
+                edit(3..3, "inserted"),
+                edit(7..8, "mid-char"),
+                edit(13..20, "out of bounds"),
+                edit(start..end, "backwards"),
+            ],
+        );
+        assert_eq!(applied.text, "goodbye wörld!");
+        assert_eq!(
+            applied
+                .skipped
+                .iter()
+                .map(|it| &*it.replacement)
+                .collect::<Vec<_>>(),
+            [
+                "inserted",
+                "overlaps",
+                "backwards",
+                "mid-char",
+                "out of bounds"
+            ]
+        );
+        // insertions at the same offset as another edit
+        let applied = apply("ab", [edit(1..1, "-"), edit(1..1, "+"), edit(1..2, "c")]);
+        assert_eq!(applied.text, "a+b");
+        assert_eq!(applied.skipped, [edit(1..1, "-"), edit(1..2, "c")]);
+    }
+
+    /// Replaces `a` with `b`, and `b` with `c`, or `x` with both `y` and `z`.
+    struct Step;
+
+    impl Rule for Step {
+        fn name(&self) -> &'static str {
+            "step"
+        }
+        fn description(&self) -> &'static str {
+            "step letters"
+        }
+        fn check(&self, _: &Ast<'_>, source: &str, _: &LineIndex<'_>, out: &mut Vec<Diagnostic>) {
+            for (ix, c) in source.char_indices() {
+                let replacements: &[&str] = match c {
+                    'a' => &["b"],
+                    'b' => &["c"],
+                    'x' => &["y", "z"],
+                    _ => &[],
+                };
+                for replacement in replacements {
+                    out.push(
+                        Diagnostic::new("step", Span(ix..ix + 1), "step")
+                            .with_fix(edit(ix..ix + 1, replacement)),
+                    )
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn fixer_rounds() {
+        let linter = Linter::empty().rule(Step);
+        let fixed = Fixer::new(&linter).fix("aab\n");
+        assert_eq!(
+            (&*fixed.text, fixed.rounds, fixed.applied.len()),
+            ("ccc\n", 2, 5)
+        );
+        assert!(fixed.skipped.is_empty());
+
+        let fixed = Fixer::new(&linter).max_rounds(1).fix("aab\n");
+        assert_eq!((&*fixed.text, fixed.rounds), ("bbc\n", 1));
+        assert_eq!(fixed.skipped.len(), 2);
+
+        // the fixes for `x` overlap, so only one is applied
+        let fixed = Fixer::new(&linter).fix("x\n");
+        assert_eq!(
+            (&*fixed.text, fixed.rounds, fixed.applied.len()),
+            ("y\n", 1, 1)
+        );
+    }
+
+    #[test]
+    fn fixer_with_core_rules() {
+        let linter = Linter::new();
+        let fixed =
+            Fixer::new(&linter).fix("# A.\n\n### B\n\n- one\n* two\n\nhttps://example.com\n");
+        assert_eq!(
+            fixed.text,
+            "# A\n\n## B\n\n- one\n- two\n\n<https://example.com>\n"
+        );
+        assert!(Ast::new(&fixed.text)
+            .lint(&fixed.text)
+            .iter()
+            .all(|it| it.fix.is_none()));
+    }
+}
@@ -0,0 +1,250 @@
+//! An index of the footnotes in an [`Ast`].
+//!
+//! [`Tree::FootnoteReference`]s and [`Tag::FootnoteDefinition`]s may occur in any order,
+//! and labels match case-insensitively.
+
+use std::collections::{HashMap, HashSet};
+
+use pulldown_cmark::{CowStr, Tag};
+
+use crate::{visit_mut::VisitMut, Ast, Group, Span, Spanned, Tree};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Footnote<'a> {
+    pub label: CowStr<'a>,
+    pub span: Span,
+    /// Suitable for [`Ast::get`].
+    pub path: Vec<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Footnotes<'a> {
+    /// In document order.
+    definitions: Vec<Footnote<'a>>,
+    /// In document order.
+    references: Vec<Footnote<'a>>,
+}
+
+fn key(label: &str) -> String {
+    label.to_lowercase()
+}
+
+impl<'a> Footnotes<'a> {
+    pub fn new(ast: &Ast<'a>) -> Self {
+        let mut this = Self::default();
+        this.collect(ast, &mut vec![]);
+        this
+    }
+    fn collect(&mut self, ast: &Ast<'a>, path: &mut Vec<usize>) {
+        for (ix, tree) in ast.0.iter().enumerate() {
+            path.push(ix);
+            match tree {
+                Tree::FootnoteReference(Spanned { item, span }) => self.references.push(Footnote {
+                    label: item.clone(),
+                    span: span.clone(),
+                    path: path.clone(),
+                }),
+                Tree::Group(Group {
+                    tag:
+                        Spanned {
+                            item: Tag::FootnoteDefinition(label),
+                            ..
+                        },
+                    ..
+                }) => self.definitions.push(Footnote {
+                    label: label.clone(),
+                    span: tree.span(),
+                    path: path.clone(),
+                }),
+                _ => {}
+            }
+            if let Some(stream) = tree.stream() {
+                self.collect(stream, path)
+            }
+            path.pop();
+        }
+    }
+    pub fn definitions(&self) -> &[Footnote<'a>] {
+        &self.definitions
+    }
+    pub fn references(&self) -> &[Footnote<'a>] {
+        &self.references
+    }
+    /// The first definition with this label.
+    pub fn definition(&self, label: &str) -> Option<&Footnote<'a>> {
+        let key = key(label);
+        self.definitions
+            .iter()
+            .find(|it| self::key(&it.label) == key)
+    }
+    pub fn references_to<'s>(&'s self, label: &str) -> impl Iterator<Item = &'s Footnote<'a>> {
+        let key = key(label);
+        self.references
+            .iter()
+            .filter(move |it| self::key(&it.label) == key)
+    }
+    /// References without a definition.
+    pub fn undefined(&self) -> Vec<&Footnote<'a>> {
+        let defined = self
+            .definitions
+            .iter()
+            .map(|it| key(&it.label))
+            .collect::<HashSet<_>>();
+        self.references
+            .iter()
+            .filter(|it| !defined.contains(&key(&it.label)))
+            .collect()
+    }
+    /// Definitions without any references.
+    pub fn unreferenced(&self) -> Vec<&Footnote<'a>> {
+        let referenced = self
+            .references
+            .iter()
+            .map(|it| key(&it.label))
+            .collect::<HashSet<_>>();
+        self.definitions
+            .iter()
+            .filter(|it| !referenced.contains(&key(&it.label)))
+            .collect()
+    }
+    /// Definitions with the same label as an earlier definition.
+    pub fn duplicates(&self) -> Vec<&Footnote<'a>> {
+        let mut seen = HashSet::new();
+        self.definitions
+            .iter()
+            .filter(|it| !seen.insert(key(&it.label)))
+            .collect()
+    }
+    /// The number of each label, in order of first reference,
+    /// followed by unreferenced definitions in document order.
+    pub fn numbering(&self) -> HashMap<String, usize> {
+        let mut numbers = HashMap::new();
+        for it in self.references.iter().chain(&self.definitions) {
+            let next = numbers.len() + 1;
+            numbers.entry(key(&it.label)).or_insert(next);
+        }
+        numbers
+    }
+}
+
+impl<'a> Ast<'a> {
+    pub fn footnotes(&self) -> Footnotes<'a> {
+        Footnotes::new(self)
+    }
+    /// Relabel footnotes as `1`, `2`, ... in order of first reference
+    /// (see [`Footnotes::numbering`]), and move all definitions to the end of
+    /// the document in that order.
+    pub fn renumber_footnotes(&mut self) {
+        struct Relabel(HashMap<String, usize>);
+        impl<'a> VisitMut<'a> for Relabel {
+            fn visit_footnote_reference_mut(&mut self, node: &mut Spanned<CowStr<'a>>) {
+                if let Some(n) = self.0.get(&key(&node.item)) {
+                    node.item = n.to_string().into()
+                }
+            }
+            fn visit_footnote_definition_mut(
+                &mut self,
+                node: &mut CowStr<'a>,
+                stream: &mut Ast<'a>,
+                _: &mut Span,
+                _: &mut Span,
+            ) {
+                if let Some(n) = self.0.get(&key(node)) {
+                    *node = n.to_string().into()
+                }
+                self.visit_ast_mut(stream)
+            }
+        }
+
+        fn take_definitions<'a>(ast: &mut Ast<'a>, definitions: &mut Vec<Group<'a>>) {
+            for tree in std::mem::take(&mut ast.0) {
+                match tree {
+                    Tree::Group(
+                        group @ Group {
+                            tag:
+                                Spanned {
+                                    item: Tag::FootnoteDefinition(_),
+                                    ..
+                                },
+                            ..
+                        },
+                    ) => definitions.push(group),
+                    Tree::Group(mut group) => {
+                        take_definitions(&mut group.stream, definitions);
+                        ast.0.push(Tree::Group(group))
+                    }
+                    other => ast.0.push(other),
+                }
+            }
+        }
+
+        Relabel(self.footnotes().numbering()).visit_ast_mut(self);
+        let mut definitions = vec![];
+        take_definitions(self, &mut definitions);
+        // stable, so duplicates stay in document order
+        definitions.sort_by_key(|it| match &it.tag.item {
+            Tag::FootnoteDefinition(label) => label.parse::<usize>().unwrap_or(usize::MAX),
+            _ => unreachable!(),
+        });
+        self.0.extend(definitions.into_iter().map(Tree::Group))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pulldown_cmark::Options;
+
+    use super::*;
+
+    const DOC: &str = "\
+a[^b] c[^a] d[^B] e[^x]
+
+[^a]: first
+
+> [^b]: second
+
+[^c]: unreferenced
+
+[^a]: duplicate
+";
+
+    fn labels(footnotes: Vec<&Footnote<'_>>) -> Vec<String> {
+        footnotes.iter().map(|it| it.label.to_string()).collect()
+    }
+
+    #[test]
+    fn index() {
+        let ast = Ast::new_ext(DOC, Options::ENABLE_OLD_FOOTNOTES);
+        let footnotes = ast.footnotes();
+        assert_eq!(footnotes.references().len(), 4);
+        assert_eq!(footnotes.definitions().len(), 4);
+        let b = footnotes.definition("B").unwrap();
+        assert_eq!(b.path, [2, 0]);
+        assert_eq!(&DOC[b.span.0.clone()], "[^b]: second\n");
+        assert_eq!(footnotes.references_to("b").count(), 2);
+        assert_eq!(labels(footnotes.undefined()), ["x"]);
+        assert_eq!(labels(footnotes.unreferenced()), ["c"]);
+        assert_eq!(
+            footnotes
+                .duplicates()
+                .iter()
+                .map(|it| &it.path)
+                .collect::<Vec<_>>(),
+            [&vec![4]]
+        );
+        let numbering = footnotes.numbering();
+        assert_eq!(["b", "a", "x", "c"].map(|it| numbering[it]), [1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn renumber() {
+        let source = "a[^z] b[^y] c[^z]\n\n[^y]: Y\n\n[^z]: Z\n\nd\n";
+        let mut ast = Ast::new_ext(source, Options::ENABLE_FOOTNOTES);
+        ast.renumber_footnotes();
+        let expected = Ast::new_ext(
+            "a[^1] b[^2] c[^1]\n\nd\n\n[^1]: Z\n\n[^2]: Y\n",
+            Options::ENABLE_FOOTNOTES,
+        );
+        assert!(ast.structurally_eq(&expected), "{ast:#?}");
+    }
+}
@@ -0,0 +1,248 @@
+//! Front matter, parsed by [`pulldown_cmark`] as a [`Tag::MetadataBlock`]
+//! when [`Options::ENABLE_YAML_STYLE_METADATA_BLOCKS`] or
+//! [`Options::ENABLE_PLUSES_DELIMITED_METADATA_BLOCKS`] are set.
+//!
+//! Deserializing YAML (`---` delimited) and TOML (`+++` delimited) front matter
+//! requires the `yaml` and `toml` features respectively.
+//!
+//! [`Options::ENABLE_YAML_STYLE_METADATA_BLOCKS`]: pulldown_cmark::Options::ENABLE_YAML_STYLE_METADATA_BLOCKS
+//! [`Options::ENABLE_PLUSES_DELIMITED_METADATA_BLOCKS`]: pulldown_cmark::Options::ENABLE_PLUSES_DELIMITED_METADATA_BLOCKS
+
+use std::fmt;
+
+use pulldown_cmark::{CowStr, MetadataBlockKind, Tag};
+
+use crate::{Ast, Group, Span, Spanned, Tree};
+
+/// The contents of a metadata block at the start of a document.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrontMatter<'a> {
+    pub kind: MetadataBlockKind,
+    /// The raw text between the delimiters.
+    pub text: CowStr<'a>,
+    /// The span of [`FrontMatter::text`].
+    pub span: Span,
+    /// The whole block, including delimiters.
+    pub block_span: Span,
+}
+
+/// An error (de)serializing front matter.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrontMatterError {
+    pub message: String,
+    /// Where in the document the error occurred, if known.
+    pub span: Option<Span>,
+}
+
+impl fmt::Display for FrontMatterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for FrontMatterError {}
+
+impl<'a> Ast<'a> {
+    /// The metadata block at the start of the document, if any.
+    pub fn front_matter_block(&self) -> Option<FrontMatter<'a>> {
+        let Tree::Group(Group {
+            tag:
+                Spanned {
+                    item: Tag::MetadataBlock(kind),
+                    ..
+                },
+            stream,
+            ..
+        }) = self.0.first()?
+        else {
+            return None;
+        };
+        let block_span = self.0[0].span();
+        let (text, span) = match stream.0.as_slice() {
+            [Tree::Text(Spanned { item, span })] => (item.clone(), span.clone()),
+            // the block's contents are never split, but be defensive
+            _ => (
+                CowStr::from(stream.plain_text()),
+                Span(block_span.0.start..block_span.0.start),
+            ),
+        };
+        Some(FrontMatter {
+            kind: *kind,
+            text,
+            span,
+            block_span,
+        })
+    }
+}
+
+#[cfg(any(feature = "yaml", feature = "toml"))]
+mod serde_impl {
+    use pulldown_cmark::Options;
+    use serde::{de::DeserializeOwned, Serialize};
+
+    use super::*;
+    use crate::Edit;
+
+    impl FrontMatter<'_> {
+        /// Deserialize the front matter, according to its [`MetadataBlockKind`].
+        pub fn deserialize<T: DeserializeOwned>(&self) -> Result<T, FrontMatterError> {
+            match self.kind {
+                #[cfg(feature = "yaml")]
+                MetadataBlockKind::YamlStyle => {
+                    serde_yaml::from_str(&self.text).map_err(|e| FrontMatterError {
+                        span: e.location().map(|it| {
+                            let offset = self.span.0.start + it.index().min(self.text.len());
+                            Span(offset..offset)
+                        }),
+                        message: e.to_string(),
+                    })
+                }
+                #[cfg(feature = "toml")]
+                MetadataBlockKind::PlusesStyle => {
+                    toml::from_str(&self.text).map_err(|e| FrontMatterError {
+                        span: e.span().map(|it| {
+                            Span(self.span.0.start + it.start..self.span.0.start + it.end)
+                        }),
+                        message: e.message().into(),
+                    })
+                }
+                #[allow(unreachable_patterns)]
+                kind => Err(FrontMatterError {
+                    message: format!("support for {kind:?} front matter is not enabled"),
+                    span: Some(self.block_span.clone()),
+                }),
+            }
+        }
+        /// Serialize `value` in the same format as this front matter.
+        pub fn serialize<T: Serialize>(&self, value: &T) -> Result<String, FrontMatterError> {
+            let err = |message: String| FrontMatterError {
+                message,
+                span: None,
+            };
+            let mut text = match self.kind {
+                #[cfg(feature = "yaml")]
+                MetadataBlockKind::YamlStyle => {
+                    serde_yaml::to_string(value).map_err(|e| err(e.to_string()))?
+                }
+                #[cfg(feature = "toml")]
+                MetadataBlockKind::PlusesStyle => {
+                    toml::to_string(value).map_err(|e| err(e.to_string()))?
+                }
+                #[allow(unreachable_patterns)]
+                kind => {
+                    return Err(err(format!(
+                        "support for {kind:?} front matter is not enabled"
+                    )))
+                }
+            };
+            if !text.ends_with('\n') {
+                text.push('\n')
+            }
+            Ok(text)
+        }
+        /// An [`Edit`] to the source replacing this front matter with `value`.
+        pub fn edit<T: Serialize>(&self, value: &T) -> Result<Edit, FrontMatterError> {
+            Ok(Edit {
+                span: self.span.clone(),
+                replacement: self.serialize(value)?,
+            })
+        }
+    }
+
+    impl Ast<'_> {
+        /// Deserialize the front matter, if there is any.
+        pub fn front_matter<T: DeserializeOwned>(&self) -> Result<Option<T>, FrontMatterError> {
+            self.front_matter_block()
+                .map(|it| it.deserialize())
+                .transpose()
+        }
+        /// Replace the contents of the existing front matter in `source`,
+        /// which this tree was parsed from with `options`, with `value`.
+        ///
+        /// Returns the new source, which this tree is [reparsed](Ast::reparse_ext) to match,
+        /// or `None` if there is no front matter.
+        pub fn set_front_matter<T: Serialize>(
+            &mut self,
+            source: &str,
+            value: &T,
+            options: Options,
+        ) -> Result<Option<String>, FrontMatterError> {
+            let Some(block) = self.front_matter_block() else {
+                return Ok(None);
+            };
+            let edit = block.edit(value)?;
+            let Span(range) = &edit.span;
+            let new_source = [
+                &source[..range.start],
+                &edit.replacement,
+                &source[range.end..],
+            ]
+            .concat();
+            self.reparse_ext(&edit, &new_source, options);
+            Ok(Some(new_source))
+        }
+    }
+}
+
+#[cfg(all(test, feature = "yaml", feature = "toml"))]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use pulldown_cmark::Options;
+
+    use super::*;
+
+    const OPTIONS: Options = Options::ENABLE_YAML_STYLE_METADATA_BLOCKS
+        .union(Options::ENABLE_PLUSES_DELIMITED_METADATA_BLOCKS);
+
+    type Map = BTreeMap<String, String>;
+
+    #[test]
+    fn round_trip() {
+        for source in [
+            "---\ntitle: Hello\n---\n\n# Hello\n",
+            "+++\ntitle = \"Hello\"\n+++\n\n# Hello\n",
+        ] {
+            let mut ast = Ast::new_ext(source, OPTIONS);
+            let mut value = ast.front_matter::<Map>().unwrap().unwrap();
+            assert_eq!(value["title"], "Hello");
+
+            value.insert("author".into(), "me".into());
+            let new = ast
+                .set_front_matter(source, &value, OPTIONS)
+                .unwrap()
+                .unwrap();
+            assert!(new.ends_with("\n\n# Hello\n"));
+            assert_eq!(ast, Ast::new_ext(&new, OPTIONS));
+            assert_eq!(ast.front_matter::<Map>().unwrap().unwrap(), value);
+        }
+    }
+
+    #[test]
+    fn no_front_matter() {
+        let mut ast = Ast::new_ext("# Hello\n", OPTIONS);
+        assert_eq!(ast.front_matter::<Map>(), Ok(None));
+        assert_eq!(
+            ast.set_front_matter("# Hello\n", &Map::new(), OPTIONS),
+            Ok(None)
+        );
+    }
+
+    #[test]
+    fn error_spans() {
+        let source = "---\ntitle: [\n---\n";
+        let err = Ast::new_ext(source, OPTIONS)
+            .front_matter::<Map>()
+            .unwrap_err();
+        let Span(span) = err.span.unwrap();
+        assert!((4..source.len() - 4).contains(&span.start), "{span:?}");
+
+        let source = "+++\ntitle = \"a\"\nbad\n+++\n";
+        let err = Ast::new_ext(source, OPTIONS)
+            .front_matter::<Map>()
+            .unwrap_err();
+        let Span(span) = err.span.unwrap();
+        let line = source.find("bad").unwrap()..source.find("\n+++").unwrap() + 1;
+        assert!(line.contains(&span.start), "{span:?}");
+    }
+}
@@ -0,0 +1,197 @@
+//! Stable identifiers for the nodes of an [`Ast`].
+//!
+//! [`NodeId`]s are assigned in document (pre-)order, and resolved through an
+//! [`AstIndex`], which stores the path to each node rather than a reference,
+//! so side tables keyed by [`NodeId`] don't hold a borrow of the [`Ast`].
+
+use crate::{Ast, Tree};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NodeId(usize);
+
+impl NodeId {
+    /// The position of this node in document order.
+    pub fn index(self) -> usize {
+        self.0
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+    path: Vec<usize>,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+}
+
+/// Maps [`NodeId`]s to paths into an [`Ast`].
+///
+/// The index is a snapshot: it must be rebuilt if the shape of the [`Ast`] changes.
+#[derive(Debug, Clone, Default)]
+pub struct AstIndex {
+    entries: Vec<Entry>,
+    roots: Vec<NodeId>,
+}
+
+impl AstIndex {
+    pub fn new(ast: &Ast<'_>) -> Self {
+        let mut this = Self::default();
+        let mut path = vec![];
+        this.roots = this.index_ast(ast, None, &mut path);
+        this
+    }
+    fn index_ast(
+        &mut self,
+        ast: &Ast<'_>,
+        parent: Option<NodeId>,
+        path: &mut Vec<usize>,
+    ) -> Vec<NodeId> {
+        let Ast(trees) = ast;
+        let mut ids = vec![];
+        for (ix, tree) in trees.iter().enumerate() {
+            path.push(ix);
+            let id = NodeId(self.entries.len());
+            self.entries.push(Entry {
+                path: path.clone(),
+                parent,
+                children: vec![],
+            });
+            if let Some(stream) = tree.stream() {
+                let children = self.index_ast(stream, Some(id), path);
+                self.entries[id.0].children = children;
+            }
+            path.pop();
+            ids.push(id);
+        }
+        ids
+    }
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+    /// All ids, in document order.
+    pub fn ids(&self) -> impl Iterator<Item = NodeId> {
+        (0..self.entries.len()).map(NodeId)
+    }
+    /// The top-level nodes of the [`Ast`].
+    pub fn roots(&self) -> &[NodeId] {
+        &self.roots
+    }
+    /// The path to this node, suitable for [`Ast::get`].
+    pub fn path(&self, id: NodeId) -> Option<&[usize]> {
+        self.entries.get(id.0).map(|it| it.path.as_slice())
+    }
+    /// The id of the node at `path`, if any.
+    pub fn id(&self, path: &[usize]) -> Option<NodeId> {
+        // pre-order ids are sorted by path
+        self.entries
+            .binary_search_by(|it| it.path.as_slice().cmp(path))
+            .ok()
+            .map(NodeId)
+    }
+    pub fn get<'ast, 'a>(&self, ast: &'ast Ast<'a>, id: NodeId) -> Option<&'ast Tree<'a>> {
+        ast.get(self.path(id)?)
+    }
+    pub fn get_mut<'ast, 'a>(
+        &self,
+        ast: &'ast mut Ast<'a>,
+        id: NodeId,
+    ) -> Option<&'ast mut Tree<'a>> {
+        ast.get_mut(self.path(id)?)
+    }
+    pub fn parent(&self, id: NodeId) -> Option<NodeId> {
+        self.entries.get(id.0)?.parent
+    }
+    pub fn children(&self, id: NodeId) -> &[NodeId] {
+        match self.entries.get(id.0) {
+            Some(it) => &it.children,
+            None => &[],
+        }
+    }
+    fn siblings(&self, id: NodeId) -> &[NodeId] {
+        match self.parent(id) {
+            Some(parent) => self.children(parent),
+            None => &self.roots,
+        }
+    }
+    pub fn next_sibling(&self, id: NodeId) -> Option<NodeId> {
+        let ix = *self.entries.get(id.0)?.path.last()?;
+        self.siblings(id).get(ix + 1).copied()
+    }
+    pub fn prev_sibling(&self, id: NodeId) -> Option<NodeId> {
+        let ix = *self.entries.get(id.0)?.path.last()?;
+        self.siblings(id).get(ix.checked_sub(1)?).copied()
+    }
+    /// The ancestors of this node, nearest first.
+    pub fn ancestors(&self, id: NodeId) -> impl Iterator<Item = NodeId> + '_ {
+        let mut cur = self.parent(id);
+        std::iter::from_fn(move || {
+            let it = cur?;
+            cur = self.parent(it);
+            Some(it)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ids_are_assigned_in_document_order() {
+        let ast = Ast::new("# a\n\n- b\n- c\n");
+        let index = ast.index();
+        let kinds = index
+            .ids()
+            .map(|id| index.get(&ast, id).unwrap().kind())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            ["heading", "text", "list", "item", "text", "item", "text"]
+        );
+        assert_eq!(index.len(), 7);
+        assert_eq!(index.roots(), [NodeId(0), NodeId(2)]);
+    }
+
+    #[test]
+    fn paths_and_ids_round_trip() {
+        let ast = Ast::new("> a *b*\n\nc\n");
+        let index = ast.index();
+        for id in index.ids() {
+            assert_eq!(index.id(index.path(id).unwrap()), Some(id));
+        }
+        assert_eq!(index.path(NodeId(4)), Some(&[0, 0, 1, 0][..]));
+        assert_eq!(index.id(&[0, 5]), None);
+        assert_eq!(index.path(NodeId(100)), None);
+    }
+
+    #[test]
+    fn navigation() {
+        let ast = Ast::new("- a\n- b\n- c\n");
+        let index = ast.index();
+        let list = index.roots()[0];
+        let items = index.children(list);
+        assert_eq!(items.len(), 3);
+        assert_eq!(index.next_sibling(items[0]), Some(items[1]));
+        assert_eq!(index.prev_sibling(items[1]), Some(items[0]));
+        assert_eq!(index.prev_sibling(items[0]), None);
+        assert_eq!(index.next_sibling(items[2]), None);
+        let text = index.children(items[2])[0];
+        assert_eq!(index.parent(text), Some(items[2]));
+        assert_eq!(index.ancestors(text).collect::<Vec<_>>(), [items[2], list]);
+        assert_eq!(index.parent(list), None);
+        assert!(index.children(text).is_empty());
+    }
+
+    #[test]
+    fn get_mut() {
+        let mut ast = Ast::new("a\n");
+        let index = ast.index();
+        let text = index.children(index.roots()[0])[0];
+        if let Some(Tree::Text(text)) = index.get_mut(&mut ast, text) {
+            text.item = "b".into()
+        }
+        assert_eq!(ast.plain_text(), "b");
+    }
+}
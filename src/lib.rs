@@ -1,10 +1,41 @@
 //! An abstract syntax tree for [`pulldown_cmark`].
 
-use pulldown_cmark::{BrokenLinkCallback, CowStr, Event, Options, Parser, Tag, TagEnd};
+use pulldown_cmark::{
+    BrokenLinkCallback, CodeBlockKind, CowStr, Event, LinkType, OffsetIter, Options, Parser, Tag,
+    TagEnd,
+};
 use std::{iter, ops::Range};
+#[cfg(test)]
+mod corpus;
+pub mod cursor;
+pub mod diff;
+pub mod display;
+pub mod fix;
+pub mod fmt;
+pub mod footnotes;
+pub mod front_matter;
+pub mod index;
+pub mod line_index;
+pub mod links;
+pub mod lint;
+#[cfg(feature = "mdast")]
+pub mod mdast;
+pub mod merge;
+pub mod normalize;
+#[cfg(feature = "pandoc")]
+pub mod pandoc;
+pub mod query;
+pub mod references;
+pub mod reparse;
+pub mod report;
+#[cfg(feature = "serde")]
+mod serde_impl;
+pub mod sexpr;
+pub mod structural;
 pub mod visit_mut;
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Spanned<T> {
     pub item: T,
     pub span: Span,
@@ -18,6 +49,23 @@ impl<T> Spanned<T> {
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Span(pub Range<usize>);
 
+/// A replacement of the source text at `span`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Edit {
+    pub span: Span,
+    pub replacement: String,
+}
+
+/// A reference link or image whose label has no definition,
+/// which [`pulldown_cmark`] leaves as text.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BrokenLink<'a> {
+    /// The label that was looked up, e.g `bar` in `[foo][bar]`.
+    pub reference: CowStr<'a>,
+    pub link_type: LinkType,
+    pub span: Span,
+}
+
 #[derive(Default, Debug, Clone, PartialEq)]
 pub struct Ast<'a>(pub Vec<Tree<'a>>);
 
@@ -32,6 +80,41 @@ impl<'a> IntoIterator for Ast<'a> {
     }
 }
 
+/// A node in an [`Ast`].
+///
+/// # Serialization
+/// With the `serde` feature, [`Ast`]s serialize as a sequence of nodes.
+/// Each node is a map with a kebab-case `"type"` (see [`Tree::kind`]),
+/// and a `"span"` of `[start, end]` byte offsets.
+///
+/// - [`Tree::Group`]s have an `"end_span"`, a sequence of `"children"`,
+///   and the fields of their [`Tag`]:
+///   - `heading`: `"level"` (`1`-`6`), `"id"` (or `null`), `"classes"`,
+///     and `"attrs"` as `[key, value-or-null]` pairs.
+///   - `block-quote`: `"kind"`, one of `"note"`, `"tip"`, `"important"`, `"warning"`,
+///     `"caution"` or `null`.
+///   - `code-block`: `"info"`, which is `null` for indented code blocks.
+///   - `list`: `"start"`, which is `null` for unordered lists.
+///   - `footnote-definition`: `"label"`.
+///   - `table`: `"alignments"`, each one of `"none"`, `"left"`, `"center"` or `"right"`.
+///   - `link` and `image`: `"link_type"` (kebab-case, e.g `"reference-unknown"`),
+///     `"dest_url"`, `"title"` and `"id"`.
+///   - `metadata-block`: `"kind"`, either `"yaml"` or `"toml"`.
+/// - `text`, `code`, `html`, `inline-html`, `inline-math` and `display-math`
+///   have a `"value"`.
+/// - `footnote-reference` has a `"label"`.
+/// - `task-list-marker` has a boolean `"checked"`.
+/// - `broken-link` has a `"reference"`, a `"link_type"` and `"children"`.
+///
+/// ```json
+/// [{"type": "heading", "level": 1, "id": null, "classes": [], "attrs": [],
+///   "span": [0, 8], "end_span": [0, 8],
+///   "children": [{"type": "text", "value": "Hello", "span": [2, 7]}]}]
+/// ```
+///
+/// Strings are borrowed from the input where possible:
+/// use [`Ast::into_static`] to detach the result,
+/// or `Ast::deserialize_owned` where the input doesn't outlive it, like a reader.
 #[derive(Debug, Clone, PartialEq)]
 pub enum Tree<'a> {
     Group(Group<'a>),
@@ -58,6 +141,216 @@ pub enum Tree<'a> {
     InlineMath(Spanned<CowStr<'a>>),
     /// A display math environment node.
     DisplayMath(Spanned<CowStr<'a>>),
+    /// The text of a [`BrokenLink`].
+    /// Only created by [`Ast::mark_broken_links`].
+    BrokenLink(BrokenLink<'a>, Ast<'a>),
+}
+
+impl<'a> Tree<'a> {
+    /// The children of this node, if it is a [`Tree::Group`].
+    pub fn stream(&self) -> Option<&Ast<'a>> {
+        match self {
+            Tree::Group(Group { stream, .. }) | Tree::BrokenLink(_, stream) => Some(stream),
+            _ => None,
+        }
+    }
+    pub fn stream_mut(&mut self) -> Option<&mut Ast<'a>> {
+        match self {
+            Tree::Group(Group { stream, .. }) | Tree::BrokenLink(_, stream) => Some(stream),
+            _ => None,
+        }
+    }
+}
+
+impl Tree<'_> {
+    /// The span of the whole node, including both ends of a [`Tree::Group`].
+    pub fn span(&self) -> Span {
+        match self {
+            Tree::Group(Group { tag, end_span, .. }) => Span(tag.span.0.start..end_span.0.end),
+            Tree::Text(Spanned { span, .. })
+            | Tree::Code(Spanned { span, .. })
+            | Tree::Html(Spanned { span, .. })
+            | Tree::InlineHtml(Spanned { span, .. })
+            | Tree::FootnoteReference(Spanned { span, .. })
+            | Tree::TaskListMarker(Spanned { span, .. })
+            | Tree::InlineMath(Spanned { span, .. })
+            | Tree::DisplayMath(Spanned { span, .. })
+            | Tree::SoftBreak(span)
+            | Tree::HardBreak(span)
+            | Tree::Rule(span)
+            | Tree::BrokenLink(BrokenLink { span, .. }, _) => span.clone(),
+        }
+    }
+    /// A short, kebab-case name for this kind of node, e.g `"heading"` or `"soft-break"`.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Tree::Group(Group { tag, .. }) => tag_kind(&tag.item),
+            Tree::Text(_) => "text",
+            Tree::Code(_) => "code",
+            Tree::Html(_) => "html",
+            Tree::InlineHtml(_) => "inline-html",
+            Tree::FootnoteReference(_) => "footnote-reference",
+            Tree::SoftBreak(_) => "soft-break",
+            Tree::HardBreak(_) => "hard-break",
+            Tree::Rule(_) => "rule",
+            Tree::TaskListMarker(_) => "task-list-marker",
+            Tree::InlineMath(_) => "inline-math",
+            Tree::DisplayMath(_) => "display-math",
+            Tree::BrokenLink(..) => "broken-link",
+        }
+    }
+    /// Whether this is a block-level node, rather than inline content.
+    pub fn is_block(&self) -> bool {
+        match self {
+            Tree::Group(Group { tag, .. }) => !matches!(
+                tag.item,
+                Tag::Emphasis
+                    | Tag::Strong
+                    | Tag::Strikethrough
+                    | Tag::Link { .. }
+                    | Tag::Image { .. }
+                    | Tag::TableCell
+            ),
+            Tree::Rule(_) => true,
+            _ => false,
+        }
+    }
+    /// The textual content of this node, without any markup or HTML.
+    pub fn plain_text(&self) -> String {
+        let mut buf = String::new();
+        self.push_plain_text(&mut buf);
+        buf
+    }
+    /// Take ownership of all borrowed strings.
+    pub fn into_static(self) -> Tree<'static> {
+        fn spanned(Spanned { item, span }: Spanned<CowStr<'_>>) -> Spanned<CowStr<'static>> {
+            Spanned {
+                item: own(item),
+                span,
+            }
+        }
+        match self {
+            Tree::Group(Group {
+                tag: Spanned { item, span },
+                stream,
+                end_span,
+            }) => Tree::Group(Group {
+                tag: Spanned {
+                    item: own_tag(item),
+                    span,
+                },
+                stream: stream.into_static(),
+                end_span,
+            }),
+            Tree::Text(it) => Tree::Text(spanned(it)),
+            Tree::Code(it) => Tree::Code(spanned(it)),
+            Tree::Html(it) => Tree::Html(spanned(it)),
+            Tree::InlineHtml(it) => Tree::InlineHtml(spanned(it)),
+            Tree::FootnoteReference(it) => Tree::FootnoteReference(spanned(it)),
+            Tree::SoftBreak(span) => Tree::SoftBreak(span),
+            Tree::HardBreak(span) => Tree::HardBreak(span),
+            Tree::Rule(span) => Tree::Rule(span),
+            Tree::TaskListMarker(it) => Tree::TaskListMarker(it),
+            Tree::InlineMath(it) => Tree::InlineMath(spanned(it)),
+            Tree::DisplayMath(it) => Tree::DisplayMath(spanned(it)),
+            Tree::BrokenLink(
+                BrokenLink {
+                    reference,
+                    link_type,
+                    span,
+                },
+                stream,
+            ) => Tree::BrokenLink(
+                BrokenLink {
+                    reference: own(reference),
+                    link_type,
+                    span,
+                },
+                stream.into_static(),
+            ),
+        }
+    }
+    fn push_plain_text(&self, buf: &mut String) {
+        match self {
+            Tree::Group(Group { stream, .. }) | Tree::BrokenLink(_, stream) => {
+                stream.push_plain_text(buf)
+            }
+            Tree::Text(Spanned { item, .. })
+            | Tree::Code(Spanned { item, .. })
+            | Tree::InlineMath(Spanned { item, .. })
+            | Tree::DisplayMath(Spanned { item, .. }) => buf.push_str(item),
+            Tree::SoftBreak(_) => buf.push(' '),
+            Tree::HardBreak(_) => buf.push('\n'),
+            Tree::Html(_)
+            | Tree::InlineHtml(_)
+            | Tree::FootnoteReference(_)
+            | Tree::Rule(_)
+            | Tree::TaskListMarker(_) => {}
+        }
+    }
+}
+
+fn own(s: CowStr<'_>) -> CowStr<'static> {
+    s.into_string().into()
+}
+
+fn own_tag(tag: Tag<'_>) -> Tag<'static> {
+    match tag {
+        Tag::Paragraph => Tag::Paragraph,
+        Tag::Heading {
+            level,
+            id,
+            classes,
+            attrs,
+        } => Tag::Heading {
+            level,
+            id: id.map(own),
+            classes: classes.into_iter().map(own).collect(),
+            attrs: attrs
+                .into_iter()
+                .map(|(k, v)| (own(k), v.map(own)))
+                .collect(),
+        },
+        Tag::BlockQuote(kind) => Tag::BlockQuote(kind),
+        Tag::CodeBlock(CodeBlockKind::Indented) => Tag::CodeBlock(CodeBlockKind::Indented),
+        Tag::CodeBlock(CodeBlockKind::Fenced(info)) => {
+            Tag::CodeBlock(CodeBlockKind::Fenced(own(info)))
+        }
+        Tag::HtmlBlock => Tag::HtmlBlock,
+        Tag::List(start) => Tag::List(start),
+        Tag::Item => Tag::Item,
+        Tag::FootnoteDefinition(label) => Tag::FootnoteDefinition(own(label)),
+        Tag::Table(alignments) => Tag::Table(alignments),
+        Tag::TableHead => Tag::TableHead,
+        Tag::TableRow => Tag::TableRow,
+        Tag::TableCell => Tag::TableCell,
+        Tag::Emphasis => Tag::Emphasis,
+        Tag::Strong => Tag::Strong,
+        Tag::Strikethrough => Tag::Strikethrough,
+        Tag::Link {
+            link_type,
+            dest_url,
+            title,
+            id,
+        } => Tag::Link {
+            link_type,
+            dest_url: own(dest_url),
+            title: own(title),
+            id: own(id),
+        },
+        Tag::Image {
+            link_type,
+            dest_url,
+            title,
+            id,
+        } => Tag::Image {
+            link_type,
+            dest_url: own(dest_url),
+            title: own(title),
+            id: own(id),
+        },
+        Tag::MetadataBlock(kind) => Tag::MetadataBlock(kind),
+    }
 }
 
 impl<'a> IntoIterator for Tree<'a> {
@@ -85,6 +378,7 @@ impl<'a> IntoIterator for Tree<'a> {
             Tree::TaskListMarker(Spanned { item, span }) => once(Event::TaskListMarker(item), span),
             Tree::InlineMath(Spanned { item, span }) => once(Event::InlineMath(item), span),
             Tree::DisplayMath(Spanned { item, span }) => once(Event::DisplayMath(item), span),
+            Tree::BrokenLink(_, stream) => stream.into_iter(),
         }
     }
 }
@@ -134,20 +428,134 @@ impl<'a> Ast<'a> {
         options: Options,
         broken_link_callback: Option<C>,
     ) -> Self {
-        match Self::from_events(
-            &mut Parser::new_with_broken_link_callback(text, options, broken_link_callback)
-                .into_offset_iter()
-                .map(|(item, range)| Spanned {
-                    item,
-                    span: Span(range),
-                }),
-        ) {
-            Ok((this, None)) => this,
+        Self::parse(text, options, broken_link_callback, |_| ()).0
+    }
+    /// Also collect the document's link reference definitions,
+    /// which are otherwise resolved by [`pulldown_cmark`] and don't appear in the tree.
+    pub fn new_with_reference_definitions(
+        text: &'a str,
+        options: Options,
+    ) -> (Self, references::ReferenceDefinitions<'a>) {
+        let (this, definitions) = Self::parse(text, options, Some(|_| None), |iter| {
+            references::ReferenceDefinitions::from_parser(text, iter.reference_definitions())
+        });
+        let definitions = definitions.with_duplicates(text, &this);
+        (this, definitions)
+    }
+    /// Also collect the reference links and images which couldn't be resolved.
+    pub fn new_with_broken_links(text: &'a str, options: Options) -> (Self, Vec<BrokenLink<'a>>) {
+        let mut broken = vec![];
+        let (this, ()) = Self::parse(
+            text,
+            options,
+            Some(|link: pulldown_cmark::BrokenLink<'a>| {
+                broken.push(BrokenLink {
+                    reference: link.reference,
+                    link_type: link.link_type,
+                    span: Span(link.span),
+                });
+                None
+            }),
+            |_| (),
+        );
+        (this, broken)
+    }
+    /// Wrap the text of each of `links` in a [`Tree::BrokenLink`].
+    ///
+    /// Links whose span doesn't cover whole nodes are skipped.
+    pub fn mark_broken_links(&mut self, links: impl IntoIterator<Item = BrokenLink<'a>>) {
+        fn mark<'a>(ast: &mut Ast<'a>, link: BrokenLink<'a>) {
+            let Span(range) = &link.span;
+            let Ast(trees) = ast;
+            if let Some(parent) = trees.iter_mut().find(|it| {
+                let Span(it) = it.span();
+                it.start <= range.start && range.end <= it.end && it != *range
+            }) {
+                if let Some(stream) = parent.stream_mut() {
+                    mark(stream, link)
+                }
+                return;
+            }
+            let start = trees.iter().position(|it| it.span().0.start >= range.start);
+            let end = trees.iter().rposition(|it| it.span().0.end <= range.end);
+            if let (Some(start), Some(end)) = (start, end) {
+                if start <= end
+                    && trees[start].span().0.start == range.start
+                    && trees[end].span().0.end == range.end
+                {
+                    let stream = trees.drain(start..=end).collect();
+                    trees.insert(start, Tree::BrokenLink(link, Ast(stream)))
+                }
+            }
+        }
+        for link in links {
+            mark(self, link)
+        }
+    }
+    /// Build the tree, then inspect the exhausted parser with `finish`.
+    fn parse<C: BrokenLinkCallback<'a>, T>(
+        text: &'a str,
+        options: Options,
+        broken_link_callback: Option<C>,
+        finish: impl FnOnce(&OffsetIter<'a, C>) -> T,
+    ) -> (Self, T) {
+        let mut iter = Parser::new_with_broken_link_callback(text, options, broken_link_callback)
+            .into_offset_iter();
+        match Self::from_events(&mut iter.by_ref().map(|(item, range)| Spanned {
+            item,
+            span: Span(range),
+        })) {
+            Ok((this, None)) => {
+                let t = finish(&iter);
+                (this, t)
+            }
             Ok((_, Some(_))) | Err(_) => {
                 unreachable!("pulldown_cmark guarantees delimters are matched")
             }
         }
     }
+    /// Follow `path` through nested [`Group`]s, indexing each level's stream.
+    pub fn get(&self, path: &[usize]) -> Option<&Tree<'a>> {
+        let (last, init) = path.split_last()?;
+        let mut ast = self;
+        for ix in init {
+            ast = ast.0.get(*ix)?.stream()?;
+        }
+        ast.0.get(*last)
+    }
+    pub fn get_mut(&mut self, path: &[usize]) -> Option<&mut Tree<'a>> {
+        let (last, init) = path.split_last()?;
+        let mut ast = self;
+        for ix in init {
+            ast = ast.0.get_mut(*ix)?.stream_mut()?;
+        }
+        ast.0.get_mut(*last)
+    }
+    /// The textual content of this tree, without any markup or HTML.
+    pub fn plain_text(&self) -> String {
+        let mut buf = String::new();
+        self.push_plain_text(&mut buf);
+        buf
+    }
+    /// Take ownership of all borrowed strings.
+    pub fn into_static(self) -> Ast<'static> {
+        Ast(self.0.into_iter().map(Tree::into_static).collect())
+    }
+    fn push_plain_text(&self, buf: &mut String) {
+        for tree in &self.0 {
+            tree.push_plain_text(buf)
+        }
+    }
+    /// Assign a [`NodeId`](index::NodeId) to every node in this tree.
+    pub fn index(&self) -> index::AstIndex {
+        index::AstIndex::new(self)
+    }
+    pub fn cursor(&self) -> cursor::Cursor<'_, 'a> {
+        cursor::Cursor::new(self)
+    }
+    pub fn cursor_mut(&mut self) -> cursor::CursorMut<'_, 'a> {
+        cursor::CursorMut::new(self)
+    }
     fn from_events(
         evts: &mut dyn Iterator<Item = Spanned<Event<'a>>>,
     ) -> Result<(Self, Option<Spanned<TagEnd>>), Mismatched> {
@@ -190,4 +598,87 @@ impl<'a> Ast<'a> {
     }
 }
 
+/// The [`Tree::kind`] of a group with this tag.
+pub(crate) fn tag_kind(tag: &Tag<'_>) -> &'static str {
+    match tag {
+        Tag::Paragraph => "paragraph",
+        Tag::Heading { .. } => "heading",
+        Tag::BlockQuote(_) => "block-quote",
+        Tag::CodeBlock(_) => "code-block",
+        Tag::HtmlBlock => "html-block",
+        Tag::List(_) => "list",
+        Tag::Item => "item",
+        Tag::FootnoteDefinition(_) => "footnote-definition",
+        Tag::Table(_) => "table",
+        Tag::TableHead => "table-head",
+        Tag::TableRow => "table-row",
+        Tag::TableCell => "table-cell",
+        Tag::Emphasis => "emphasis",
+        Tag::Strong => "strong",
+        Tag::Strikethrough => "strikethrough",
+        Tag::Link { .. } => "link",
+        Tag::Image { .. } => "image",
+        Tag::MetadataBlock(_) => "metadata-block",
+    }
+}
+
 struct Mismatched;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn broken_links() {
+        let source = "[a] and [b][c] and [d][] and [e](f)\n\n[g]: h\n\n[g]\n";
+        let (_, broken) = Ast::new_with_broken_links(source, Options::empty());
+        let broken = broken
+            .iter()
+            .map(|it| (&*it.reference, it.link_type, &source[it.span.0.clone()]))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            broken,
+            [
+                ("a", LinkType::Shortcut, "[a]"),
+                ("c", LinkType::Reference, "[b][c]"),
+                // pulldown_cmark's span excludes the `[]`
+                ("d", LinkType::Collapsed, "[d]"),
+            ]
+        );
+    }
+
+    #[test]
+    fn mark_broken_links() {
+        let source = "x [a] *[b][c]*\n\n> [d][]\n";
+        let (mut ast, broken) = Ast::new_with_broken_links(source, Options::empty());
+        ast.mark_broken_links(broken);
+        let marked = ast
+            .query("broken-link")
+            .unwrap()
+            .into_iter()
+            .map(|it| (it.path, it.tree.plain_text(), &source[it.span.0]))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            marked,
+            [
+                (vec![0, 1], "[a]".into(), "[a]"),
+                (vec![0, 3, 0], "[b][c]".into(), "[b][c]"),
+                (vec![1, 0, 0], "[d]".into(), "[d]"),
+            ]
+        );
+        // the text is unchanged
+        assert_eq!(ast.plain_text(), Ast::new(source).plain_text());
+    }
+
+    #[test]
+    fn broken_links_not_covering_whole_nodes_are_skipped() {
+        let mut ast = Ast::new("a [b *c] d*\n");
+        let before = ast.clone();
+        ast.mark_broken_links([BrokenLink {
+            reference: "b *c".into(),
+            link_type: LinkType::Shortcut,
+            span: Span(2..8),
+        }]);
+        assert_eq!(ast, before);
+    }
+}
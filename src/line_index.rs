@@ -0,0 +1,182 @@
+//! Convert between byte offsets and line/column positions.
+
+/// A 1-based line and column, where columns count [`char`]s,
+/// unless the position is from [`LineIndex::line_col_in`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct LineCol {
+    pub line: usize,
+    pub column: usize,
+}
+
+/// What columns and offsets count.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Unit {
+    Bytes,
+    Chars,
+    /// UTF-16 code units, as in JavaScript strings.
+    Utf16,
+}
+
+impl Unit {
+    fn len(self, text: &str) -> usize {
+        match self {
+            Unit::Bytes => text.len(),
+            _ => text.chars().map(|it| self.char_len(it)).sum(),
+        }
+    }
+    fn char_len(self, c: char) -> usize {
+        match self {
+            Unit::Bytes => c.len_utf8(),
+            Unit::Chars => 1,
+            Unit::Utf16 => c.len_utf16(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LineIndex<'a> {
+    text: &'a str,
+    /// Offset of the start of each line.
+    starts: Vec<usize>,
+    /// [`Self::starts`], in UTF-16 code units.
+    wide_starts: Vec<usize>,
+}
+
+impl<'a> LineIndex<'a> {
+    pub fn new(text: &'a str) -> Self {
+        let starts = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(ix, _)| ix + 1))
+            .collect::<Vec<_>>();
+        let wide_starts = starts
+            .iter()
+            .scan((0, 0), |(prev, wide), &start| {
+                *wide += Unit::Utf16.len(&text[*prev..start]);
+                *prev = start;
+                Some(*wide)
+            })
+            .collect();
+        Self {
+            text,
+            starts,
+            wide_starts,
+        }
+    }
+    pub fn text(&self) -> &'a str {
+        self.text
+    }
+    pub fn line_count(&self) -> usize {
+        self.starts.len()
+    }
+    /// The position of the byte `offset`, which is clamped to the text,
+    /// and rounded down to a [`char`] boundary.
+    pub fn line_col(&self, offset: usize) -> LineCol {
+        self.line_col_in(offset, Unit::Chars)
+    }
+    /// Like [`Self::line_col`], with the column counting `unit`s.
+    pub fn line_col_in(&self, offset: usize, unit: Unit) -> LineCol {
+        let offset = self.floor(offset);
+        let line = self.starts.partition_point(|it| *it <= offset);
+        let start = self.starts[line - 1];
+        LineCol {
+            line,
+            column: unit.len(&self.text[start..offset]) + 1,
+        }
+    }
+    /// The byte `offset` counted in `unit`s from the start of the text,
+    /// with `offset` clamped and rounded like in [`Self::line_col`].
+    pub fn to_unit(&self, offset: usize, unit: Unit) -> usize {
+        let offset = self.floor(offset);
+        match unit {
+            Unit::Bytes => offset,
+            Unit::Chars => self.text[..offset].chars().count(),
+            Unit::Utf16 => {
+                let line = self.starts.partition_point(|it| *it <= offset) - 1;
+                self.wide_starts[line] + unit.len(&self.text[self.starts[line]..offset])
+            }
+        }
+    }
+    /// The byte offset `count` `unit`s from the start of the text, if it is in the text.
+    ///
+    /// An offset in the middle of a [`char`] is rounded down.
+    pub fn from_unit(&self, count: usize, unit: Unit) -> Option<usize> {
+        let (start, rest) = match unit {
+            Unit::Bytes => {
+                return (count <= self.text.len()).then(|| self.floor(count));
+            }
+            Unit::Chars => (0, count),
+            Unit::Utf16 => {
+                let line = self.wide_starts.partition_point(|it| *it <= count) - 1;
+                (self.starts[line], count - self.wide_starts[line])
+            }
+        };
+        let mut units = 0;
+        for (ix, c) in self.text[start..].char_indices() {
+            let len = unit.char_len(c);
+            if units + len > rest {
+                return Some(start + ix);
+            }
+            units += len;
+        }
+        (units == rest).then_some(self.text.len())
+    }
+    fn floor(&self, offset: usize) -> usize {
+        let mut offset = offset.min(self.text.len());
+        while !self.text.is_char_boundary(offset) {
+            offset -= 1
+        }
+        offset
+    }
+    /// The byte offset of `position`, if it is in the text.
+    ///
+    /// A column one past the end of a line is allowed.
+    pub fn offset(&self, position: LineCol) -> Option<usize> {
+        let line = self.line(position.line)?;
+        match position.column.checked_sub(1)? {
+            0 => Some(self.starts[position.line - 1]),
+            n => line
+                .char_indices()
+                .map(|(ix, _)| ix)
+                .chain([line.len()])
+                .nth(n)
+                .map(|ix| self.starts[position.line - 1] + ix),
+        }
+    }
+    /// The text of the 1-based `line`, without its line ending.
+    pub fn line(&self, line: usize) -> Option<&'a str> {
+        let start = *self.starts.get(line.checked_sub(1)?)?;
+        let end = self
+            .starts
+            .get(line)
+            .map_or(self.text.len(), |next| next - 1);
+        Some(self.text[start..end].trim_end_matches('\r'))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn units() {
+        let text = "aé\n😀b\n";
+        let lines = LineIndex::new(text);
+        let b = text.find('b').unwrap();
+        assert_eq!(lines.line_col(b), LineCol { line: 2, column: 2 });
+        assert_eq!(
+            lines.line_col_in(b, Unit::Utf16),
+            LineCol { line: 2, column: 3 }
+        );
+        assert_eq!(
+            lines.line_col_in(b, Unit::Bytes),
+            LineCol { line: 2, column: 5 }
+        );
+        for (unit, count) in [(Unit::Bytes, 8), (Unit::Chars, 4), (Unit::Utf16, 5)] {
+            assert_eq!(lines.to_unit(b, unit), count);
+            assert_eq!(lines.from_unit(count, unit), Some(b));
+        }
+        // inside the surrogate pair
+        assert_eq!(lines.from_unit(4, Unit::Utf16), Some(b - 4));
+        assert_eq!(lines.from_unit(7, Unit::Utf16), Some(text.len()));
+        assert_eq!(lines.from_unit(8, Unit::Utf16), None);
+    }
+}
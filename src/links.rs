@@ -0,0 +1,728 @@
+//! Extract and rewrite [`Tag::Link`]s and [`Tag::Image`]s in an [`Ast`].
+
+use std::fmt;
+
+use pulldown_cmark::{CowStr, LinkType, Tag};
+
+use crate::{
+    references::ReferenceDefinitions, visit_mut::VisitMut, Ast, Edit, Group, Span, Spanned, Tree,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Link<'a> {
+    pub link_type: LinkType,
+    pub dest_url: CowStr<'a>,
+    pub title: CowStr<'a>,
+    /// Identifier of reference links, e.g. `world` in the link `[hello][world]`.
+    pub id: CowStr<'a>,
+    /// The plain text of the label, or the alt text of an image.
+    pub text: String,
+    /// The whole link or image.
+    pub span: Span,
+    /// Where the destination is written in the source, if it is written in the link itself
+    /// (i.e not for reference links).
+    pub dest_span: Option<Span>,
+    /// Suitable for [`Ast::get`].
+    pub path: Vec<usize>,
+}
+
+impl Link<'_> {
+    /// Whether the destination is given in a reference definition elsewhere in the document
+    /// (or by a broken link callback).
+    pub fn is_reference(&self) -> bool {
+        matches!(
+            self.link_type,
+            LinkType::Reference
+                | LinkType::ReferenceUnknown
+                | LinkType::Collapsed
+                | LinkType::CollapsedUnknown
+                | LinkType::Shortcut
+                | LinkType::ShortcutUnknown
+        )
+    }
+    pub fn is_autolink(&self) -> bool {
+        matches!(self.link_type, LinkType::Autolink | LinkType::Email)
+    }
+}
+
+impl<'a> Ast<'a> {
+    /// All the links in this tree, in document order.
+    ///
+    /// `source` must be the text this tree was parsed from.
+    pub fn links(&self, source: &str) -> Vec<Link<'a>> {
+        let mut links = vec![];
+        collect(self, source, false, &mut vec![], &mut links);
+        links
+    }
+    /// All the images in this tree, in document order.
+    ///
+    /// `source` must be the text this tree was parsed from.
+    pub fn images(&self, source: &str) -> Vec<Link<'a>> {
+        let mut images = vec![];
+        collect(self, source, true, &mut vec![], &mut images);
+        images
+    }
+    /// Rewrite the destination of every link and image in place.
+    pub fn rewrite_links(&mut self, rewrite: impl RewriteLink) {
+        struct Visitor<R>(R);
+        impl<'a, R: RewriteLink> VisitMut<'a> for Visitor<R> {
+            fn visit_link_mut(
+                &mut self,
+                _: &mut LinkType,
+                dest_url: &mut CowStr<'a>,
+                _: &mut CowStr<'a>,
+                _: &mut CowStr<'a>,
+            ) {
+                if let Some(new) = rewritten(&mut self.0, dest_url) {
+                    *dest_url = new.into()
+                }
+            }
+            fn visit_image_mut(
+                &mut self,
+                _: &mut LinkType,
+                dest_url: &mut CowStr<'a>,
+                _: &mut CowStr<'a>,
+                _: &mut CowStr<'a>,
+            ) {
+                if let Some(new) = rewritten(&mut self.0, dest_url) {
+                    *dest_url = new.into()
+                }
+            }
+        }
+        Visitor(rewrite).visit_ast_mut(self)
+    }
+    /// Like [`Ast::rewrite_links`], but return [`Edit`]s to `source` instead of
+    /// changing this tree.
+    ///
+    /// Reference links are rewritten through their `definitions`, including duplicates,
+    /// as collected by [`Ast::new_with_reference_definitions`].
+    /// New destinations are escaped, and put in `<...>` if they contain spaces.
+    /// Autolinks whose new destination can't be an autolink become inline links.
+    pub fn rewrite_links_edits(
+        &self,
+        source: &str,
+        definitions: &ReferenceDefinitions<'_>,
+        mut rewrite: impl RewriteLink,
+    ) -> Vec<Edit> {
+        let mut links = self.links(source);
+        links.extend(self.images(source));
+        let mut edits = links
+            .into_iter()
+            .filter_map(|link| {
+                let new = rewritten(&mut rewrite, &link.dest_url)?;
+                match link.is_autolink() {
+                    true => Some(Edit {
+                        replacement: autolink(&link, &new),
+                        span: link.span,
+                    }),
+                    false => {
+                        let span = link.dest_span?;
+                        let bracketed = source[..span.0.start].ends_with('<');
+                        let replacement = escape_destination(&new, bracketed);
+                        Some(Edit { span, replacement })
+                    }
+                }
+            })
+            .collect::<Vec<_>>();
+        for definition in definitions.iter().chain(definitions.duplicates()) {
+            if let (Some(span), Some(new)) = (
+                &definition.dest_span,
+                rewritten(&mut rewrite, &definition.dest_url),
+            ) {
+                let bracketed = source[..span.0.start].ends_with('<');
+                edits.push(Edit {
+                    span: span.clone(),
+                    replacement: escape_destination(&new, bracketed),
+                })
+            }
+        }
+        edits.sort_by_key(|it| it.span.0.start);
+        edits
+    }
+}
+
+fn rewritten(rewrite: &mut impl RewriteLink, url: &str) -> Option<String> {
+    let mut dest = Destination::parse(url);
+    rewrite.rewrite(&mut dest);
+    let new = dest.to_string();
+    match new == url {
+        true => None,
+        false => Some(new),
+    }
+}
+
+/// Write `dest` so that it parses back to itself in a link or definition.
+///
+/// If it's already `bracketed` in the source, write what goes inside the `<...>`,
+/// otherwise only add brackets if they're needed.
+fn escape_destination(dest: &str, bracketed: bool) -> String {
+    let wrap = !bracketed && (dest.is_empty() || dest.contains(char::is_whitespace));
+    let bracketed = bracketed || wrap;
+    let mut escaped = String::with_capacity(dest.len() + 2);
+    for (ix, c) in dest.char_indices() {
+        let rest = &dest[ix + c.len_utf8()..];
+        match c {
+            // line endings can't be written in a destination
+            '\n' => escaped.push_str("%0A"),
+            '\r' => escaped.push_str("%0D"),
+            '&' if is_entity(rest) => escaped.push_str("\\&"),
+            '\\' | '<' => {
+                escaped.push('\\');
+                escaped.push(c)
+            }
+            '>' if bracketed => escaped.push_str("\\>"),
+            '(' | ')' if !bracketed => {
+                escaped.push('\\');
+                escaped.push(c)
+            }
+            c => escaped.push(c),
+        }
+    }
+    match wrap {
+        true => format!("<{escaped}>"),
+        false => escaped,
+    }
+}
+
+/// Whether `&` followed by `rest` would be read as an entity, like `&amp;`.
+fn is_entity(rest: &str) -> bool {
+    rest.find(';').is_some_and(|end| {
+        end > 0
+            && rest[..end]
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '#')
+    })
+}
+
+/// An autolink to `new`, or an inline link if it can't be one.
+fn autolink(link: &Link<'_>, new: &str) -> String {
+    let email = link.link_type == LinkType::Email;
+    let valid = !new.is_empty()
+        && !new.contains(|c: char| c.is_whitespace() || c.is_control() || c == '<' || c == '>')
+        && Destination::parse(new).has_scheme() != email
+        && (!email || new.contains('@'));
+    if valid {
+        return format!("<{new}>");
+    }
+    let mut text = String::new();
+    for c in link.text.chars() {
+        if matches!(
+            c,
+            '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '&' | '~' | '|' | '$'
+        ) {
+            text.push('\\')
+        }
+        text.push(c)
+    }
+    let dest = match email {
+        true => format!("mailto:{new}"),
+        false => new.to_owned(),
+    };
+    format!("[{text}]({})", escape_destination(&dest, false))
+}
+
+/// A link destination, split into its parts.
+///
+/// Parts that a [`RewriteLink`] doesn't touch are preserved.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct Destination {
+    /// Everything before the query or fragment, including any scheme and host.
+    pub path: String,
+    /// Without the leading `?`.
+    pub query: Option<String>,
+    /// Without the leading `#`.
+    pub fragment: Option<String>,
+}
+
+impl Destination {
+    pub fn parse(url: &str) -> Self {
+        let (rest, fragment) = match url.split_once('#') {
+            Some((rest, fragment)) => (rest, Some(fragment.into())),
+            None => (url, None),
+        };
+        let (path, query) = match rest.split_once('?') {
+            Some((path, query)) => (path, Some(query.into())),
+            None => (rest, None),
+        };
+        Self {
+            path: path.into(),
+            query,
+            fragment,
+        }
+    }
+    /// Whether the path has a scheme like `https:` or `mailto:`.
+    pub fn has_scheme(&self) -> bool {
+        match self.path.split_once(':') {
+            Some((scheme, _)) => {
+                scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+                    && scheme
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+            }
+            None => false,
+        }
+    }
+    /// Whether this is a non-empty path relative to the current document,
+    /// like `foo.md` or `../foo.md`.
+    pub fn is_relative(&self) -> bool {
+        !self.path.is_empty() && !self.has_scheme() && !self.path.starts_with('/')
+    }
+    /// The extension of the last path segment, if any.
+    pub fn extension(&self) -> Option<&str> {
+        let segment = self.path.rsplit('/').next()?;
+        match segment.rsplit_once('.') {
+            Some(("", _) | (_, "")) | None => None,
+            Some((_, ext)) => Some(ext),
+        }
+    }
+}
+
+impl fmt::Display for Destination {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Self {
+            path,
+            query,
+            fragment,
+        } = self;
+        f.write_str(path)?;
+        if let Some(query) = query {
+            write!(f, "?{query}")?
+        }
+        if let Some(fragment) = fragment {
+            write!(f, "#{fragment}")?
+        }
+        Ok(())
+    }
+}
+
+/// Rewrite link destinations.
+///
+/// Implemented for closures and [`Rules`].
+pub trait RewriteLink {
+    fn rewrite(&mut self, dest: &mut Destination);
+}
+
+impl<F: FnMut(&mut Destination)> RewriteLink for F {
+    fn rewrite(&mut self, dest: &mut Destination) {
+        self(dest)
+    }
+}
+
+/// A table of rewrite rules.
+///
+/// Rules are applied in this order:
+/// 1. The first matching [`Rules::prefix`].
+/// 2. The first matching [`Rules::extension`], for paths without a scheme.
+/// 3. The [`Rules::base_url`], for paths which are still relative.
+/// 4. The first matching [`Rules::anchor`], for links within the same document.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Rules {
+    prefixes: Vec<(String, String)>,
+    extensions: Vec<(String, String)>,
+    base_url: Option<String>,
+    anchors: Vec<(String, String)>,
+}
+
+impl Rules {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Replace a leading `from` in the path with `to`, e.g `../` with `/docs/`.
+    pub fn prefix(mut self, from: impl Into<String>, to: impl Into<String>) -> Self {
+        self.prefixes.push((from.into(), to.into()));
+        self
+    }
+    /// Replace the extension `from` with `to`, e.g `md` with `html`.
+    ///
+    /// An empty `from` matches paths without an extension, like `guide`,
+    /// so `.extension("", "html")` adds one, and an empty `to` removes the extension.
+    pub fn extension(mut self, from: impl Into<String>, to: impl Into<String>) -> Self {
+        self.extensions.push((from.into(), to.into()));
+        self
+    }
+    /// Prefix relative paths with `base`.
+    pub fn base_url(mut self, base: impl Into<String>) -> Self {
+        self.base_url = Some(base.into());
+        self
+    }
+    /// Rename `#from` to `#to`, e.g after a heading is renamed.
+    pub fn anchor(mut self, from: impl Into<String>, to: impl Into<String>) -> Self {
+        self.anchors.push((from.into(), to.into()));
+        self
+    }
+}
+
+impl RewriteLink for Rules {
+    fn rewrite(&mut self, dest: &mut Destination) {
+        if let Some((from, to)) = self
+            .prefixes
+            .iter()
+            .find(|(from, _)| dest.path.starts_with(from.as_str()))
+        {
+            dest.path.replace_range(..from.len(), to)
+        }
+        if !dest.has_scheme() {
+            let segment = dest.path.rsplit('/').next().unwrap_or_default();
+            let ext = match dest.extension() {
+                Some(ext) => Some(ext),
+                // not a directory, or `..`
+                None if !segment.is_empty() && !segment.ends_with('.') => Some(""),
+                None => None,
+            };
+            if let Some((from, to)) = self
+                .extensions
+                .iter()
+                .find(|(from, _)| Some(&**from) == ext)
+            {
+                let stem = match from.is_empty() {
+                    true => dest.path.len(),
+                    false => dest.path.len() - from.len() - 1,
+                };
+                dest.path.truncate(stem);
+                if !to.is_empty() {
+                    dest.path.push('.');
+                    dest.path.push_str(to)
+                }
+            }
+        }
+        if let Some(base) = &self.base_url {
+            if dest.is_relative() {
+                dest.path = match (base.ends_with('/'), dest.path.starts_with("./")) {
+                    (true, true) => format!("{base}{}", &dest.path[2..]),
+                    (true, false) => format!("{base}{}", dest.path),
+                    (false, true) => format!("{base}/{}", &dest.path[2..]),
+                    (false, false) => format!("{base}/{}", dest.path),
+                }
+            }
+        }
+        if dest.path.is_empty() {
+            if let Some(fragment) = &mut dest.fragment {
+                if let Some((_, to)) = self.anchors.iter().find(|(from, _)| from == fragment) {
+                    *fragment = to.clone()
+                }
+            }
+        }
+    }
+}
+
+fn collect<'a>(
+    ast: &Ast<'a>,
+    source: &str,
+    images: bool,
+    path: &mut Vec<usize>,
+    links: &mut Vec<Link<'a>>,
+) {
+    for (ix, tree) in ast.0.iter().enumerate() {
+        path.push(ix);
+        if let Tree::Group(Group {
+            tag: Spanned { item: tag, .. },
+            stream,
+            ..
+        }) = tree
+        {
+            add(tree, tag, stream, source, images, path, links)
+        }
+        if let Some(stream) = tree.stream() {
+            collect(stream, source, images, path, links);
+        }
+        path.pop();
+    }
+}
+
+fn add<'a>(
+    tree: &Tree<'a>,
+    tag: &Tag<'a>,
+    stream: &Ast<'a>,
+    source: &str,
+    images: bool,
+    path: &[usize],
+    links: &mut Vec<Link<'a>>,
+) {
+    match (tag, images) {
+        (
+            Tag::Link {
+                link_type,
+                dest_url,
+                title,
+                id,
+            },
+            false,
+        )
+        | (
+            Tag::Image {
+                link_type,
+                dest_url,
+                title,
+                id,
+            },
+            true,
+        ) => {
+            let span = tree.span();
+            links.push(Link {
+                link_type: *link_type,
+                dest_url: dest_url.clone(),
+                title: title.clone(),
+                id: id.clone(),
+                text: stream.plain_text(),
+                dest_span: dest_span(source, *link_type, &span, stream),
+                span,
+                path: path.to_vec(),
+            })
+        }
+        _ => {}
+    }
+}
+
+/// Find the destination of a link in the source.
+pub(crate) fn dest_span(
+    source: &str,
+    link_type: LinkType,
+    span: &Span,
+    stream: &Ast,
+) -> Option<Span> {
+    let Span(range) = span;
+    match link_type {
+        LinkType::Autolink | LinkType::Email => {
+            let text = source.get(range.clone())?;
+            match text.starts_with('<') && text.ends_with('>') {
+                true => Some(Span(range.start + 1..range.end - 1)),
+                // GFM extended autolinks have no brackets
+                false => Some(span.clone()),
+            }
+        }
+        LinkType::Inline => {
+            // the label ends after the last child, so the destination follows the next `](`
+            let label_end = stream
+                .0
+                .last()
+                .map(|it| it.span().0.end)
+                .unwrap_or(range.start)
+                .max(range.start);
+            let after = source.get(label_end..range.end)?;
+            let open = label_end + after.find("](")? + 2;
+            let rest = &source[open..range.end];
+            let skipped = rest.len() - rest.trim_start().len();
+            let start = open + skipped;
+            let rest = &source[start..range.end];
+            let len = match rest.strip_prefix('<') {
+                Some(inner) => return Some(Span(start + 1..start + 1 + inner.find('>')?)),
+                None => bare_destination_len(rest),
+            };
+            Some(Span(start..start + len))
+        }
+        _ => None,
+    }
+}
+
+/// Length of a destination not in `<...>`, which ends at whitespace or an unbalanced `)`.
+fn bare_destination_len(s: &str) -> usize {
+    let mut depth = 0usize;
+    let mut escaped = false;
+    for (ix, c) in s.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '(' => depth += 1,
+            ')' if depth == 0 => return ix,
+            ')' => depth -= 1,
+            c if c.is_whitespace() => return ix,
+            _ => {}
+        }
+    }
+    s.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use pulldown_cmark::Options;
+
+    use super::*;
+    use crate::fix;
+
+    fn dests(source: &str) -> Vec<Option<&str>> {
+        Ast::new(source)
+            .links(source)
+            .iter()
+            .map(|it| it.dest_span.as_ref().map(|Span(it)| &source[it.clone()]))
+            .collect()
+    }
+
+    #[test]
+    fn dest_spans() {
+        assert_eq!(dests("[a](b)"), [Some("b")]);
+        assert_eq!(dests("[a]( b \"title\")"), [Some("b")]);
+        assert_eq!(dests("[a](<b c>)"), [Some("b c")]);
+        assert_eq!(dests("[a](b(c)d)"), [Some("b(c)d")]);
+        assert_eq!(dests("[a](b\\)c)"), [Some("b\\)c")]);
+        assert_eq!(dests("[a [b] c](d)"), [Some("d")]);
+        assert_eq!(dests("[a\\](b) c](d)"), [Some("d")]);
+        assert_eq!(dests("[`](`](d)"), [Some("d")]);
+        assert_eq!(dests("[![i](j)](d)"), [Some("d")]);
+        assert_eq!(dests("[](d)"), [Some("d")]);
+        assert_eq!(dests("> [a](b\n> )"), [Some("b")]);
+        assert_eq!(dests("<http://a.b>"), [Some("http://a.b")]);
+        assert_eq!(dests("[a]\n\n[a]: b"), [None]);
+    }
+
+    #[test]
+    fn collect_links_and_images() {
+        let source = "[a *b*](u \"t\") ![alt](i.png)\n\n- [c][r]\n\n[r]: v\n";
+        let ast = Ast::new(source);
+        let links = ast.links(source);
+        assert_eq!(links.len(), 2);
+        assert_eq!(
+            (&*links[0].dest_url, &*links[0].title, &*links[0].text),
+            ("u", "t", "a b")
+        );
+        assert_eq!(links[0].path, [0, 0]);
+        assert!(links[1].is_reference());
+        assert_eq!(&*links[1].id, "r");
+        let images = ast.images(source);
+        assert_eq!(images.len(), 1);
+        assert_eq!((&*images[0].dest_url, &*images[0].text), ("i.png", "alt"));
+    }
+
+    fn rules() -> Rules {
+        Rules::new()
+            .prefix("../", "/docs/")
+            .extension("md", "html")
+            .extension("", "html")
+            .base_url("https://example.com")
+            .anchor("old", "new")
+    }
+
+    fn rewrite(dest: &str, mut rules: Rules) -> String {
+        let mut dest = Destination::parse(dest);
+        rules.rewrite(&mut dest);
+        dest.to_string()
+    }
+
+    #[test]
+    fn destinations() {
+        let dest = Destination::parse("a/b.md?x=1#y");
+        assert_eq!(dest.path, "a/b.md");
+        assert_eq!(dest.query.as_deref(), Some("x=1"));
+        assert_eq!(dest.fragment.as_deref(), Some("y"));
+        assert_eq!(dest.extension(), Some("md"));
+        assert!(dest.is_relative());
+        assert_eq!(dest.to_string(), "a/b.md?x=1#y");
+        assert!(Destination::parse("mailto:a@b").has_scheme());
+        assert!(!Destination::parse("/a").is_relative());
+        assert_eq!(Destination::parse("a/.hidden").extension(), None);
+        assert_eq!(Destination::parse("a.b/c").extension(), None);
+    }
+
+    #[test]
+    fn rules_preserve_queries_and_fragments() {
+        assert_eq!(rewrite("../a.md#x", rules()), "/docs/a.html#x");
+        assert_eq!(
+            rewrite("a.md?v=1", rules()),
+            "https://example.com/a.html?v=1"
+        );
+        assert_eq!(
+            rewrite("./guide", rules()),
+            "https://example.com/guide.html"
+        );
+        assert_eq!(rewrite("#old", rules()), "#new");
+        assert_eq!(
+            rewrite("a.md#old", rules()),
+            "https://example.com/a.html#old"
+        );
+        assert_eq!(rewrite("https://x.y/a.md", rules()), "https://x.y/a.md");
+        assert_eq!(
+            rewrite("img.png", Rules::new().extension("md", "html")),
+            "img.png"
+        );
+    }
+
+    #[test]
+    fn rules_for_extensionless_paths() {
+        let rules = || Rules::new().extension("", "html");
+        assert_eq!(rewrite("guide", rules()), "guide.html");
+        assert_eq!(rewrite("a/guide#x", rules()), "a/guide.html#x");
+        assert_eq!(rewrite("a/", rules()), "a/");
+        assert_eq!(rewrite("..", rules()), "..");
+        assert_eq!(rewrite("#x", rules()), "#x");
+        assert_eq!(rewrite("a.md", rules()), "a.md");
+        assert_eq!(rewrite("a.md", Rules::new().extension("md", "")), "a");
+    }
+
+    #[test]
+    fn rewrite_in_place() {
+        let mut ast = Ast::new("[a](../a.md) ![b](b.md)");
+        ast.rewrite_links(rules());
+        let dests = ast.links("").into_iter().chain(ast.images(""));
+        assert_eq!(
+            dests.map(|it| it.dest_url.to_string()).collect::<Vec<_>>(),
+            ["/docs/a.html", "https://example.com/b.html"]
+        );
+    }
+
+    /// Rewrite every destination to `to`, and check the result parses to it.
+    fn rewrite_edits(source: &str, to: &str) -> String {
+        let (ast, definitions) = Ast::new_with_reference_definitions(source, Options::empty());
+        let edits = ast.rewrite_links_edits(source, &definitions, |dest: &mut Destination| {
+            *dest = Destination::parse(to)
+        });
+        let new = fix::apply(source, edits).text;
+        let parsed = Ast::new(&new);
+        for link in parsed.links(&new) {
+            assert_eq!(link.dest_url, to.into(), "{new}");
+        }
+        new
+    }
+
+    #[test]
+    fn edits_escape_destinations() {
+        assert_eq!(rewrite_edits("[a](b)", "c d"), "[a](<c d>)");
+        assert_eq!(rewrite_edits("[a](<b>)", "c d"), "[a](<c d>)");
+        assert_eq!(rewrite_edits("[a](b)", "c)"), "[a](c\\))");
+        assert_eq!(rewrite_edits("[a](b)", "<c>"), "[a](\\<c>)");
+        assert_eq!(rewrite_edits("[a](<b>)", "<c>"), "[a](<\\<c\\>>)");
+        assert_eq!(rewrite_edits("[a](b)", "c&copy;"), "[a](c\\&copy;)");
+        assert_eq!(rewrite_edits("[a](b)", "c&d"), "[a](c&d)");
+        assert_eq!(rewrite_edits("[a](b)", "c\\d"), "[a](c\\\\d)");
+        assert_eq!(rewrite_edits("[a](b \"t\")", ""), "[a](<> \"t\")");
+    }
+
+    #[test]
+    fn edits_rewrite_reference_definitions() {
+        assert_eq!(
+            rewrite_edits("[a] [b][a]\n\n[a]: b\n[A]: <b>\n", "c d"),
+            "[a] [b][a]\n\n[a]: <c d>\n[A]: <c d>\n"
+        );
+        assert_eq!(
+            rewrite_edits("> [a]\n>\n> [a]:\n> b \"t\"\n", "c"),
+            "> [a]\n>\n> [a]:\n> c \"t\"\n"
+        );
+    }
+
+    #[test]
+    fn edits_rewrite_autolinks() {
+        assert_eq!(rewrite_edits("<http://a>", "https://b"), "<https://b>");
+        assert_eq!(rewrite_edits("<http://a>", "b c"), "[http://a](<b c>)");
+        assert_eq!(rewrite_edits("<http://a_b>", "c"), "[http://a\\_b](c)");
+    }
+
+    #[test]
+    fn edits_are_only_for_changed_destinations() {
+        let source = "[a](a.md) [b](b.png)";
+        let ast = Ast::new(source);
+        let edits = ast.rewrite_links_edits(source, &Default::default(), rules());
+        assert_eq!(
+            edits,
+            [
+                Edit {
+                    span: Span(4..8),
+                    replacement: "https://example.com/a.html".into()
+                },
+                Edit {
+                    span: Span(14..19),
+                    replacement: "https://example.com/b.png".into()
+                }
+            ]
+        );
+        let edits = ast.rewrite_links_edits(source, &Default::default(), Rules::new());
+        assert!(edits.is_empty());
+    }
+}
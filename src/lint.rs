@@ -0,0 +1,926 @@
+//! Check Markdown for style problems, with rules modeled on
+//! [markdownlint](https://github.com/DavidAnson/markdownlint).
+//!
+//! | Rule                      | markdownlint |
+//! | ------------------------- | ------------ |
+//! | `heading-increment`       | MD001        |
+//! | `ul-style`                | MD004        |
+//! | `no-duplicate-heading`    | MD024        |
+//! | `single-h1`               | MD025        |
+//! | `no-trailing-punctuation` | MD026        |
+//! | `no-bare-urls`            | MD034        |
+//! | `no-empty-links`          | MD042        |
+//!
+//! # Suppression
+//!
+//! Diagnostics can be silenced with HTML comments naming the rules,
+//! or all rules if none are named:
+//!
+//! ```markdown
+//! <!-- md-ast-disable no-bare-urls single-h1 -->
+//! Until the next `md-ast-enable`, or the end of the document.
+//! <!-- md-ast-enable -->
+//!
+//! <!-- md-ast-disable-next-line no-bare-urls -->
+//! Only on this line: https://example.com
+//! ```
+//!
+//! Directives that don't silence anything are reported as [`UNUSED_SUPPRESSION`].
+
+use std::{collections::HashMap, fmt, ops::Range};
+
+use pulldown_cmark::{HeadingLevel, Tag};
+
+use crate::{
+    line_index::{LineCol, LineIndex},
+    Ast, Edit, Group, Span, Spanned, Tree,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Diagnostic {
+    /// The [`Rule::name`] of the rule that emitted this.
+    pub rule: &'static str,
+    pub severity: Severity,
+    pub message: String,
+    pub span: Span,
+    pub fix: Option<Edit>,
+}
+
+impl Diagnostic {
+    /// A [`Severity::Warning`].
+    pub fn new(rule: &'static str, span: Span, message: impl Into<String>) -> Self {
+        Self {
+            rule,
+            severity: Severity::Warning,
+            message: message.into(),
+            span,
+            fix: None,
+        }
+    }
+    pub fn with_fix(mut self, fix: Edit) -> Self {
+        self.fix = Some(fix);
+        self
+    }
+}
+
+pub trait Rule {
+    /// A kebab-case name, e.g `heading-increment`.
+    fn name(&self) -> &'static str;
+    fn description(&self) -> &'static str;
+    /// `ast` was parsed from `source`, which `lines` indexes.
+    fn check(
+        &self,
+        ast: &Ast<'_>,
+        source: &str,
+        lines: &LineIndex<'_>,
+        diagnostics: &mut Vec<Diagnostic>,
+    );
+}
+
+/// Which rules are enabled, and how severe their diagnostics are.
+///
+/// All rules are enabled by default.
+#[derive(Debug, Clone)]
+pub struct Config {
+    default: bool,
+    enabled: HashMap<String, bool>,
+    severities: HashMap<String, Severity>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Config {
+    pub fn new() -> Self {
+        Self {
+            default: true,
+            enabled: HashMap::new(),
+            severities: HashMap::new(),
+        }
+    }
+    /// Whether rules not mentioned by [`Self::enable`] or [`Self::disable`] are run.
+    pub fn enable_all(mut self, enabled: bool) -> Self {
+        self.default = enabled;
+        self
+    }
+    pub fn enable(mut self, rule: impl Into<String>) -> Self {
+        self.enabled.insert(rule.into(), true);
+        self
+    }
+    pub fn disable(mut self, rule: impl Into<String>) -> Self {
+        self.enabled.insert(rule.into(), false);
+        self
+    }
+    /// Override the severity of the diagnostics emitted by `rule`.
+    pub fn severity(mut self, rule: impl Into<String>, severity: Severity) -> Self {
+        self.severities.insert(rule.into(), severity);
+        self
+    }
+    pub fn is_enabled(&self, rule: &str) -> bool {
+        self.enabled.get(rule).copied().unwrap_or(self.default)
+    }
+}
+
+/// A set of [`Rule`]s to run over documents.
+pub struct Linter {
+    rules: Vec<Box<dyn Rule>>,
+    config: Config,
+}
+
+impl fmt::Debug for Linter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Linter")
+            .field(
+                "rules",
+                &self.rules.iter().map(|it| it.name()).collect::<Vec<_>>(),
+            )
+            .field("config", &self.config)
+            .finish()
+    }
+}
+
+impl Default for Linter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Linter {
+    /// A linter with the [`core_rules`].
+    pub fn new() -> Self {
+        Self {
+            rules: core_rules(),
+            config: Config::new(),
+        }
+    }
+    /// A linter with no rules.
+    pub fn empty() -> Self {
+        Self {
+            rules: vec![],
+            config: Config::new(),
+        }
+    }
+    pub fn rule(mut self, rule: impl Rule + 'static) -> Self {
+        self.rules.push(Box::new(rule));
+        self
+    }
+    pub fn config(mut self, config: Config) -> Self {
+        self.config = config;
+        self
+    }
+    pub fn rules(&self) -> impl Iterator<Item = &dyn Rule> {
+        self.rules.iter().map(|it| &**it)
+    }
+    /// Run the enabled rules, returning their diagnostics in source order.
+    ///
+    /// `source` must be the text `ast` was parsed from.
+    pub fn lint(&self, ast: &Ast<'_>, source: &str) -> Vec<Diagnostic> {
+        let lines = LineIndex::new(source);
+        let mut diagnostics = vec![];
+        for rule in &self.rules {
+            if !self.config.is_enabled(rule.name()) {
+                continue;
+            }
+            let len = diagnostics.len();
+            rule.check(ast, source, &lines, &mut diagnostics);
+            if let Some(severity) = self.config.severities.get(rule.name()) {
+                for diagnostic in &mut diagnostics[len..] {
+                    diagnostic.severity = *severity
+                }
+            }
+        }
+        let (suppressions, mut directives) = suppressions(ast, &lines);
+        diagnostics.retain(|diagnostic| {
+            let suppressed_by = suppressions.iter().find(|it| {
+                it.range.contains(&diagnostic.span.0.start) && it.rules.matches(diagnostic.rule)
+            });
+            if let Some(suppression) = suppressed_by {
+                directives[suppression.directive].1 = true
+            }
+            suppressed_by.is_none()
+        });
+        if self.config.is_enabled(UNUSED_SUPPRESSION) {
+            for (span, used) in directives {
+                if !used {
+                    diagnostics.push(Diagnostic {
+                        severity: self
+                            .config
+                            .severities
+                            .get(UNUSED_SUPPRESSION)
+                            .copied()
+                            .unwrap_or(Severity::Warning),
+                        ..Diagnostic::new(UNUSED_SUPPRESSION, span, "unused suppression")
+                    })
+                }
+            }
+        }
+        diagnostics.sort_by_key(|it| (it.span.0.start, it.span.0.end, it.rule));
+        diagnostics
+    }
+}
+
+/// The [`Diagnostic::rule`] for suppression directives that didn't suppress anything.
+pub const UNUSED_SUPPRESSION: &str = "unused-suppression";
+
+/// The rules named by a directive.
+#[derive(Debug, Clone)]
+struct Rules {
+    /// If `true`, every rule except `names`.
+    all: bool,
+    names: Vec<String>,
+}
+
+impl Rules {
+    fn matches(&self, rule: &str) -> bool {
+        self.all != self.names.iter().any(|it| it == rule)
+    }
+}
+
+#[derive(Debug)]
+struct Suppression {
+    rules: Rules,
+    range: Range<usize>,
+    /// Index of the directive.
+    directive: usize,
+}
+
+/// Returns the suppressed ranges, and the span of each disabling directive.
+fn suppressions(ast: &Ast<'_>, lines: &LineIndex<'_>) -> (Vec<Suppression>, Vec<(Span, bool)>) {
+    let mut directives = vec![];
+    let mut closed = vec![];
+    let mut open = vec![];
+    walk(ast, &mut vec![], &mut |tree, _| {
+        let (Tree::Html(Spanned { item, span }) | Tree::InlineHtml(Spanned { item, span })) = tree
+        else {
+            return;
+        };
+        let Some(comment) = item
+            .trim()
+            .strip_prefix("<!--")
+            .and_then(|it| it.strip_suffix("-->"))
+        else {
+            return;
+        };
+        let mut words = comment.split_whitespace();
+        let directive = words.next().unwrap_or_default();
+        let names = words.map(String::from).collect::<Vec<_>>();
+        let rules = Rules {
+            all: names.is_empty(),
+            names: names.clone(),
+        };
+        match directive {
+            "md-ast-disable" => {
+                open.push(Suppression {
+                    rules,
+                    range: span.0.end..usize::MAX,
+                    directive: directives.len(),
+                });
+                directives.push((span.clone(), false))
+            }
+            "md-ast-disable-next-line" => {
+                let line = lines.line_col(span.0.end.saturating_sub(1)).line + 1;
+                let offset = |line| lines.offset(LineCol { line, column: 1 });
+                if let Some(start) = offset(line) {
+                    closed.push(Suppression {
+                        rules,
+                        range: start..offset(line + 1).unwrap_or(usize::MAX),
+                        directive: directives.len(),
+                    })
+                }
+                directives.push((span.clone(), false))
+            }
+            "md-ast-enable" => {
+                for mut suppression in std::mem::take(&mut open) {
+                    let start = suppression.range.start;
+                    // rules which stay disabled
+                    let rest = match (rules.all, suppression.rules.all) {
+                        (true, _) => None,
+                        (false, true) => Some(Rules {
+                            all: true,
+                            names: [&suppression.rules.names[..], &names].concat(),
+                        }),
+                        (false, false) => Some(Rules {
+                            all: false,
+                            names: suppression
+                                .rules
+                                .names
+                                .iter()
+                                .filter(|it| !names.contains(it))
+                                .cloned()
+                                .collect(),
+                        })
+                        .filter(|it| !it.names.is_empty()),
+                    };
+                    if let Some(rest) = rest {
+                        open.push(Suppression {
+                            rules: rest,
+                            range: span.0.end..usize::MAX,
+                            directive: suppression.directive,
+                        })
+                    }
+                    suppression.range = start..span.0.start;
+                    closed.push(suppression)
+                }
+            }
+            _ => {}
+        }
+    });
+    closed.extend(open);
+    (closed, directives)
+}
+
+impl Ast<'_> {
+    /// Run the [`core_rules`] over this tree.
+    ///
+    /// `source` must be the text this tree was parsed from.
+    pub fn lint(&self, source: &str) -> Vec<Diagnostic> {
+        Linter::new().lint(self, source)
+    }
+}
+
+pub fn core_rules() -> Vec<Box<dyn Rule>> {
+    vec![
+        Box::new(HeadingIncrement),
+        Box::new(UlStyle),
+        Box::new(NoDuplicateHeading),
+        Box::new(SingleH1),
+        Box::new(NoTrailingPunctuation::default()),
+        Box::new(NoBareUrls),
+        Box::new(NoEmptyLinks),
+    ]
+}
+
+/// Call `f` on every node in document order, with its ancestors.
+fn walk<'t, 'a>(
+    ast: &'t Ast<'a>,
+    ancestors: &mut Vec<&'t Tree<'a>>,
+    f: &mut dyn FnMut(&'t Tree<'a>, &[&'t Tree<'a>]),
+) {
+    for tree in &ast.0 {
+        f(tree, ancestors);
+        if let Some(stream) = tree.stream() {
+            ancestors.push(tree);
+            walk(stream, ancestors, f);
+            ancestors.pop();
+        }
+    }
+}
+
+/// `(level, heading)` for every heading.
+fn headings<'t, 'a>(ast: &'t Ast<'a>) -> Vec<(HeadingLevel, &'t Tree<'a>)> {
+    let mut headings = vec![];
+    walk(ast, &mut vec![], &mut |tree, _| {
+        if let Tree::Group(Group {
+            tag:
+                Spanned {
+                    item: Tag::Heading { level, .. },
+                    ..
+                },
+            ..
+        }) = tree
+        {
+            headings.push((*level, tree))
+        }
+    });
+    headings
+}
+
+/// Where `text` is in the source, if it was written without escapes or entities.
+fn verbatim(source: &str, text: &str, span: &Span) -> Option<usize> {
+    (source.get(span.0.clone()) == Some(text)).then_some(span.0.start)
+}
+
+/// Heading levels should only increment by one level at a time.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HeadingIncrement;
+
+impl Rule for HeadingIncrement {
+    fn name(&self) -> &'static str {
+        "heading-increment"
+    }
+    fn description(&self) -> &'static str {
+        "Heading levels should only increment by one level at a time"
+    }
+    fn check(&self, ast: &Ast<'_>, source: &str, _: &LineIndex<'_>, out: &mut Vec<Diagnostic>) {
+        let mut previous = None;
+        for (level, heading) in headings(ast) {
+            let (level, expected) = (level as usize, previous.map_or(1, |it| it + 1));
+            if previous.is_some() && level > expected {
+                let span = heading.span();
+                let mut diagnostic = Diagnostic::new(
+                    self.name(),
+                    span.clone(),
+                    format!("expected a level {expected} heading, not level {level}"),
+                );
+                // only ATX headings can be fixed
+                let text = &source[span.0.clone()];
+                let indent = text.len() - text.trim_start_matches(' ').len();
+                let hashes = text[indent..].len() - text[indent..].trim_start_matches('#').len();
+                if hashes == level {
+                    let start = span.0.start + indent;
+                    diagnostic = diagnostic.with_fix(Edit {
+                        span: Span(start..start + hashes),
+                        replacement: "#".repeat(expected),
+                    })
+                }
+                out.push(diagnostic)
+            }
+            previous = Some(level)
+        }
+    }
+}
+
+/// Bullet lists should use the same marker as the first one in the document.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UlStyle;
+
+impl Rule for UlStyle {
+    fn name(&self) -> &'static str {
+        "ul-style"
+    }
+    fn description(&self) -> &'static str {
+        "Unordered list style should be consistent"
+    }
+    fn check(&self, ast: &Ast<'_>, source: &str, _: &LineIndex<'_>, out: &mut Vec<Diagnostic>) {
+        let mut style = None;
+        walk(ast, &mut vec![], &mut |tree, ancestors| {
+            let Some(Tree::Group(Group {
+                tag:
+                    Spanned {
+                        item: Tag::List(None),
+                        ..
+                    },
+                ..
+            })) = ancestors.last()
+            else {
+                return;
+            };
+            let start = tree.span().0.start;
+            let text = &source[start..];
+            let offset = start + text.len() - text.trim_start().len();
+            let Some(marker) = source[offset..].chars().next() else {
+                return;
+            };
+            let style = *style.get_or_insert(marker);
+            if marker != style {
+                out.push(
+                    Diagnostic::new(
+                        self.name(),
+                        Span(offset..offset + 1),
+                        format!("expected `{style}` as the list marker, not `{marker}`"),
+                    )
+                    .with_fix(Edit {
+                        span: Span(offset..offset + 1),
+                        replacement: style.into(),
+                    }),
+                )
+            }
+        })
+    }
+}
+
+/// Headings should have different content.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoDuplicateHeading;
+
+impl Rule for NoDuplicateHeading {
+    fn name(&self) -> &'static str {
+        "no-duplicate-heading"
+    }
+    fn description(&self) -> &'static str {
+        "Multiple headings with the same content"
+    }
+    fn check(&self, ast: &Ast<'_>, _: &str, lines: &LineIndex<'_>, out: &mut Vec<Diagnostic>) {
+        let mut seen = HashMap::new();
+        for (_, heading) in headings(ast) {
+            let text = heading.plain_text();
+            let span = heading.span();
+            match seen.get(text.trim()) {
+                Some(first) => out.push(Diagnostic::new(
+                    self.name(),
+                    span,
+                    format!(
+                        "duplicate heading {:?}, first on line {}",
+                        text.trim(),
+                        lines.line_col(*first).line
+                    ),
+                )),
+                None => {
+                    seen.insert(text.trim().to_owned(), span.0.start);
+                }
+            }
+        }
+    }
+}
+
+/// There should only be one level 1 heading, for the title.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SingleH1;
+
+impl Rule for SingleH1 {
+    fn name(&self) -> &'static str {
+        "single-h1"
+    }
+    fn description(&self) -> &'static str {
+        "Multiple top-level headings in the same document"
+    }
+    fn check(&self, ast: &Ast<'_>, _: &str, lines: &LineIndex<'_>, out: &mut Vec<Diagnostic>) {
+        let mut first = None;
+        for (level, heading) in headings(ast) {
+            if level != HeadingLevel::H1 {
+                continue;
+            }
+            match first {
+                Some(first) => out.push(Diagnostic::new(
+                    self.name(),
+                    heading.span(),
+                    format!(
+                        "multiple top-level headings, the first is on line {}",
+                        lines.line_col(first).line
+                    ),
+                )),
+                None => first = Some(heading.span().0.start),
+            }
+        }
+    }
+}
+
+/// Headings shouldn't end with punctuation.
+#[derive(Debug, Clone)]
+pub struct NoTrailingPunctuation {
+    /// The characters which are reported.
+    pub punctuation: String,
+}
+
+impl Default for NoTrailingPunctuation {
+    fn default() -> Self {
+        Self {
+            punctuation: String::from(".,;:!。，；：！"),
+        }
+    }
+}
+
+impl Rule for NoTrailingPunctuation {
+    fn name(&self) -> &'static str {
+        "no-trailing-punctuation"
+    }
+    fn description(&self) -> &'static str {
+        "Trailing punctuation in heading"
+    }
+    fn check(&self, ast: &Ast<'_>, source: &str, _: &LineIndex<'_>, out: &mut Vec<Diagnostic>) {
+        for (_, heading) in headings(ast) {
+            let text = heading.plain_text();
+            let Some(last) = text.trim_end().chars().next_back() else {
+                continue;
+            };
+            if !self.punctuation.contains(last) {
+                continue;
+            }
+            let mut diagnostic = Diagnostic::new(
+                self.name(),
+                heading.span(),
+                format!("heading ends with punctuation `{last}`"),
+            );
+            if let Some(Tree::Text(Spanned { item, span })) =
+                heading.stream().and_then(|it| it.0.last())
+            {
+                if let (Some(start), true) = (verbatim(source, item, span), item.ends_with(last)) {
+                    let end = start + item.len();
+                    diagnostic = diagnostic.with_fix(Edit {
+                        span: Span(end - last.len_utf8()..end),
+                        replacement: String::new(),
+                    })
+                }
+            }
+            out.push(diagnostic)
+        }
+    }
+}
+
+/// URLs should be written as links, e.g `<https://example.com>`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoBareUrls;
+
+impl Rule for NoBareUrls {
+    fn name(&self) -> &'static str {
+        "no-bare-urls"
+    }
+    fn description(&self) -> &'static str {
+        "Bare URL used"
+    }
+    fn check(&self, ast: &Ast<'_>, source: &str, _: &LineIndex<'_>, out: &mut Vec<Diagnostic>) {
+        self.urls(ast, source, out)
+    }
+}
+
+impl NoBareUrls {
+    fn urls(&self, ast: &Ast<'_>, source: &str, out: &mut Vec<Diagnostic>) {
+        let mut rest = &ast.0[..];
+        while let Some(first) = rest.first() {
+            // text is split at escapes and delimiters, which may be in URLs
+            let len = rest
+                .iter()
+                .take_while(|it| matches!(it, Tree::Text(_)))
+                .count();
+            if len == 0 {
+                let skip = matches!(
+                    first,
+                    Tree::Group(Group {
+                        tag: Spanned {
+                            item: Tag::Link { .. }
+                                | Tag::Image { .. }
+                                | Tag::CodeBlock(_)
+                                | Tag::HtmlBlock
+                                | Tag::MetadataBlock(_),
+                            ..
+                        },
+                        ..
+                    })
+                );
+                if let (Some(stream), false) = (first.stream(), skip) {
+                    self.urls(stream, source, out)
+                }
+                rest = &rest[1..];
+                continue;
+            }
+            let (run, tail) = rest.split_at(len);
+            rest = tail;
+            let text = run.iter().map(Tree::plain_text).collect::<String>();
+            let span = Span(run[0].span().0.start..run[len - 1].span().0.end);
+            let start = verbatim(source, &text, &span);
+            for (offset, url) in bare_urls(&text) {
+                let message = format!("bare URL `{url}`");
+                out.push(match start {
+                    Some(start) => {
+                        let span = Span(start + offset..start + offset + url.len());
+                        Diagnostic::new(self.name(), span.clone(), message).with_fix(Edit {
+                            span,
+                            replacement: format!("<{url}>"),
+                        })
+                    }
+                    None => Diagnostic::new(self.name(), span.clone(), message),
+                })
+            }
+        }
+    }
+}
+
+/// `(offset, url)` for the `http` and `https` URLs in `text`.
+fn bare_urls(text: &str) -> Vec<(usize, &str)> {
+    let mut urls = vec![];
+    let mut from = 0;
+    while let Some(found) = ["http://", "https://"]
+        .iter()
+        .filter_map(|scheme| text[from..].find(scheme))
+        .min()
+    {
+        let start = from + found;
+        let rest = &text[start..];
+        let mut url = &rest[..rest
+            .find(|c: char| c.is_whitespace() || matches!(c, '<' | '>' | '"'))
+            .unwrap_or(rest.len())];
+        // trailing punctuation is probably part of the sentence
+        loop {
+            let trimmed = url.trim_end_matches(['.', ',', ':', ';', '!', '?', '\'', '*', '_']);
+            let trimmed = match trimmed.ends_with(')')
+                && trimmed.matches(')').count() > trimmed.matches('(').count()
+            {
+                true => &trimmed[..trimmed.len() - 1],
+                false => trimmed,
+            };
+            if trimmed == url {
+                break;
+            }
+            url = trimmed
+        }
+        let preceded = text[..start]
+            .chars()
+            .next_back()
+            .is_some_and(|c| c.is_alphanumeric());
+        if !preceded && url.len() > "https://".len() {
+            urls.push((start, url))
+        }
+        from = start + url.len().max(1)
+    }
+    urls
+}
+
+/// Links should have a destination.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoEmptyLinks;
+
+impl Rule for NoEmptyLinks {
+    fn name(&self) -> &'static str {
+        "no-empty-links"
+    }
+    fn description(&self) -> &'static str {
+        "No empty links"
+    }
+    fn check(&self, ast: &Ast<'_>, _: &str, _: &LineIndex<'_>, out: &mut Vec<Diagnostic>) {
+        walk(ast, &mut vec![], &mut |tree, _| {
+            if let Tree::Group(Group {
+                tag:
+                    Spanned {
+                        item: Tag::Link { dest_url, .. },
+                        ..
+                    },
+                ..
+            }) = tree
+            {
+                if dest_url.is_empty() || &**dest_url == "#" {
+                    out.push(Diagnostic::new(
+                        self.name(),
+                        tree.span(),
+                        "link has an empty destination",
+                    ))
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The rule and source text of each diagnostic.
+    fn lint(source: &str) -> Vec<(&'static str, &str)> {
+        Ast::new(source)
+            .lint(source)
+            .into_iter()
+            .map(|it| (it.rule, &source[it.span.0]))
+            .collect()
+    }
+
+    fn fixes(source: &str) -> Vec<(&str, String)> {
+        Ast::new(source)
+            .lint(source)
+            .into_iter()
+            .filter_map(|it| it.fix)
+            .map(|it| (&source[it.span.0], it.replacement))
+            .collect()
+    }
+
+    #[test]
+    fn heading_increment() {
+        let source = "# A\n\n### B\n\n## C\n\n#### D\n";
+        assert_eq!(
+            lint(source),
+            [
+                ("heading-increment", "### B\n"),
+                ("heading-increment", "#### D\n")
+            ]
+        );
+        assert_eq!(
+            fixes(source),
+            [("###", "##".into()), ("####", "###".into())]
+        );
+    }
+
+    #[test]
+    fn ul_style() {
+        let source = "* a\n* b\n\n- c\n\n+ d\n  - e\n";
+        assert_eq!(
+            lint(source),
+            [("ul-style", "-"), ("ul-style", "+"), ("ul-style", "-")]
+        );
+        assert!(fixes(source).iter().all(|(_, it)| it == "*"));
+    }
+
+    #[test]
+    fn headings() {
+        assert_eq!(
+            lint("# A\n\n## B\n\n## B\n\n# A\n"),
+            [
+                ("no-duplicate-heading", "## B\n"),
+                ("no-duplicate-heading", "# A\n"),
+                ("single-h1", "# A\n"),
+            ]
+        );
+        let source = "# A:\n\n## B.\n\n## C?\n";
+        assert_eq!(
+            lint(source),
+            [
+                ("no-trailing-punctuation", "# A:\n"),
+                ("no-trailing-punctuation", "## B.\n")
+            ]
+        );
+        assert_eq!(fixes(source), [(":", String::new()), (".", String::new())]);
+    }
+
+    #[test]
+    fn no_bare_urls() {
+        let source = "See https://example.com and <https://a.com> and [x](https://b.com), \
+                      `https://c.com`.\n\nhttps://d.com/x).\n";
+        assert_eq!(
+            lint(source),
+            [
+                ("no-bare-urls", "https://example.com"),
+                ("no-bare-urls", "https://d.com/x")
+            ]
+        );
+        assert_eq!(
+            fixes(source),
+            [
+                ("https://example.com", "<https://example.com>".into()),
+                ("https://d.com/x", "<https://d.com/x>".into())
+            ]
+        );
+    }
+
+    #[test]
+    fn no_empty_links() {
+        assert_eq!(
+            lint("[](a) [x]() [y](#) ![](i.png)\n"),
+            [("no-empty-links", "[x]()"), ("no-empty-links", "[y](#)")]
+        );
+    }
+
+    #[test]
+    fn config() {
+        let source = "# A\n\n### B\n\n# C\n";
+        let linter = Linter::new().config(
+            Config::new()
+                .disable("single-h1")
+                .severity("heading-increment", Severity::Error),
+        );
+        let diagnostics = linter.lint(&Ast::new(source), source);
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(|it| (it.rule, it.severity))
+                .collect::<Vec<_>>(),
+            [("heading-increment", Severity::Error)]
+        );
+        let only = Linter::new().config(Config::new().enable_all(false).enable("single-h1"));
+        assert_eq!(only.lint(&Ast::new(source), source).len(), 1);
+    }
+
+    #[test]
+    fn disable_and_enable() {
+        assert_eq!(
+            lint(
+                "<!-- md-ast-disable no-bare-urls -->\nhttps://a.com\n\
+                 <!-- md-ast-enable -->\nhttps://b.com\n"
+            ),
+            [("no-bare-urls", "https://b.com")]
+        );
+        // everything but `no-bare-urls` stays disabled
+        assert_eq!(
+            lint(
+                "<!-- md-ast-disable -->\nhttps://a.com\n\
+                 <!-- md-ast-enable no-bare-urls -->\n# A\n\n# B\n\nhttps://b.com\n"
+            ),
+            [("no-bare-urls", "https://b.com")]
+        );
+        // until the end of the document
+        assert_eq!(lint("<!-- md-ast-disable -->\n# A\n\n# B\n"), []);
+    }
+
+    #[test]
+    fn disable_next_line() {
+        assert_eq!(
+            lint("<!-- md-ast-disable-next-line -->\nhttps://a.com https://b.com\nhttps://c.com\n"),
+            [("no-bare-urls", "https://c.com")]
+        );
+        assert_eq!(
+            lint("Text <!-- md-ast-disable-next-line no-bare-urls -->\nhttps://a.com\n"),
+            []
+        );
+    }
+
+    #[test]
+    fn unused_suppressions() {
+        let source = "<!-- md-ast-disable single-h1 -->\n# A\n<!-- md-ast-enable -->\n\
+                      <!-- md-ast-disable -->\n<!-- md-ast-enable -->\n";
+        assert_eq!(
+            lint(source),
+            [
+                ("unused-suppression", "<!-- md-ast-disable single-h1 -->\n"),
+                ("unused-suppression", "<!-- md-ast-disable -->\n")
+            ]
+        );
+        let linter = Linter::new().config(Config::new().disable(UNUSED_SUPPRESSION));
+        assert_eq!(linter.lint(&Ast::new(source), source), []);
+    }
+}
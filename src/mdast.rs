@@ -0,0 +1,994 @@
+//! Conversion to and from [mdast](https://github.com/syntax-tree/mdast),
+//! as used by [remark](https://github.com/remarkjs/remark).
+//!
+//! Each node has a `position` derived from its [`Span`].
+//! Like in JavaScript, `offset`s and `column`s count UTF-16 code units,
+//! unless another [`Unit`] is given.
+//!
+//! Some information does not survive the round trip:
+//! - Reference links and autolinks are exported as resolved `link`s and `image`s.
+//! - Image descriptions are exported as plain `alt` text.
+//! - Block quote kinds (GFM alerts) are dropped.
+//! - Fenced code blocks without an info string are imported as indented code blocks,
+//!   and whitespace in info strings is collapsed to a space between `lang` and `meta`.
+//! - Heading attributes are exported as `hProperties`, an object,
+//!   so they're imported sorted by name, with empty values dropped.
+//! - [`Tree::BrokenLink`]s are exported as their text.
+//! - [`Tree::DisplayMath`] is exported as a `math` node if it is alone in its paragraph,
+//!   and as `inlineMath` otherwise.
+//! - Newlines in text, like from `&#10;`, are imported as [`Tree::SoftBreak`]s.
+//! - HTML within a paragraph is imported as [`Tree::InlineHtml`],
+//!   which [`pulldown_cmark`] splits differently when it spans lines.
+//!
+//! Imported `linkReference`s and `imageReference`s are resolved against the
+//! document's `definition`s.
+
+use std::{collections::HashMap, fmt};
+
+use pulldown_cmark::{
+    Alignment, CodeBlockKind, CowStr, HeadingLevel, LinkType, MetadataBlockKind, Tag,
+};
+use serde_json::{json, Map, Value};
+
+use crate::{
+    line_index::{LineCol, LineIndex, Unit},
+    Ast, Group, Span, Spanned, Tree,
+};
+
+/// An error importing mdast.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MdastError {
+    pub message: String,
+}
+
+impl fmt::Display for MdastError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for MdastError {}
+
+fn error<T>(message: impl fmt::Display) -> Result<T, MdastError> {
+    Err(MdastError {
+        message: message.to_string(),
+    })
+}
+
+impl Ast<'_> {
+    /// Convert to an mdast `root` node.
+    ///
+    /// `source` is the text this tree was parsed from, for computing positions.
+    pub fn to_mdast(&self, source: &str) -> Value {
+        self.to_mdast_ext(source, Unit::Utf16)
+    }
+    /// Like [`Ast::to_mdast`], with positions counting `unit`s.
+    pub fn to_mdast_ext(&self, source: &str, unit: Unit) -> Value {
+        let exporter = Exporter {
+            lines: &LineIndex::new(source),
+            unit,
+        };
+        json!({
+            "type": "root",
+            "children": exporter.flow(&self.0),
+            "position": exporter.position(&Span(0..source.len())),
+        })
+    }
+}
+
+impl Ast<'static> {
+    /// Convert from an mdast `root` node.
+    ///
+    /// `source` is the text the positions in `root` refer to, for converting them to spans.
+    pub fn from_mdast(root: &Value, source: &str) -> Result<Self, MdastError> {
+        Self::from_mdast_ext(root, source, Unit::Utf16)
+    }
+    /// Like [`Ast::from_mdast`], with positions counting `unit`s.
+    pub fn from_mdast_ext(root: &Value, source: &str, unit: Unit) -> Result<Self, MdastError> {
+        if ty(root)? != "root" {
+            return error(format_args!("expected a root node, not {}", ty(root)?));
+        }
+        let mut definitions = HashMap::new();
+        collect_definitions(root, &mut definitions);
+        Importer {
+            definitions,
+            lines: LineIndex::new(source),
+            unit,
+        }
+        .children(root, true)
+        .map(Ast)
+    }
+}
+
+struct Exporter<'i, 's> {
+    lines: &'i LineIndex<'s>,
+    unit: Unit,
+}
+
+impl Exporter<'_, '_> {
+    fn position(&self, span: &Span) -> Value {
+        let point = |offset: usize| {
+            let LineCol { line, column } = self.lines.line_col_in(offset, self.unit);
+            let offset = self.lines.to_unit(offset, self.unit);
+            json!({ "line": line, "column": column, "offset": offset })
+        };
+        json!({ "start": point(span.0.start), "end": point(span.0.end) })
+    }
+    fn node(&self, ty: &str, span: &Span, fields: Value) -> Value {
+        let mut map = Map::new();
+        map.insert("type".into(), ty.into());
+        if let Value::Object(fields) = fields {
+            map.extend(fields)
+        }
+        map.insert("position".into(), self.position(span));
+        Value::Object(map)
+    }
+    /// A `math` node for `trees` if they're a lone [`Tree::DisplayMath`].
+    fn display_math(&self, trees: &[Tree<'_>], span: &Span) -> Option<Value> {
+        match trees {
+            [Tree::DisplayMath(Spanned { item, .. })] => {
+                Some(self.node("math", span, json!({ "value": &**item })))
+            }
+            _ => None,
+        }
+    }
+    /// Export `trees` as flow content, wrapping runs of inline nodes in paragraphs.
+    fn flow(&self, trees: &[Tree<'_>]) -> Vec<Value> {
+        let mut out = vec![];
+        let mut rest = trees;
+        while let Some(first) = rest.first() {
+            let len = match first.is_block() {
+                true => 1,
+                false => rest.iter().take_while(|it| !it.is_block()).count(),
+            };
+            let (run, tail) = rest.split_at(len);
+            rest = tail;
+            match first.is_block() {
+                true => out.extend(self.tree(first)),
+                false => {
+                    let span = Span(first.span().0.start..run[len - 1].span().0.end);
+                    if let Some(math) = self.display_math(run, &span) {
+                        out.push(math);
+                        continue;
+                    }
+                    let children = self.phrasing(run);
+                    if !children.is_empty() {
+                        out.push(self.node("paragraph", &span, json!({ "children": children })))
+                    }
+                }
+            }
+        }
+        out
+    }
+    /// Export `trees` as phrasing content, merging text and soft breaks.
+    fn phrasing(&self, trees: &[Tree<'_>]) -> Vec<Value> {
+        fn flatten<'t, 'a>(trees: &'t [Tree<'a>], out: &mut Vec<&'t Tree<'a>>) {
+            for tree in trees {
+                match tree {
+                    Tree::BrokenLink(_, stream) => flatten(&stream.0, out),
+                    Tree::TaskListMarker(_) => {}
+                    other => out.push(other),
+                }
+            }
+        }
+        let mut flat = vec![];
+        flatten(trees, &mut flat);
+        let mut out = vec![];
+        let mut text: Option<(String, Span)> = None;
+        for tree in flat {
+            let (s, span) = match tree {
+                Tree::Text(Spanned { item, span }) => (&**item, span),
+                Tree::SoftBreak(span) => ("\n", span),
+                other => {
+                    if let Some((value, span)) = text.take() {
+                        out.push(self.node("text", &span, json!({ "value": value })))
+                    }
+                    out.extend(self.tree(other));
+                    continue;
+                }
+            };
+            match &mut text {
+                Some((value, Span(range))) => {
+                    value.push_str(s);
+                    range.end = span.0.end
+                }
+                None => text = Some((s.into(), span.clone())),
+            }
+        }
+        if let Some((value, span)) = text {
+            out.push(self.node("text", &span, json!({ "value": value })))
+        }
+        out
+    }
+    fn tree(&self, tree: &Tree<'_>) -> Option<Value> {
+        let span = tree.span();
+        let value = |ty, item: &CowStr<'_>| Some(self.node(ty, &span, json!({ "value": &**item })));
+        match tree {
+            Tree::Group(group) => Some(self.group(group, &span)),
+            Tree::Text(Spanned { item, .. }) => value("text", item),
+            Tree::Code(Spanned { item, .. }) => value("inlineCode", item),
+            Tree::Html(Spanned { item, .. }) | Tree::InlineHtml(Spanned { item, .. }) => {
+                value("html", item)
+            }
+            Tree::FootnoteReference(Spanned { item, .. }) => Some(self.node(
+                "footnoteReference",
+                &span,
+                json!({ "identifier": item.to_lowercase(), "label": &**item }),
+            )),
+            Tree::SoftBreak(_) => value("text", &CowStr::Borrowed("\n")),
+            Tree::HardBreak(_) => Some(self.node("break", &span, json!({}))),
+            Tree::Rule(_) => Some(self.node("thematicBreak", &span, json!({}))),
+            Tree::TaskListMarker(_) => None,
+            Tree::InlineMath(Spanned { item, .. }) => value("inlineMath", item),
+            // a lone `math` is exported by `flow` or `group`
+            Tree::DisplayMath(Spanned { item, .. }) => value("inlineMath", item),
+            // flattened by `phrasing`
+            Tree::BrokenLink(..) => None,
+        }
+    }
+    fn group(&self, group: &Group<'_>, span: &Span) -> Value {
+        let stream = &group.stream.0;
+        let children = || self.phrasing(stream);
+        let fields = match &group.tag.item {
+            Tag::Paragraph => match self.display_math(stream, span) {
+                Some(math) => return math,
+                None => ("paragraph", json!({ "children": children() })),
+            },
+            Tag::Heading {
+                level,
+                id,
+                classes,
+                attrs,
+            } => {
+                let mut fields = json!({ "depth": *level as usize, "children": children() });
+                let mut properties = Map::new();
+                if let Some(id) = id {
+                    properties.insert("id".into(), (**id).into());
+                }
+                if !classes.is_empty() {
+                    properties.insert(
+                        "className".into(),
+                        classes.iter().map(|it| &**it).collect::<Vec<_>>().into(),
+                    );
+                }
+                for (k, v) in attrs {
+                    properties.insert((**k).into(), v.as_deref().unwrap_or_default().into());
+                }
+                if !properties.is_empty() {
+                    fields["data"] = json!({ "hProperties": properties })
+                }
+                ("heading", fields)
+            }
+            Tag::BlockQuote(_) => ("blockquote", json!({ "children": self.flow(stream) })),
+            Tag::CodeBlock(kind) => {
+                let (lang, meta) = match kind {
+                    CodeBlockKind::Fenced(info) if !info.trim().is_empty() => {
+                        match info.trim().split_once(char::is_whitespace) {
+                            Some((lang, meta)) => (Some(lang), Some(meta.trim())),
+                            None => (Some(info.trim()), None),
+                        }
+                    }
+                    _ => (None, None),
+                };
+                let value = group.stream.plain_text();
+                let value = value.strip_suffix('\n').unwrap_or(&value);
+                (
+                    "code",
+                    json!({ "lang": lang, "meta": meta, "value": value }),
+                )
+            }
+            Tag::HtmlBlock => {
+                let value = stream
+                    .iter()
+                    .filter_map(|it| match it {
+                        Tree::Html(Spanned { item, .. }) | Tree::Text(Spanned { item, .. }) => {
+                            Some(&**item)
+                        }
+                        _ => None,
+                    })
+                    .collect::<String>();
+                let value = value.strip_suffix('\n').unwrap_or(&value);
+                ("html", json!({ "value": value }))
+            }
+            Tag::List(start) => {
+                let spread = stream.iter().any(|it| it.stream().is_some_and(is_loose));
+                let items = stream
+                    .iter()
+                    .map(|it| match it {
+                        Tree::Group(item @ Group { tag, .. }) if tag.item == Tag::Item => {
+                            self.item(item, spread)
+                        }
+                        other => self.tree(other).unwrap_or(Value::Null),
+                    })
+                    .collect::<Vec<_>>();
+                (
+                    "list",
+                    json!({
+                        "ordered": start.is_some(),
+                        "start": start,
+                        "spread": spread,
+                        "children": items,
+                    }),
+                )
+            }
+            Tag::Item => return self.item(group, is_loose(&group.stream)),
+            Tag::FootnoteDefinition(label) => (
+                "footnoteDefinition",
+                json!({
+                    "identifier": label.to_lowercase(),
+                    "label": &**label,
+                    "children": self.flow(stream),
+                }),
+            ),
+            Tag::Table(alignments) => {
+                let align = alignments
+                    .iter()
+                    .map(|it| match it {
+                        Alignment::None => None,
+                        Alignment::Left => Some("left"),
+                        Alignment::Center => Some("center"),
+                        Alignment::Right => Some("right"),
+                    })
+                    .collect::<Vec<_>>();
+                let rows = stream
+                    .iter()
+                    .filter_map(|it| self.tree(it))
+                    .collect::<Vec<_>>();
+                ("table", json!({ "align": align, "children": rows }))
+            }
+            Tag::TableHead | Tag::TableRow => {
+                let cells = stream
+                    .iter()
+                    .filter_map(|it| self.tree(it))
+                    .collect::<Vec<_>>();
+                ("tableRow", json!({ "children": cells }))
+            }
+            Tag::TableCell => ("tableCell", json!({ "children": children() })),
+            Tag::Emphasis => ("emphasis", json!({ "children": children() })),
+            Tag::Strong => ("strong", json!({ "children": children() })),
+            Tag::Strikethrough => ("delete", json!({ "children": children() })),
+            Tag::Link {
+                dest_url, title, ..
+            } => (
+                "link",
+                json!({
+                    "url": &**dest_url,
+                    "title": (!title.is_empty()).then_some(&**title),
+                    "children": children(),
+                }),
+            ),
+            Tag::Image {
+                dest_url, title, ..
+            } => (
+                "image",
+                json!({
+                    "url": &**dest_url,
+                    "title": (!title.is_empty()).then_some(&**title),
+                    "alt": group.stream.plain_text(),
+                }),
+            ),
+            Tag::MetadataBlock(kind) => {
+                let value = group.stream.plain_text();
+                let value = value.strip_suffix('\n').unwrap_or(&value);
+                match kind {
+                    MetadataBlockKind::YamlStyle => ("yaml", json!({ "value": value })),
+                    MetadataBlockKind::PlusesStyle => ("toml", json!({ "value": value })),
+                }
+            }
+        };
+        self.node(fields.0, span, fields.1)
+    }
+    fn item(&self, item: &Group<'_>, spread: bool) -> Value {
+        let checked = item
+            .stream
+            .0
+            .iter()
+            .chain(
+                item.stream
+                    .0
+                    .first()
+                    .and_then(Tree::stream)
+                    .into_iter()
+                    .flat_map(|it| &it.0),
+            )
+            .find_map(|it| match it {
+                Tree::TaskListMarker(Spanned { item, .. }) => Some(*item),
+                _ => None,
+            });
+        self.node(
+            "listItem",
+            &Span(item.tag.span.0.start..item.end_span.0.end),
+            json!({
+                "spread": spread,
+                "checked": checked,
+                "children": self.flow(&item.stream.0),
+            }),
+        )
+    }
+}
+
+/// Whether the list item's contents are wrapped in paragraphs.
+fn is_loose(item: &Ast<'_>) -> bool {
+    item.0.iter().any(|it| {
+        matches!(
+            it,
+            Tree::Group(Group {
+                tag: Spanned {
+                    item: Tag::Paragraph,
+                    ..
+                },
+                ..
+            })
+        )
+    })
+}
+
+fn ty(node: &Value) -> Result<&str, MdastError> {
+    match node.get("type").and_then(Value::as_str) {
+        Some(it) => Ok(it),
+        None => error("node has no type"),
+    }
+}
+
+fn string(node: &Value, key: &str) -> Option<String> {
+    node.get(key).and_then(Value::as_str).map(Into::into)
+}
+
+fn collect_definitions(node: &Value, definitions: &mut HashMap<String, (String, String)>) {
+    if let (Ok("definition"), Some(identifier)) = (ty(node), string(node, "identifier")) {
+        definitions
+            .entry(identifier.to_lowercase())
+            .or_insert_with(|| {
+                (
+                    string(node, "url").unwrap_or_default(),
+                    string(node, "title").unwrap_or_default(),
+                )
+            });
+    }
+    for child in node
+        .get("children")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+    {
+        collect_definitions(child, definitions)
+    }
+}
+
+struct Importer<'s> {
+    /// `url` and `title` by lowercase identifier.
+    definitions: HashMap<String, (String, String)>,
+    lines: LineIndex<'s>,
+    unit: Unit,
+}
+
+impl Importer<'_> {
+    /// The span of `node`'s position in the source.
+    fn span(&self, node: &Value) -> Span {
+        let offset = |point| {
+            let it = node
+                .pointer(&format!("/position/{point}/offset"))
+                .and_then(Value::as_u64)?;
+            self.lines.from_unit(it as usize, self.unit)
+        };
+        match (offset("start"), offset("end")) {
+            (Some(start), Some(end)) => Span(start..end),
+            (Some(start), None) => Span(start..start),
+            _ => Span(0..0),
+        }
+    }
+    fn children(&self, node: &Value, flow: bool) -> Result<Vec<Tree<'static>>, MdastError> {
+        let mut out = vec![];
+        for child in node
+            .get("children")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+        {
+            self.node(child, flow, &mut out)?
+        }
+        Ok(out)
+    }
+    fn group(&self, tag: Tag<'static>, node: &Value, stream: Vec<Tree<'static>>) -> Tree<'static> {
+        let span = self.span(node);
+        Tree::Group(Group {
+            tag: Spanned {
+                item: tag,
+                span: span.clone(),
+            },
+            stream: Ast(stream),
+            end_span: span,
+        })
+    }
+    fn node(
+        &self,
+        node: &Value,
+        flow: bool,
+        out: &mut Vec<Tree<'static>>,
+    ) -> Result<(), MdastError> {
+        let span = self.span(node);
+        let value = || CowStr::from(string(node, "value").unwrap_or_default());
+        let text = |value: CowStr<'static>| {
+            Tree::Text(Spanned {
+                item: value,
+                span: span.clone(),
+            })
+        };
+        let tree = match ty(node)? {
+            "paragraph" => self.group(Tag::Paragraph, node, self.children(node, false)?),
+            "heading" => {
+                let depth = node.get("depth").and_then(Value::as_u64).unwrap_or(1);
+                let Ok(level) = HeadingLevel::try_from(depth as usize) else {
+                    return error(format_args!("invalid heading depth {depth}"));
+                };
+                let mut id = None;
+                let mut classes = vec![];
+                let mut attrs = vec![];
+                for (k, v) in node
+                    .pointer("/data/hProperties")
+                    .and_then(Value::as_object)
+                    .into_iter()
+                    .flatten()
+                {
+                    match (k.as_str(), v) {
+                        ("id", Value::String(it)) => id = Some(it.clone().into()),
+                        ("className", Value::Array(it)) => classes.extend(
+                            it.iter()
+                                .filter_map(Value::as_str)
+                                .map(|it| CowStr::from(it.to_owned())),
+                        ),
+                        (_, Value::String(v)) => attrs
+                            .push((k.clone().into(), (!v.is_empty()).then(|| v.clone().into()))),
+                        _ => {}
+                    }
+                }
+                let tag = Tag::Heading {
+                    level,
+                    id,
+                    classes,
+                    attrs,
+                };
+                self.group(tag, node, self.children(node, false)?)
+            }
+            "thematicBreak" => Tree::Rule(span),
+            "blockquote" => self.group(Tag::BlockQuote(None), node, self.children(node, true)?),
+            "list" => {
+                let start = match node.get("ordered").and_then(Value::as_bool) {
+                    Some(true) => Some(node.get("start").and_then(Value::as_u64).unwrap_or(1)),
+                    _ => None,
+                };
+                let spread = node.get("spread").and_then(Value::as_bool) == Some(true);
+                let mut items = vec![];
+                for item in node
+                    .get("children")
+                    .and_then(Value::as_array)
+                    .into_iter()
+                    .flatten()
+                {
+                    items.push(self.item(item, spread)?)
+                }
+                self.group(Tag::List(start), node, items)
+            }
+            "listItem" => {
+                let spread = node.get("spread").and_then(Value::as_bool) == Some(true);
+                self.item(node, spread)?
+            }
+            "code" => {
+                let kind = match (string(node, "lang"), string(node, "meta")) {
+                    (Some(lang), Some(meta)) => {
+                        CodeBlockKind::Fenced(format!("{lang} {meta}").into())
+                    }
+                    (Some(lang), None) => CodeBlockKind::Fenced(lang.into()),
+                    (None, _) => CodeBlockKind::Indented,
+                };
+                let mut value = string(node, "value").unwrap_or_default();
+                if !value.is_empty() {
+                    value.push('\n')
+                }
+                self.group(Tag::CodeBlock(kind), node, vec![text(value.into())])
+            }
+            "html" if flow => {
+                let mut value = string(node, "value").unwrap_or_default();
+                value.push('\n');
+                let html = Tree::Html(Spanned {
+                    item: value.into(),
+                    span: span.clone(),
+                });
+                self.group(Tag::HtmlBlock, node, vec![html])
+            }
+            "html" => Tree::InlineHtml(Spanned {
+                item: value(),
+                span,
+            }),
+            "table" => {
+                let alignments = node
+                    .get("align")
+                    .and_then(Value::as_array)
+                    .into_iter()
+                    .flatten()
+                    .map(|it| match it.as_str() {
+                        Some("left") => Alignment::Left,
+                        Some("center") => Alignment::Center,
+                        Some("right") => Alignment::Right,
+                        _ => Alignment::None,
+                    })
+                    .collect();
+                let mut rows = self.children(node, true)?;
+                if let Some(Tree::Group(Group { tag, .. })) = rows.first_mut() {
+                    tag.item = Tag::TableHead
+                }
+                self.group(Tag::Table(alignments), node, rows)
+            }
+            "tableRow" => self.group(Tag::TableRow, node, self.children(node, true)?),
+            "tableCell" => self.group(Tag::TableCell, node, self.children(node, false)?),
+            "emphasis" => self.group(Tag::Emphasis, node, self.children(node, false)?),
+            "strong" => self.group(Tag::Strong, node, self.children(node, false)?),
+            "delete" => self.group(Tag::Strikethrough, node, self.children(node, false)?),
+            ty @ ("link" | "linkReference" | "image" | "imageReference") => {
+                let image = ty.starts_with("image");
+                let (link_type, dest_url, title, id) = match ty.ends_with("Reference") {
+                    false => (
+                        LinkType::Inline,
+                        string(node, "url").unwrap_or_default(),
+                        string(node, "title").unwrap_or_default(),
+                        String::new(),
+                    ),
+                    true => {
+                        let identifier = string(node, "identifier").unwrap_or_default();
+                        let label = string(node, "label").unwrap_or_else(|| identifier.clone());
+                        let link_type = match node.get("referenceType").and_then(Value::as_str) {
+                            Some("collapsed") => LinkType::Collapsed,
+                            Some("shortcut") => LinkType::Shortcut,
+                            _ => LinkType::Reference,
+                        };
+                        match self.definitions.get(&identifier.to_lowercase()) {
+                            Some((url, title)) => (link_type, url.clone(), title.clone(), label),
+                            // pulldown_cmark leaves these as text
+                            None => {
+                                let children = match image {
+                                    true => {
+                                        vec![text(string(node, "alt").unwrap_or_default().into())]
+                                    }
+                                    false => self.children(node, false)?,
+                                };
+                                out.extend(children);
+                                return Ok(());
+                            }
+                        }
+                    }
+                };
+                let (dest_url, title, id) = (dest_url.into(), title.into(), id.into());
+                match image {
+                    true => {
+                        let alt = string(node, "alt").unwrap_or_default();
+                        let stream = match alt.is_empty() {
+                            true => vec![],
+                            false => vec![text(alt.into())],
+                        };
+                        let tag = Tag::Image {
+                            link_type,
+                            dest_url,
+                            title,
+                            id,
+                        };
+                        self.group(tag, node, stream)
+                    }
+                    false => {
+                        let tag = Tag::Link {
+                            link_type,
+                            dest_url,
+                            title,
+                            id,
+                        };
+                        self.group(tag, node, self.children(node, false)?)
+                    }
+                }
+            }
+            // pulldown_cmark resolves these while parsing
+            "definition" => return Ok(()),
+            "footnoteDefinition" => {
+                let label = string(node, "label")
+                    .or_else(|| string(node, "identifier"))
+                    .unwrap_or_default();
+                let tag = Tag::FootnoteDefinition(label.into());
+                self.group(tag, node, self.children(node, true)?)
+            }
+            "footnoteReference" => Tree::FootnoteReference(Spanned {
+                item: string(node, "label")
+                    .or_else(|| string(node, "identifier"))
+                    .unwrap_or_default()
+                    .into(),
+                span,
+            }),
+            "text" => {
+                let value = string(node, "value").unwrap_or_default();
+                // only split the span if it matches the value
+                let exact = span.0.len() == value.len();
+                let mut offset = span.0.start;
+                for (ix, line) in value.split('\n').enumerate() {
+                    if ix > 0 {
+                        out.push(Tree::SoftBreak(match exact {
+                            true => Span(offset..offset + 1),
+                            false => span.clone(),
+                        }));
+                        offset += 1;
+                    }
+                    if !line.is_empty() {
+                        out.push(Tree::Text(Spanned {
+                            item: line.to_owned().into(),
+                            span: match exact {
+                                true => Span(offset..offset + line.len()),
+                                false => span.clone(),
+                            },
+                        }))
+                    }
+                    offset += line.len();
+                }
+                return Ok(());
+            }
+            "break" => Tree::HardBreak(span),
+            "inlineCode" => Tree::Code(Spanned {
+                item: value(),
+                span,
+            }),
+            "inlineMath" => Tree::InlineMath(Spanned {
+                item: value(),
+                span,
+            }),
+            "math" => {
+                let math = Tree::DisplayMath(Spanned {
+                    item: value(),
+                    span,
+                });
+                match flow {
+                    true => self.group(Tag::Paragraph, node, vec![math]),
+                    false => math,
+                }
+            }
+            ty @ ("yaml" | "toml") => {
+                let kind = match ty {
+                    "yaml" => MetadataBlockKind::YamlStyle,
+                    _ => MetadataBlockKind::PlusesStyle,
+                };
+                let mut value = string(node, "value").unwrap_or_default();
+                value.push('\n');
+                self.group(Tag::MetadataBlock(kind), node, vec![text(value.into())])
+            }
+            other => return error(format_args!("unsupported node type {other}")),
+        };
+        out.push(tree);
+        Ok(())
+    }
+    fn item(&self, node: &Value, spread: bool) -> Result<Tree<'static>, MdastError> {
+        if ty(node)? != "listItem" {
+            return error(format_args!("expected a listItem, not {}", ty(node)?));
+        }
+        let mut stream = vec![];
+        for child in self.children(node, true)? {
+            match child {
+                // tight lists don't have paragraphs
+                Tree::Group(Group {
+                    tag:
+                        Spanned {
+                            item: Tag::Paragraph,
+                            ..
+                        },
+                    stream: Ast(inner),
+                    ..
+                }) if !spread => stream.extend(inner),
+                other => stream.push(other),
+            }
+        }
+        if let Some(checked) = node.get("checked").and_then(Value::as_bool) {
+            let target = match stream.first_mut() {
+                Some(Tree::Group(Group {
+                    tag:
+                        Spanned {
+                            item: Tag::Paragraph,
+                            ..
+                        },
+                    stream,
+                    ..
+                })) => &mut stream.0,
+                _ => &mut stream,
+            };
+            let start = target
+                .first()
+                .map_or(self.span(node).0.start, |it| it.span().0.start);
+            target.insert(
+                0,
+                Tree::TaskListMarker(Spanned {
+                    item: checked,
+                    span: Span(start..start),
+                }),
+            )
+        }
+        Ok(self.group(Tag::Item, node, stream))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pulldown_cmark::Options;
+
+    use super::*;
+    use crate::{corpus, structural::Structural};
+
+    #[test]
+    fn utf16_positions() {
+        let source = "é 😀 *x*\n";
+        let ast = Ast::new(source);
+        let root = ast.to_mdast(source);
+        let emphasis = &root["children"][0]["children"][1];
+        assert_eq!(emphasis["type"], "emphasis");
+        assert_eq!(
+            emphasis["position"]["start"],
+            json!({ "line": 1, "column": 6, "offset": 5 })
+        );
+        assert_eq!(emphasis["position"]["end"]["offset"], 8);
+
+        let bytes = ast.to_mdast_ext(source, Unit::Bytes);
+        let emphasis = &bytes["children"][0]["children"][1];
+        assert_eq!(
+            emphasis["position"]["start"],
+            json!({ "line": 1, "column": 9, "offset": 8 })
+        );
+
+        for (root, unit) in [(root, Unit::Utf16), (bytes, Unit::Bytes)] {
+            let imported = Ast::from_mdast_ext(&root, source, unit).unwrap();
+            assert_eq!(imported.0[0].stream().unwrap().0[1].span(), Span(8..11));
+        }
+    }
+
+    #[test]
+    fn display_math() {
+        let options = Options::ENABLE_MATH;
+        let source = "$$x$$\n\na $$y$$\n";
+        let root = Ast::new_ext(source, options).to_mdast(source);
+        assert_eq!(root["children"][0]["type"], "math");
+        assert_eq!(root["children"][0]["value"], "x");
+        let paragraph = &root["children"][1];
+        assert_eq!(paragraph["type"], "paragraph");
+        assert_eq!(paragraph["children"][1]["type"], "inlineMath");
+        assert_eq!(paragraph["children"][1]["value"], "y");
+
+        let imported = Ast::from_mdast(&root, source).unwrap();
+        assert!(imported.structurally_eq(&Ast::new_ext("$$x$$\n\na $y$\n", options)));
+    }
+
+    macro_rules! fixtures {
+        ($($name:literal),* $(,)?) => {
+            [$((
+                $name,
+                include_str!(concat!("../tests/fixtures/mdast/", $name, ".md")),
+                include_str!(concat!("../tests/fixtures/mdast/", $name, ".json")),
+            )),*]
+        };
+    }
+
+    #[test]
+    fn golden() {
+        let options = Options::ENABLE_HEADING_ATTRIBUTES
+            | Options::ENABLE_TABLES
+            | Options::ENABLE_FOOTNOTES
+            | Options::ENABLE_TASKLISTS;
+        let fixtures = fixtures!("headings", "lists", "tables", "footnotes", "links", "code");
+        for (name, markdown, json) in fixtures {
+            let ast = Ast::new_ext(markdown, options);
+            let root = ast.to_mdast(markdown);
+            assert_eq!(
+                serde_json::to_string_pretty(&root).unwrap(),
+                json.trim_end(),
+                "{name}"
+            );
+            let imported = Ast::from_mdast(&serde_json::from_str(json).unwrap(), markdown).unwrap();
+            assert!(
+                Structural::new()
+                    .merge_text(true)
+                    .eq(&imported, &lossy(&ast)),
+                "{name}"
+            );
+        }
+    }
+
+    /// `ast`, with what doesn't survive the round trip removed.
+    fn lossy(ast: &Ast<'_>) -> Ast<'static> {
+        let mut out = vec![];
+        let alone = matches!(&*ast.0, [Tree::DisplayMath(_)]);
+        for tree in &ast.0 {
+            let tree = match tree.clone().into_static() {
+                Tree::BrokenLink(_, stream) => {
+                    out.extend(lossy(&stream).0);
+                    continue;
+                }
+                Tree::DisplayMath(math) if !alone => Tree::InlineMath(math),
+                Tree::Group(mut group) => {
+                    match &mut group.tag.item {
+                        Tag::Link { link_type, id, .. } => {
+                            (*link_type, *id) = (LinkType::Inline, "".into())
+                        }
+                        Tag::Image { link_type, id, .. } => {
+                            (*link_type, *id) = (LinkType::Inline, "".into());
+                            let alt = group.stream.plain_text();
+                            group.stream = Ast(match alt.is_empty() {
+                                true => vec![],
+                                false => vec![Tree::Text(Spanned {
+                                    item: alt.into(),
+                                    span: Span(0..0),
+                                })],
+                            })
+                        }
+                        Tag::Heading { attrs, .. } => {
+                            let sorted = attrs
+                                .drain(..)
+                                .map(|(k, v)| (k.to_string(), (k, v.filter(|it| !it.is_empty()))))
+                                .collect::<std::collections::BTreeMap<_, _>>();
+                            attrs.extend(sorted.into_values())
+                        }
+                        Tag::BlockQuote(kind) => *kind = None,
+                        Tag::CodeBlock(kind) => {
+                            if let CodeBlockKind::Fenced(info) = kind {
+                                *kind = match info.trim().split_once(char::is_whitespace) {
+                                    _ if info.trim().is_empty() => CodeBlockKind::Indented,
+                                    Some((lang, meta)) => CodeBlockKind::Fenced(
+                                        format!("{lang} {}", meta.trim()).into(),
+                                    ),
+                                    None => CodeBlockKind::Fenced(info.trim().to_owned().into()),
+                                }
+                            }
+                        }
+                        _ => {}
+                    }
+                    group.stream = lossy(&group.stream);
+                    if !matches!(
+                        group.tag.item,
+                        Tag::HtmlBlock | Tag::CodeBlock(_) | Tag::MetadataBlock(_)
+                    ) {
+                        group.stream = Ast(group.stream.0.into_iter().flat_map(phrasing).collect())
+                    }
+                    Tree::Group(group)
+                }
+                other => other,
+            };
+            out.push(tree)
+        }
+        Ast(out)
+    }
+
+    fn phrasing(tree: Tree<'static>) -> Vec<Tree<'static>> {
+        match tree {
+            Tree::Html(html) => vec![Tree::InlineHtml(html)],
+            Tree::Text(Spanned { item, span }) if item.contains('\n') => {
+                let mut out = vec![];
+                for (ix, line) in item.split('\n').enumerate() {
+                    if ix > 0 {
+                        out.push(Tree::SoftBreak(span.clone()))
+                    }
+                    if !line.is_empty() {
+                        out.push(Tree::Text(Spanned {
+                            item: line.to_owned().into(),
+                            span: span.clone(),
+                        }))
+                    }
+                }
+                out
+            }
+            other => vec![other],
+        }
+    }
+
+    #[test]
+    fn corpus_round_trip() {
+        let structural = Structural::new().merge_text(true);
+        let mut failed = vec![];
+        for case in corpus::cases() {
+            let ast = Ast::new_ext(&case.markdown, case.options);
+            let root = ast.to_mdast(&case.markdown);
+            let imported = Ast::from_mdast(&root, &case.markdown).unwrap();
+            if !structural.eq(&imported, &lossy(&ast)) {
+                failed.push(case.name)
+            }
+        }
+        assert_eq!(failed, Vec::<String>::new());
+    }
+}
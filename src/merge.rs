@@ -0,0 +1,458 @@
+//! Three-way merges of [`Ast`]s, at block granularity.
+//!
+//! Blocks are matched between the base and each side as in a [`diff`](crate::diff).
+//! Where only one side changed a run of blocks, that side is taken.
+//! Where both changed it in place, each block is merged separately,
+//! and the children of block quotes, lists, list items, tables and footnote definitions
+//! which contain only blocks are merged in turn.
+//! Conflicting list items or table rows are a conflict of the whole list or table.
+//! Otherwise, the run is a [`Conflict`].
+//! The merged [`Ast`](Merged::ast) has our side of each conflict,
+//! and [`Merged::render`] writes both sides between markers:
+//!
+//! ```text
+//! <<<<<<< ours
+//!
+//! Our paragraph.
+//!
+//! =======
+//!
+//! Their paragraph.
+//!
+//! >>>>>>> theirs
+//! ```
+//!
+//! A heading is a block like any other,
+//! so changes to different sections of a document never conflict.
+
+use pulldown_cmark::{CowStr, Tag};
+
+use crate::{
+    diff::lcs,
+    fmt::{FormatError, Formatter},
+    Ast, Group, Span, Spanned, Tree,
+};
+
+/// Returned by [`merge`].
+///
+/// The fields are private, since [`Merged::render`] finds conflicts in the tree by path.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Merged<'a> {
+    ast: Ast<'a>,
+    conflicts: Vec<Conflict>,
+    /// Where each conflict is in `ast`, in the same order.
+    markers: Vec<Marker<'a>>,
+}
+
+/// Blocks which both sides changed differently.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Conflict {
+    /// The blocks in the base.
+    ///
+    /// Like the others, empty where there are none, at where they would be.
+    pub span: Span,
+    /// The blocks in our input.
+    pub ours: Span,
+    /// The blocks in their input.
+    pub theirs: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Marker<'a> {
+    /// The path of our first block, or of where it would be.
+    path: Vec<usize>,
+    /// The number of our blocks.
+    len: usize,
+    theirs: Vec<Tree<'a>>,
+}
+
+/// Merge the changes from `base` to `ours` and `theirs`.
+pub fn merge<'a>(base: &Ast<'a>, ours: &Ast<'a>, theirs: &Ast<'a>) -> Merged<'a> {
+    let mut pending = vec![];
+    let trees = blocks(&mut pending, &base.0, &ours.0, &theirs.0, [0; 3]);
+    let (conflicts, markers) = pending.into_iter().unzip();
+    Merged {
+        ast: Ast(trees),
+        conflicts,
+        markers,
+    }
+}
+
+impl<'a> Merged<'a> {
+    /// The merged document, with our side of each conflict.
+    ///
+    /// Spans refer to whichever input a node came from.
+    pub fn ast(&self) -> &Ast<'a> {
+        &self.ast
+    }
+    pub fn into_ast(self) -> Ast<'a> {
+        self.ast
+    }
+    pub fn conflicts(&self) -> &[Conflict] {
+        &self.conflicts
+    }
+    /// Render the merged document, with markers around each conflict.
+    ///
+    /// Fails if either side of the conflicts wouldn't render to the same [`Ast`],
+    /// as [`Formatter::render_checked`].
+    pub fn render(&self, formatter: &Formatter) -> Result<String, FormatError> {
+        formatter.render_checked(&self.ast)?;
+        if self.markers.is_empty() {
+            return Ok(formatter.render(&self.ast));
+        }
+        formatter.render_checked(&self.resolve(|_, _, theirs| theirs.to_vec()))?;
+        let marked = self.resolve(|conflict, ours, theirs| {
+            let start = conflict.ours.0.start;
+            let marker = |marker: &'static str| {
+                let span = Span(start..start);
+                Tree::Group(Group {
+                    tag: Spanned {
+                        item: Tag::HtmlBlock,
+                        span: span.clone(),
+                    },
+                    stream: Ast(vec![Tree::Html(Spanned {
+                        item: CowStr::Borrowed(marker),
+                        span: span.clone(),
+                    })]),
+                    end_span: span,
+                })
+            };
+            let mut trees = vec![marker("<<<<<<< ours\n")];
+            trees.extend(ours.iter().cloned());
+            trees.push(marker("=======\n"));
+            trees.extend(theirs.iter().cloned());
+            trees.push(marker(">>>>>>> theirs\n"));
+            trees
+        });
+        Ok(formatter.render(&marked))
+    }
+    /// `ast`, with our side of each conflict replaced by `blocks(conflict, ours, theirs)`.
+    fn resolve(
+        &self,
+        blocks: impl Fn(&Conflict, &[Tree<'a>], &[Tree<'a>]) -> Vec<Tree<'a>>,
+    ) -> Ast<'a> {
+        let mut ast = self.ast.clone();
+        // later conflicts first, so the paths of earlier ones stay valid
+        for (conflict, marker) in self.conflicts.iter().zip(&self.markers).rev() {
+            let (ix, parent) = marker.path.split_last().unwrap();
+            let stream = match parent.is_empty() {
+                true => &mut ast,
+                false => ast.get_mut(parent).and_then(Tree::stream_mut).unwrap(),
+            };
+            let range = *ix..ix + marker.len;
+            let trees = blocks(conflict, &stream.0[range.clone()], &marker.theirs);
+            stream.0.splice(range, trees);
+        }
+        ast
+    }
+}
+
+/// Append `trees` to `merged`, where `pending` are conflicts in `trees`.
+fn append<'a>(
+    merged: &mut Vec<Tree<'a>>,
+    pending: &mut [(Conflict, Marker<'a>)],
+    trees: Vec<Tree<'a>>,
+) {
+    for (_, marker) in pending {
+        marker.path[0] += merged.len()
+    }
+    merged.extend(trees)
+}
+
+/// `at` is the start of the parent in each input.
+fn blocks<'a>(
+    pending: &mut Vec<(Conflict, Marker<'a>)>,
+    base: &[Tree<'a>],
+    ours: &[Tree<'a>],
+    theirs: &[Tree<'a>],
+    mut at: [usize; 3],
+) -> Vec<Tree<'a>> {
+    let matches = |side: &[Tree<'a>]| {
+        let mut matches = vec![None; base.len()];
+        for (b, s) in lcs(base.len(), side.len(), 1, |b, s| {
+            base[b].structurally_eq(&side[s]) as u32
+        }) {
+            matches[b] = Some(s)
+        }
+        matches
+    };
+    let (to_ours, to_theirs) = (matches(ours), matches(theirs));
+    let mut merged = vec![];
+    let (mut b, mut o, mut t) = (0, 0, 0);
+    loop {
+        // the next block which neither side changed
+        let stable = (b..base.len()).find_map(|b| Some((b, to_ours[b]?, to_theirs[b]?)));
+        let (b_end, o_end, t_end) = stable.unwrap_or((base.len(), ours.len(), theirs.len()));
+        if (b, o, t) != (b_end, o_end, t_end) {
+            let from = pending.len();
+            let trees = chunk(
+                pending,
+                [&base[b..b_end], &ours[o..o_end], &theirs[t..t_end]],
+                at,
+            );
+            append(&mut merged, &mut pending[from..], trees)
+        }
+        let Some((b_stable, o_stable, t_stable)) = stable else {
+            break;
+        };
+        merged.push(ours[o_stable].clone());
+        at = [
+            base[b_stable].span().0.end,
+            ours[o_stable].span().0.end,
+            theirs[t_stable].span().0.end,
+        ];
+        (b, o, t) = (b_stable + 1, o_stable + 1, t_stable + 1)
+    }
+    merged
+}
+
+/// Merge a run of blocks which changed on at least one side.
+fn chunk<'a>(
+    pending: &mut Vec<(Conflict, Marker<'a>)>,
+    [base, ours, theirs]: [&[Tree<'a>]; 3],
+    at: [usize; 3],
+) -> Vec<Tree<'a>> {
+    let same = |left: &[Tree<'_>], right: &[Tree<'_>]| {
+        left.len() == right.len() && left.iter().zip(right).all(|(l, r)| l.structurally_eq(r))
+    };
+    if same(base, ours) {
+        return theirs.to_vec();
+    }
+    if same(base, theirs) || same(ours, theirs) {
+        return ours.to_vec();
+    }
+    if base.len() == ours.len() && base.len() == theirs.len() && base.len() > 1 {
+        // blocks changed in place
+        let mut merged = vec![];
+        for ix in 0..base.len() {
+            let range = ix..ix + 1;
+            let from = pending.len();
+            let trees = chunk(
+                pending,
+                [&base[range.clone()], &ours[range.clone()], &theirs[range]],
+                at,
+            );
+            append(&mut merged, &mut pending[from..], trees)
+        }
+        return merged;
+    }
+    if let ([base], [ours], [theirs]) = (base, ours, theirs) {
+        if let Some([b, o, t]) = container([base, ours, theirs]) {
+            let mut inner = vec![];
+            let stream = blocks(
+                &mut inner,
+                &b.stream.0,
+                &o.stream.0,
+                &t.stream.0,
+                [b.tag.span.0.start, o.tag.span.0.start, t.tag.span.0.start],
+            );
+            // markers can't go between list items or table rows
+            let markers = matches!(o.tag.item, Tag::List(_) | Tag::Table(_))
+                && inner.iter().any(|(_, it)| it.path.len() == 1);
+            if !markers {
+                for (_, marker) in &mut inner {
+                    marker.path.insert(0, 0)
+                }
+                pending.extend(inner);
+                return vec![Tree::Group(Group {
+                    stream: Ast(stream),
+                    ..o.clone()
+                })];
+            }
+        }
+    }
+    let span = |trees: &[Tree<'_>], at: usize| match (trees.first(), trees.last()) {
+        (Some(first), Some(last)) => Span(first.span().0.start..last.span().0.end),
+        _ => Span(at..at),
+    };
+    let conflict = Conflict {
+        span: span(base, at[0]),
+        ours: span(ours, at[1]),
+        theirs: span(theirs, at[2]),
+    };
+    let marker = Marker {
+        path: vec![0],
+        len: ours.len(),
+        theirs: theirs.to_vec(),
+    };
+    pending.push((conflict, marker));
+    ours.to_vec()
+}
+
+/// The groups, if they have the same tag, and their children can be merged.
+fn container<'t, 'a>(trees: [&'t Tree<'a>; 3]) -> Option<[&'t Group<'a>; 3]> {
+    let [Tree::Group(base), Tree::Group(ours), Tree::Group(theirs)] = trees else {
+        return None;
+    };
+    let mergeable = matches!(
+        base.tag.item,
+        Tag::BlockQuote(_) | Tag::List(_) | Tag::Item | Tag::Table(_) | Tag::FootnoteDefinition(_)
+    );
+    // runs of inline content, like in tight list items, aren't split
+    let blocks = [base, ours, theirs]
+        .iter()
+        .all(|it| it.stream.0.iter().all(Tree::is_block));
+    match mergeable && blocks && base.tag.item == ours.tag.item && base.tag.item == theirs.tag.item
+    {
+        true => Some([base, ours, theirs]),
+        false => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pulldown_cmark::Options;
+
+    use super::*;
+
+    fn merge_str<'a>(base: &'a str, ours: &'a str, theirs: &'a str) -> Merged<'a> {
+        let parse = |text| Ast::new_ext(text, Options::ENABLE_TABLES);
+        merge(&parse(base), &parse(ours), &parse(theirs))
+    }
+
+    fn render(merged: &Merged<'_>) -> String {
+        merged
+            .render(&Formatter::new().options(Options::ENABLE_TABLES))
+            .unwrap()
+    }
+
+    #[test]
+    fn clean() {
+        let base = "# a\n\nb\n\nc\n\nd\n";
+        let merged = merge_str(base, "# a\n\nB\n\nc\n\nd\n", "# a\n\nb\n\nc\n\nD\n\ne\n");
+        assert_eq!(merged.conflicts(), []);
+        assert_eq!(render(&merged), "# a\n\nB\n\nc\n\nD\n\ne\n");
+        // adjacent blocks changed in place merge separately
+        let merged = merge_str(base, "# a\n\nB\n\nc\n\nd\n", "# a\n\nb\n\nC\n\nd\n");
+        assert_eq!(render(&merged), "# a\n\nB\n\nC\n\nd\n");
+        // but not next to an insertion, like adjacent lines in text merges
+        let merged = merge_str(base, "# a\n\nB\n\nc\n\nd\n", "# a\n\nb\n\nC\n\nx\n\nd\n");
+        assert_eq!(merged.conflicts().len(), 1);
+        // the same change on both sides
+        let merged = merge_str(base, "# a\n\nc\n", "# a\n\nc\n");
+        assert_eq!(render(&merged), "# a\n\nc\n");
+        assert!(merged.ast().structurally_eq(&Ast::new("# a\n\nc\n")));
+    }
+
+    #[test]
+    fn conflict() {
+        let (base, ours, theirs) = ("a\n\nb\n\nc\n", "a\n\nours\n\nc\n", "a\n\ntheirs\n\nc\n");
+        let merged = merge_str(base, ours, theirs);
+        assert_eq!(
+            merged.conflicts(),
+            [Conflict {
+                span: Span(3..5),
+                ours: Span(3..8),
+                theirs: Span(3..10),
+            }]
+        );
+        // the tree has our side, without markers
+        assert!(merged.ast().structurally_eq(&Ast::new(ours)));
+        assert_eq!(
+            render(&merged),
+            "a\n\n<<<<<<< ours\n\nours\n\n=======\n\ntheirs\n\n>>>>>>> theirs\n\nc\n"
+        );
+    }
+
+    #[test]
+    fn conflicting_insertions() {
+        let merged = merge_str("a\n", "a\n\nb\n", "a\n\nc\n\nd\n");
+        assert_eq!(
+            merged.conflicts(),
+            [Conflict {
+                span: Span(2..2),
+                ours: Span(3..5),
+                theirs: Span(3..8),
+            }]
+        );
+        assert_eq!(
+            render(&merged),
+            "a\n\n<<<<<<< ours\n\nb\n\n=======\n\nc\n\nd\n\n>>>>>>> theirs\n"
+        );
+    }
+
+    #[test]
+    fn deletion_against_change() {
+        let merged = merge_str("a\n\nb\n", "a\n", "a\n\nc\n");
+        assert_eq!(merged.conflicts()[0].ours, Span(2..2));
+        assert_eq!(
+            render(&merged),
+            "a\n\n<<<<<<< ours\n\n=======\n\nc\n\n>>>>>>> theirs\n"
+        );
+    }
+
+    #[test]
+    fn nested() {
+        let merged = merge_str("> a\n>\n> b\n", "> A\n>\n> b\n", "> a\n>\n> B\n");
+        assert_eq!(merged.conflicts(), []);
+        assert_eq!(render(&merged), "> A\n>\n> B\n");
+        // markers go inside the quote
+        let merged = merge_str(
+            "x\n\n> a\n>\n> b\n",
+            "x\n\n> A\n>\n> b\n",
+            "x\n\n> B\n>\n> b\n",
+        );
+        assert_eq!(merged.conflicts().len(), 1);
+        assert_eq!(
+            render(&merged),
+            "x\n\n> <<<<<<< ours\n>\n> A\n>\n> =======\n>\n> B\n>\n> >>>>>>> theirs\n>\n> b\n"
+        );
+    }
+
+    #[test]
+    fn list_items() {
+        let base = "- a\n- b\n- c\n";
+        let merged = merge_str(base, "- A\n- b\n- c\n", "- a\n- b\n- C\n- d\n");
+        assert_eq!(merged.conflicts(), []);
+        assert_eq!(render(&merged), "- A\n- b\n- C\n- d\n");
+        // markers can't go between items
+        let merged = merge_str(base, "- a\n- B\n- c\n", "- a\n- X\n- c\n");
+        assert_eq!(
+            merged.conflicts(),
+            [Conflict {
+                span: Span(0..12),
+                ours: Span(0..12),
+                theirs: Span(0..12),
+            }]
+        );
+        assert_eq!(
+            render(&merged),
+            "<<<<<<< ours\n\n- a\n- B\n- c\n\n=======\n\n- a\n- X\n- c\n\n>>>>>>> theirs\n"
+        );
+        // but can go within the blocks of an item
+        let merged = merge_str("- a\n\n  b\n", "- a\n\n  B\n", "- a\n\n  X\n");
+        assert_eq!(merged.conflicts()[0].ours, Span(7..9));
+        assert_eq!(
+            render(&merged),
+            "- a\n\n  <<<<<<< ours\n\n  B\n\n  =======\n\n  X\n\n  >>>>>>> theirs\n"
+        );
+    }
+
+    #[test]
+    fn table_rows() {
+        let base = "| a | b |\n| - | - |\n| 1 | 2 |\n| 3 | 4 |\n| 5 | 6 |\n";
+        let ours = "| a | b |\n| - | - |\n| 1 | X |\n| 3 | 4 |\n| 5 | 6 |\n";
+        let theirs = "| a | b |\n| - | - |\n| 1 | 2 |\n| 3 | 4 |\n| 5 | Y |\n| 7 | 8 |\n";
+        let merged = merge_str(base, ours, theirs);
+        assert_eq!(merged.conflicts(), []);
+        assert_eq!(
+            render(&merged),
+            "| a   | b   |\n| --- | --- |\n| 1   | X   |\n| 3   | 4   |\n| 5   | Y   |\n| 7   | 8   |\n"
+        );
+        let theirs = "| a | b |\n| - | - |\n| 1 | Z |\n| 3 | 4 |\n| 5 | 6 |\n";
+        let merged = merge_str(base, ours, theirs);
+        assert_eq!(merged.conflicts().len(), 1);
+        assert_eq!(merged.conflicts()[0].ours, Span(0..base.len()));
+    }
+
+    #[test]
+    fn several_conflicts() {
+        let merged = merge_str("a\n\nb\n\nc\n", "1\n\nb\n\n3\n", "x\n\nb\n\nz\n");
+        assert_eq!(merged.conflicts().len(), 2);
+        assert_eq!(
+            render(&merged),
+            "<<<<<<< ours\n\n1\n\n=======\n\nx\n\n>>>>>>> theirs\n\nb\n\n\
+             <<<<<<< ours\n\n3\n\n=======\n\nz\n\n>>>>>>> theirs\n"
+        );
+    }
+}
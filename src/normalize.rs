@@ -0,0 +1,173 @@
+//! Simplify the shape of an [`Ast`], without changing what it renders to.
+//!
+//! [`pulldown_cmark`] splits text at escapes, entities, brackets and smart punctuation,
+//! so `a &amp; b` is three [`Tree::Text`]s.
+//! After normalizing:
+//! - There are no empty [`Tree::Text`]s, and no two are adjacent.
+//!   Merged text has a span from the start of the first node to the end of the last,
+//!   so it may cover escapes and entities, like the spans of single nodes already can.
+//! - There are no emphasis, strong or strikethrough groups without children.
+//! - With [`Normalize::soft_break_as_space`], there are no [`Tree::SoftBreak`]s,
+//!   and each is merged into the surrounding text as a space.
+//!
+//! Everything else, including spans of other nodes, is unchanged.
+
+use pulldown_cmark::{CowStr, Tag};
+
+use crate::{Ast, Group, Span, Spanned, Tree};
+
+impl Ast<'_> {
+    /// Normalize this tree in place, keeping soft breaks.
+    ///
+    /// See the [module documentation](crate::normalize).
+    pub fn normalize(&mut self) {
+        Normalize::new().apply(self)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Normalize {
+    soft_break_as_space: bool,
+}
+
+impl Normalize {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Replace [`Tree::SoftBreak`]s with spaces.
+    pub fn soft_break_as_space(mut self, space: bool) -> Self {
+        self.soft_break_as_space = space;
+        self
+    }
+    pub fn apply(&self, ast: &mut Ast<'_>) {
+        let mut trees = Vec::with_capacity(ast.0.len());
+        for mut tree in ast.0.drain(..) {
+            if let Some(stream) = tree.stream_mut() {
+                self.apply(stream)
+            }
+            match &tree {
+                Tree::SoftBreak(span) if self.soft_break_as_space => {
+                    tree = Tree::Text(Spanned {
+                        item: CowStr::Borrowed(" "),
+                        span: span.clone(),
+                    })
+                }
+                Tree::Text(Spanned { item, .. }) if item.is_empty() => continue,
+                Tree::Group(Group { tag, stream, .. })
+                    if stream.0.is_empty()
+                        && matches!(tag.item, Tag::Emphasis | Tag::Strong | Tag::Strikethrough) =>
+                {
+                    continue
+                }
+                _ => {}
+            }
+            match (trees.last_mut(), tree) {
+                (
+                    Some(Tree::Text(Spanned {
+                        item: left,
+                        span: Span(left_span),
+                    })),
+                    Tree::Text(Spanned {
+                        item: right,
+                        span: Span(right_span),
+                    }),
+                ) => {
+                    *left = CowStr::from(format!("{left}{right}"));
+                    left_span.end = right_span.end
+                }
+                (_, tree) => trees.push(tree),
+            }
+        }
+        ast.0 = trees
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{corpus, structural::Structural};
+
+    fn text(text: &str, span: std::ops::Range<usize>) -> Tree<'_> {
+        Tree::Text(Spanned {
+            item: text.into(),
+            span: Span(span),
+        })
+    }
+
+    fn group(tag: Tag<'static>, stream: Vec<Tree<'static>>) -> Tree<'static> {
+        Tree::Group(Group {
+            tag: Spanned {
+                item: tag,
+                span: Span(0..0),
+            },
+            stream: Ast(stream),
+            end_span: Span(0..0),
+        })
+    }
+
+    #[test]
+    fn merges_text() {
+        let mut ast = Ast::new("a &amp; [b] *c \\* d*\n");
+        assert!(ast.0[0].stream().unwrap().0.len() > 2);
+        ast.normalize();
+        let paragraph = &ast.0[0].stream().unwrap().0;
+        assert_eq!(paragraph[0], text("a & [b] ", 0..12));
+        assert_eq!(paragraph[1].stream().unwrap().0, [text("c * d", 13..19)]);
+    }
+
+    #[test]
+    fn soft_breaks() {
+        let mut ast = Ast::new("a\nb\n");
+        ast.normalize();
+        assert_eq!(ast.0[0].stream().unwrap().0.len(), 3);
+        Normalize::new().soft_break_as_space(true).apply(&mut ast);
+        assert_eq!(ast.0[0].stream().unwrap().0, [text("a b", 0..3)]);
+    }
+
+    #[test]
+    fn removes_empty_groups() {
+        let mut ast = Ast(vec![group(
+            Tag::Paragraph,
+            vec![
+                text("a", 0..1),
+                group(
+                    Tag::Emphasis,
+                    vec![group(Tag::Strong, vec![text("", 2..2)])],
+                ),
+                text("b", 5..6),
+                group(
+                    Tag::Link {
+                        link_type: pulldown_cmark::LinkType::Inline,
+                        dest_url: "c".into(),
+                        title: "".into(),
+                        id: "".into(),
+                    },
+                    vec![],
+                ),
+            ],
+        )]);
+        ast.normalize();
+        let paragraph = &ast.0[0].stream().unwrap().0;
+        // links without text are kept, since they still render
+        assert_eq!(paragraph.len(), 2);
+        assert_eq!(paragraph[0], text("ab", 0..6));
+    }
+
+    #[test]
+    fn corpus() {
+        for case in corpus::cases() {
+            let before = Ast::new_ext(&case.markdown, case.options);
+            let mut ast = before.clone();
+            ast.normalize();
+            assert!(
+                Structural::new().merge_text(true).eq(&before, &ast),
+                "{}",
+                case.name
+            );
+            assert_eq!(ast.plain_text(), before.plain_text(), "{}", case.name);
+            let normalized = ast.clone();
+            ast.normalize();
+            assert_eq!(ast, normalized, "{}", case.name);
+        }
+    }
+}
@@ -0,0 +1,732 @@
+//! Conversion to and from [pandoc](https://pandoc.org)'s JSON AST,
+//! as read by `pandoc --from json` and written by `pandoc --to json`.
+//!
+//! Footnotes are exported as `Note`s at the point of reference,
+//! and imported `Note`s become footnote definitions at the end of the document.
+//!
+//! Some information does not survive the round trip:
+//! - Metadata blocks are dropped.
+//! - Block quote kinds (GFM alerts) are dropped.
+//! - Reference links become inline links.
+//! - Code blocks keep only their classes, the first of which is exported from the info string.
+//! - Ordered lists are exported with `Decimal` numbers and `Period` delimiters,
+//!   and imported whatever their style.
+//! - `Quoted` inlines are imported as straight quotes.
+//! - Pandoc has no source positions, so imported nodes have empty [`Span`]s.
+//!
+//! Importing fails on raw content which isn't HTML, and on constructs with no
+//! Markdown equivalent, like `DefinitionList`s and `Figure`s.
+
+use std::{collections::HashMap, fmt};
+
+use pulldown_cmark::{Alignment, CodeBlockKind, CowStr, HeadingLevel, LinkType, Tag};
+use serde_json::{json, Value};
+
+use crate::{Ast, Group, Span, Spanned, Tree};
+
+/// The version of pandoc's AST which is read and written.
+pub const API_VERSION: [u64; 3] = [1, 23, 1];
+
+/// An error importing pandoc JSON.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PandocError {
+    pub message: String,
+}
+
+impl fmt::Display for PandocError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for PandocError {}
+
+fn error<T>(message: impl fmt::Display) -> Result<T, PandocError> {
+    Err(PandocError {
+        message: message.to_string(),
+    })
+}
+
+impl Ast<'_> {
+    /// Convert to a pandoc document, with empty metadata.
+    pub fn to_pandoc(&self) -> Value {
+        let mut notes = HashMap::new();
+        collect_notes(self, &mut notes);
+        let blocks = Exporter {
+            notes,
+            active: vec![],
+        }
+        .blocks(&self.0, false);
+        json!({
+            "pandoc-api-version": API_VERSION,
+            "meta": {},
+            "blocks": blocks,
+        })
+    }
+}
+
+impl Ast<'static> {
+    /// Convert from a pandoc document, whose `pandoc-api-version` must match
+    /// the major and minor versions of [`API_VERSION`].
+    pub fn from_pandoc(doc: &Value) -> Result<Self, PandocError> {
+        let version = doc
+            .get("pandoc-api-version")
+            .and_then(Value::as_array)
+            .map(|it| it.iter().filter_map(Value::as_u64).collect::<Vec<_>>());
+        match version {
+            Some(it) if it.starts_with(&API_VERSION[..2]) => {}
+            Some(it) => return error(format_args!("unsupported pandoc-api-version {it:?}")),
+            None => return error("missing pandoc-api-version"),
+        }
+        let mut importer = Importer {
+            notes: vec![],
+            count: 0,
+        };
+        let mut out = importer.blocks(&doc["blocks"], false)?;
+        out.append(&mut importer.notes);
+        Ok(Ast(out))
+    }
+}
+
+fn collect_notes<'t, 'a>(ast: &'t Ast<'a>, notes: &mut HashMap<String, &'t Ast<'a>>) {
+    for tree in &ast.0 {
+        if let Tree::Group(Group {
+            tag:
+                Spanned {
+                    item: Tag::FootnoteDefinition(label),
+                    ..
+                },
+            stream,
+            ..
+        }) = tree
+        {
+            notes.entry(label.to_lowercase()).or_insert(stream);
+        }
+        if let Some(stream) = tree.stream() {
+            collect_notes(stream, notes)
+        }
+    }
+}
+
+fn el(t: &str, c: Value) -> Value {
+    json!({ "t": t, "c": c })
+}
+
+fn nullary(t: &str) -> Value {
+    json!({ "t": t })
+}
+
+fn null_attr() -> Value {
+    json!(["", [], []])
+}
+
+struct Exporter<'t, 'a> {
+    /// Footnote definitions, by lowercase label.
+    notes: HashMap<String, &'t Ast<'a>>,
+    /// Notes being exported, to avoid recursing forever.
+    active: Vec<String>,
+}
+
+impl Exporter<'_, '_> {
+    /// Export `trees` as blocks, wrapping runs of inline nodes in `Plain` or `Para`.
+    fn blocks(&mut self, trees: &[Tree<'_>], plain: bool) -> Vec<Value> {
+        let mut out = vec![];
+        let mut rest = trees;
+        while let Some(first) = rest.first() {
+            if first.is_block() {
+                out.extend(self.block(first));
+                rest = &rest[1..];
+                continue;
+            }
+            let len = rest.iter().take_while(|it| !it.is_block()).count();
+            let inlines = self.inlines(&rest[..len]);
+            rest = &rest[len..];
+            if !inlines.is_empty() {
+                out.push(el(if plain { "Plain" } else { "Para" }, inlines.into()))
+            }
+        }
+        out
+    }
+    fn block(&mut self, tree: &Tree<'_>) -> Option<Value> {
+        let Tree::Group(Group { tag, stream, .. }) = tree else {
+            return match tree {
+                Tree::Rule(_) => Some(nullary("HorizontalRule")),
+                _ => None,
+            };
+        };
+        let trim = |s: String| s.strip_suffix('\n').map(Into::into).unwrap_or(s);
+        Some(match &tag.item {
+            Tag::Paragraph => el("Para", self.inlines(&stream.0).into()),
+            Tag::Heading {
+                level,
+                id,
+                classes,
+                attrs,
+            } => {
+                let attr = json!([
+                    id.as_deref().unwrap_or_default(),
+                    classes.iter().map(|it| &**it).collect::<Vec<_>>(),
+                    attrs
+                        .iter()
+                        .map(|(k, v)| [&**k, v.as_deref().unwrap_or_default()])
+                        .collect::<Vec<_>>(),
+                ]);
+                el(
+                    "Header",
+                    json!([*level as usize, attr, self.inlines(&stream.0)]),
+                )
+            }
+            Tag::BlockQuote(_) => el("BlockQuote", self.blocks(&stream.0, false).into()),
+            Tag::CodeBlock(kind) => {
+                let classes = match kind {
+                    CodeBlockKind::Fenced(info) => info.split_whitespace().take(1).collect(),
+                    CodeBlockKind::Indented => vec![],
+                };
+                el(
+                    "CodeBlock",
+                    json!([["", classes, []], trim(stream.plain_text())]),
+                )
+            }
+            Tag::HtmlBlock => {
+                let html = stream
+                    .0
+                    .iter()
+                    .filter_map(|it| match it {
+                        Tree::Html(Spanned { item, .. }) | Tree::Text(Spanned { item, .. }) => {
+                            Some(&**item)
+                        }
+                        _ => None,
+                    })
+                    .collect::<String>();
+                el("RawBlock", json!(["html", trim(html)]))
+            }
+            Tag::List(start) => {
+                let items = stream
+                    .0
+                    .iter()
+                    .map(|item| {
+                        let stream = item.stream().map_or(&[][..], |it| &it.0);
+                        let tight = !stream.iter().any(|it| {
+                            matches!(
+                                it,
+                                Tree::Group(Group {
+                                    tag: Spanned {
+                                        item: Tag::Paragraph,
+                                        ..
+                                    },
+                                    ..
+                                })
+                            )
+                        });
+                        self.blocks(stream, tight)
+                    })
+                    .collect::<Vec<_>>();
+                match start {
+                    Some(start) => el(
+                        "OrderedList",
+                        json!([[start, nullary("Decimal"), nullary("Period")], items]),
+                    ),
+                    None => el("BulletList", items.into()),
+                }
+            }
+            Tag::Item => el("Div", json!([null_attr(), self.blocks(&stream.0, true)])),
+            // footnotes are inlined at their references
+            Tag::FootnoteDefinition(_) | Tag::MetadataBlock(_) => return None,
+            Tag::Table(alignments) => {
+                let specs = alignments
+                    .iter()
+                    .map(|it| {
+                        let align = match it {
+                            Alignment::None => "AlignDefault",
+                            Alignment::Left => "AlignLeft",
+                            Alignment::Center => "AlignCenter",
+                            Alignment::Right => "AlignRight",
+                        };
+                        json!([nullary(align), nullary("ColWidthDefault")])
+                    })
+                    .collect::<Vec<_>>();
+                let mut head = vec![];
+                let mut body = vec![];
+                for row in &stream.0 {
+                    let Tree::Group(Group { tag, stream, .. }) = row else {
+                        continue;
+                    };
+                    let cells = stream
+                        .0
+                        .iter()
+                        .map(|cell| {
+                            let inlines = self.inlines(cell.stream().map_or(&[][..], |it| &it.0));
+                            let blocks = match inlines.is_empty() {
+                                true => vec![],
+                                false => vec![el("Plain", inlines.into())],
+                            };
+                            json!([null_attr(), nullary("AlignDefault"), 1, 1, blocks])
+                        })
+                        .collect::<Vec<_>>();
+                    let row = json!([null_attr(), cells]);
+                    match tag.item {
+                        Tag::TableHead => head.push(row),
+                        _ => body.push(row),
+                    }
+                }
+                el(
+                    "Table",
+                    json!([
+                        null_attr(),
+                        [null, []],
+                        specs,
+                        [null_attr(), head],
+                        [[null_attr(), 0, [], body]],
+                        [null_attr(), []],
+                    ]),
+                )
+            }
+            // inline content
+            _ => el("Plain", self.inlines(&stream.0).into()),
+        })
+    }
+    fn inlines(&mut self, trees: &[Tree<'_>]) -> Vec<Value> {
+        let mut out = vec![];
+        for tree in trees {
+            self.inline(tree, &mut out)
+        }
+        out
+    }
+    fn inline(&mut self, tree: &Tree<'_>, out: &mut Vec<Value>) {
+        match tree {
+            Tree::Group(Group { tag, stream, .. }) => {
+                let inlines = self.inlines(&stream.0);
+                out.push(match &tag.item {
+                    Tag::Emphasis => el("Emph", inlines.into()),
+                    Tag::Strong => el("Strong", inlines.into()),
+                    Tag::Strikethrough => el("Strikeout", inlines.into()),
+                    Tag::Link {
+                        dest_url, title, ..
+                    } => el(
+                        "Link",
+                        json!([null_attr(), inlines, [&**dest_url, &**title]]),
+                    ),
+                    Tag::Image {
+                        dest_url, title, ..
+                    } => el(
+                        "Image",
+                        json!([null_attr(), inlines, [&**dest_url, &**title]]),
+                    ),
+                    _ => return out.extend(inlines),
+                })
+            }
+            Tree::Text(Spanned { item, .. }) => {
+                for (ix, word) in item.split(' ').enumerate() {
+                    if ix > 0 && out.last() != Some(&nullary("Space")) {
+                        out.push(nullary("Space"))
+                    }
+                    match out.last_mut() {
+                        // pandoc merges adjacent text
+                        Some(last) if last["t"] == "Str" => {
+                            if let Value::String(s) = &mut last["c"] {
+                                s.push_str(word)
+                            }
+                        }
+                        _ if word.is_empty() => {}
+                        _ => out.push(el("Str", word.into())),
+                    }
+                }
+            }
+            Tree::Code(Spanned { item, .. }) => out.push(el("Code", json!([null_attr(), &**item]))),
+            Tree::Html(Spanned { item, .. }) | Tree::InlineHtml(Spanned { item, .. }) => {
+                out.push(el("RawInline", json!(["html", &**item])))
+            }
+            Tree::FootnoteReference(Spanned { item, .. }) => {
+                let key = item.to_lowercase();
+                match self.notes.get(&key) {
+                    Some(stream) if !self.active.contains(&key) => {
+                        self.active.push(key);
+                        let blocks = self.blocks(&stream.0, false);
+                        self.active.pop();
+                        out.push(el("Note", blocks.into()))
+                    }
+                    _ => out.push(el("Str", format!("[^{item}]").into())),
+                }
+            }
+            Tree::SoftBreak(_) => out.push(nullary("SoftBreak")),
+            Tree::HardBreak(_) => out.push(nullary("LineBreak")),
+            Tree::Rule(_) => {}
+            Tree::TaskListMarker(Spanned { item, .. }) => {
+                out.push(el("Str", if *item { "☒" } else { "☐" }.into()));
+                out.push(nullary("Space"))
+            }
+            Tree::InlineMath(Spanned { item, .. }) => {
+                out.push(el("Math", json!([nullary("InlineMath"), &**item])))
+            }
+            Tree::DisplayMath(Spanned { item, .. }) => {
+                out.push(el("Math", json!([nullary("DisplayMath"), &**item])))
+            }
+            Tree::BrokenLink(_, stream) => {
+                for tree in &stream.0 {
+                    self.inline(tree, out)
+                }
+            }
+        }
+    }
+}
+
+fn group(tag: Tag<'static>, stream: Vec<Tree<'static>>) -> Tree<'static> {
+    Tree::Group(Group {
+        tag: Spanned {
+            item: tag,
+            span: Span(0..0),
+        },
+        stream: Ast(stream),
+        end_span: Span(0..0),
+    })
+}
+
+fn t(el: &Value) -> Result<&str, PandocError> {
+    match el.get("t").and_then(Value::as_str) {
+        Some(it) => Ok(it),
+        None => error("element has no tag"),
+    }
+}
+
+fn array(value: &Value) -> &[Value] {
+    value.as_array().map_or(&[], Vec::as_slice)
+}
+
+fn string(value: &Value) -> String {
+    value.as_str().unwrap_or_default().into()
+}
+
+fn push_text(out: &mut Vec<Tree<'static>>, s: &str) {
+    match out.last_mut() {
+        Some(Tree::Text(Spanned { item, .. })) => *item = format!("{item}{s}").into(),
+        _ => out.push(Tree::Text(Spanned {
+            item: s.to_owned().into(),
+            span: Span(0..0),
+        })),
+    }
+}
+
+struct Importer {
+    /// Footnote definitions created from `Note`s.
+    notes: Vec<Tree<'static>>,
+    count: usize,
+}
+
+impl Importer {
+    /// `plain` unwraps `Plain` blocks, as in the items of tight lists.
+    fn blocks(&mut self, blocks: &Value, plain: bool) -> Result<Vec<Tree<'static>>, PandocError> {
+        let mut out = vec![];
+        for block in array(blocks) {
+            self.block(block, plain, &mut out)?
+        }
+        Ok(out)
+    }
+    fn block(
+        &mut self,
+        block: &Value,
+        plain: bool,
+        out: &mut Vec<Tree<'static>>,
+    ) -> Result<(), PandocError> {
+        let c = &block["c"];
+        let tree = match t(block)? {
+            "Plain" if plain => return self.inlines(c, out),
+            "Plain" | "Para" => group(Tag::Paragraph, self.inlines_vec(c)?),
+            "LineBlock" => {
+                let mut stream = vec![];
+                for (ix, line) in array(c).iter().enumerate() {
+                    if ix > 0 {
+                        stream.push(Tree::HardBreak(Span(0..0)))
+                    }
+                    self.inlines(line, &mut stream)?
+                }
+                group(Tag::Paragraph, stream)
+            }
+            "Header" => {
+                let level = c[0].as_u64().unwrap_or(1);
+                let Ok(level) = HeadingLevel::try_from(level as usize) else {
+                    return error(format_args!("invalid header level {level}"));
+                };
+                let id = c[1][0].as_str().filter(|it| !it.is_empty());
+                let tag = Tag::Heading {
+                    level,
+                    id: id.map(|it| it.to_owned().into()),
+                    classes: array(&c[1][1]).iter().map(|it| string(it).into()).collect(),
+                    attrs: array(&c[1][2])
+                        .iter()
+                        .map(|kv| {
+                            let v = string(&kv[1]);
+                            (string(&kv[0]).into(), (!v.is_empty()).then(|| v.into()))
+                        })
+                        .collect(),
+                };
+                group(tag, self.inlines_vec(&c[2])?)
+            }
+            "CodeBlock" => {
+                let classes = array(&c[0][1])
+                    .iter()
+                    .filter_map(Value::as_str)
+                    .collect::<Vec<_>>();
+                let kind = match classes.is_empty() {
+                    true => CodeBlockKind::Indented,
+                    false => CodeBlockKind::Fenced(classes.join(" ").into()),
+                };
+                let mut text = string(&c[1]);
+                if !text.is_empty() {
+                    text.push('\n')
+                }
+                group(
+                    Tag::CodeBlock(kind),
+                    vec![Tree::Text(Spanned {
+                        item: text.into(),
+                        span: Span(0..0),
+                    })],
+                )
+            }
+            "RawBlock" => match c[0].as_str() {
+                Some("html") => {
+                    let html = Tree::Html(Spanned {
+                        item: format!("{}\n", string(&c[1])).into(),
+                        span: Span(0..0),
+                    });
+                    group(Tag::HtmlBlock, vec![html])
+                }
+                format => return error(format_args!("unsupported raw format {format:?}")),
+            },
+            "BlockQuote" => group(Tag::BlockQuote(None), self.blocks(c, false)?),
+            ty @ ("BulletList" | "OrderedList") => {
+                let (start, items) = match ty {
+                    "OrderedList" => (Some(c[0][0].as_u64().unwrap_or(1)), &c[1]),
+                    _ => (None, c),
+                };
+                // pandoc's tight lists only contain `Plain`s
+                let tight = array(items)
+                    .iter()
+                    .flat_map(array)
+                    .all(|it| t(it) != Ok("Para"));
+                let mut stream = vec![];
+                for item in array(items) {
+                    stream.push(group(Tag::Item, self.blocks(item, tight)?))
+                }
+                group(Tag::List(start), stream)
+            }
+            "HorizontalRule" => Tree::Rule(Span(0..0)),
+            "Table" => {
+                let alignments = array(&c[2])
+                    .iter()
+                    .map(|spec| match spec[0]["t"].as_str() {
+                        Some("AlignLeft") => Alignment::Left,
+                        Some("AlignCenter") => Alignment::Center,
+                        Some("AlignRight") => Alignment::Right,
+                        _ => Alignment::None,
+                    })
+                    .collect();
+                let mut rows = vec![];
+                let head = array(&c[3][1]);
+                for row in head.iter().take(1) {
+                    rows.push(group(Tag::TableHead, self.cells(row)?))
+                }
+                let body = array(&c[4])
+                    .iter()
+                    .flat_map(|body| array(&body[2]).iter().chain(array(&body[3])));
+                for row in head.iter().skip(1).chain(body).chain(array(&c[5][1])) {
+                    rows.push(group(Tag::TableRow, self.cells(row)?))
+                }
+                group(Tag::Table(alignments), rows)
+            }
+            "Div" => {
+                out.extend(self.blocks(&c[1], plain)?);
+                return Ok(());
+            }
+            "Null" => return Ok(()),
+            other => return error(format_args!("unsupported block {other}")),
+        };
+        out.push(tree);
+        Ok(())
+    }
+    fn cells(&mut self, row: &Value) -> Result<Vec<Tree<'static>>, PandocError> {
+        let mut cells = vec![];
+        for cell in array(&row[1]) {
+            let mut stream = vec![];
+            for block in array(&cell[4]) {
+                self.inlines(&block["c"], &mut stream)?
+            }
+            cells.push(group(Tag::TableCell, stream))
+        }
+        Ok(cells)
+    }
+    fn inlines_vec(&mut self, inlines: &Value) -> Result<Vec<Tree<'static>>, PandocError> {
+        let mut out = vec![];
+        self.inlines(inlines, &mut out)?;
+        Ok(out)
+    }
+    fn inlines(
+        &mut self,
+        inlines: &Value,
+        out: &mut Vec<Tree<'static>>,
+    ) -> Result<(), PandocError> {
+        for inline in array(inlines) {
+            self.inline(inline, out)?
+        }
+        Ok(())
+    }
+    fn inline(&mut self, inline: &Value, out: &mut Vec<Tree<'static>>) -> Result<(), PandocError> {
+        let c = &inline["c"];
+        let spanned = |value: &Value| Spanned {
+            item: CowStr::from(string(value)),
+            span: Span(0..0),
+        };
+        let tree = match t(inline)? {
+            "Str" => {
+                push_text(out, c.as_str().unwrap_or_default());
+                return Ok(());
+            }
+            "Space" => {
+                push_text(out, " ");
+                return Ok(());
+            }
+            "SoftBreak" => Tree::SoftBreak(Span(0..0)),
+            "LineBreak" => Tree::HardBreak(Span(0..0)),
+            "Emph" => group(Tag::Emphasis, self.inlines_vec(c)?),
+            "Strong" => group(Tag::Strong, self.inlines_vec(c)?),
+            "Strikeout" => group(Tag::Strikethrough, self.inlines_vec(c)?),
+            "Underline" | "SmallCaps" | "Superscript" | "Subscript" => return self.inlines(c, out),
+            "Span" | "Cite" => return self.inlines(&c[1], out),
+            "Quoted" => {
+                let quote = match c[0]["t"].as_str() {
+                    Some("SingleQuote") => "'",
+                    _ => "\"",
+                };
+                push_text(out, quote);
+                self.inlines(&c[1], out)?;
+                push_text(out, quote);
+                return Ok(());
+            }
+            "Code" => Tree::Code(spanned(&c[1])),
+            "Math" => match c[0]["t"].as_str() {
+                Some("DisplayMath") => Tree::DisplayMath(spanned(&c[1])),
+                _ => Tree::InlineMath(spanned(&c[1])),
+            },
+            "RawInline" => match c[0].as_str() {
+                Some("html") => Tree::InlineHtml(spanned(&c[1])),
+                format => return error(format_args!("unsupported raw format {format:?}")),
+            },
+            ty @ ("Link" | "Image") => {
+                let (dest_url, title, id) = (
+                    string(&c[2][0]).into(),
+                    string(&c[2][1]).into(),
+                    CowStr::Borrowed(""),
+                );
+                let link_type = LinkType::Inline;
+                let tag = match ty {
+                    "Link" => Tag::Link {
+                        link_type,
+                        dest_url,
+                        title,
+                        id,
+                    },
+                    _ => Tag::Image {
+                        link_type,
+                        dest_url,
+                        title,
+                        id,
+                    },
+                };
+                group(tag, self.inlines_vec(&c[1])?)
+            }
+            "Note" => {
+                self.count += 1;
+                let label = CowStr::from(self.count.to_string());
+                let blocks = self.blocks(c, false)?;
+                self.notes
+                    .push(group(Tag::FootnoteDefinition(label.clone()), blocks));
+                Tree::FootnoteReference(Spanned {
+                    item: label,
+                    span: Span(0..0),
+                })
+            }
+            other => return error(format_args!("unsupported inline {other}")),
+        };
+        out.push(tree);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pulldown_cmark::Options;
+
+    use super::*;
+    use crate::structural::Structural;
+
+    // written by hand, following pandoc's `--to json` output
+    macro_rules! fixture {
+        ($name:literal) => {
+            serde_json::from_str::<Value>(include_str!(concat!(
+                "../tests/fixtures/pandoc/",
+                $name,
+                ".json"
+            )))
+            .unwrap()
+        };
+    }
+
+    #[test]
+    fn fixtures() {
+        let options = Options::ENABLE_TABLES
+            | Options::ENABLE_FOOTNOTES
+            | Options::ENABLE_MATH
+            | Options::ENABLE_HEADING_ATTRIBUTES
+            | Options::ENABLE_STRIKETHROUGH;
+        for (name, markdown, json) in [
+            (
+                "blocks",
+                include_str!("../tests/fixtures/pandoc/blocks.md"),
+                fixture!("blocks"),
+            ),
+            (
+                "inlines",
+                include_str!("../tests/fixtures/pandoc/inlines.md"),
+                fixture!("inlines"),
+            ),
+            (
+                "tables",
+                include_str!("../tests/fixtures/pandoc/tables.md"),
+                fixture!("tables"),
+            ),
+            (
+                "footnotes",
+                include_str!("../tests/fixtures/pandoc/footnotes.md"),
+                fixture!("footnotes"),
+            ),
+            (
+                "math",
+                include_str!("../tests/fixtures/pandoc/math.md"),
+                fixture!("math"),
+            ),
+        ] {
+            let ast = Ast::new_ext(markdown, options);
+            assert_eq!(ast.to_pandoc(), json, "{name}");
+            let imported = Ast::from_pandoc(&json).unwrap();
+            assert!(
+                Structural::new().merge_text(true).eq(&imported, &ast),
+                "{name}: {imported:#?}"
+            );
+        }
+    }
+
+    #[test]
+    fn import_only() {
+        let imported = Ast::from_pandoc(&fixture!("import")).unwrap();
+        let expected = Ast::new(include_str!("../tests/fixtures/pandoc/import.md"));
+        assert!(
+            Structural::new().merge_text(true).eq(&imported, &expected),
+            "{imported:#?}"
+        );
+    }
+
+    #[test]
+    fn definition_lists_are_unsupported() {
+        let err = Ast::from_pandoc(&fixture!("definition-list")).unwrap_err();
+        assert!(err.message.contains("DefinitionList"), "{err}");
+    }
+}
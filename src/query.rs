@@ -0,0 +1,819 @@
+//! CSS-like selectors over an [`Ast`].
+//!
+//! ```text
+//! table-cell link[href^=http]
+//! heading[level=2] + code-block[lang=rust]
+//! heading[level=2] || code-block[lang=rust]
+//! list > item:first-child, blockquote :contains("TODO")
+//! ```
+//!
+//! Node names are those returned by [`Tree::kind`], compared ignoring case and `-`,
+//! so `codeblock` and `code-block` are equivalent.
+//! `*` matches any node.
+//!
+//! The following attributes are available:
+//! - `heading`: `level`, `id`, `class`
+//! - `code-block`: `lang` (the first word of the info string), `info`, `fenced`
+//! - `list`: `ordered`, `start`
+//! - `link`, `image`: `href`, `title`, `id`, `type`
+//! - `footnote-definition`, `footnote-reference`: `label`
+//! - `block-quote`: `kind`
+//! - `metadata-block`: `kind`
+//! - `task-list-marker`: `checked`
+//! - `broken-link`: `reference`, `type`
+//! - `text`, `code`, `html`, `inline-html`, `inline-math`, `display-math`: `content`
+//!
+//! Attribute selectors are `[attr]` (present), `[attr=v]`, `[attr^=v]` (prefix),
+//! `[attr$=v]` (suffix), `[attr*=v]` (substring) and `[attr~=v]` (whitespace-separated word).
+//!
+//! Combinators are ` ` (descendant), `>` (child), `+` (next sibling), `~` (later sibling)
+//! and `||` (in the section of).
+//! Markdown has no section nodes, so `heading || x` matches a later sibling `x`
+//! of the heading, up to the next heading of the same or a higher level.
+//! The section includes those of deeper headings, and the deeper headings themselves.
+//! Combine it with others to look inside blocks, as in `heading || * link`.
+//!
+//! Pseudo-classes are `:first-child`, `:last-child`, `:only-child`, `:empty`
+//! and `:contains(text)`, which matches on [`Tree::plain_text`].
+
+use std::{borrow::Cow, fmt};
+
+use pulldown_cmark::{BlockQuoteKind, CodeBlockKind, LinkType, MetadataBlockKind, Tag};
+
+use crate::{Ast, BrokenLink, Group, Span, Spanned, Tree};
+
+/// A compiled selector list.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Selector {
+    alternatives: Vec<Complex>,
+}
+
+/// A node matched by a [`Selector`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Match<'ast, 'a> {
+    pub tree: &'ast Tree<'a>,
+    /// Suitable for [`Ast::get`].
+    pub path: Vec<usize>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    /// Byte offset into the selector.
+    pub offset: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at offset {}", self.message, self.offset)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Complex {
+    /// The rightmost compound is the subject.
+    /// Each compound (except the first) is paired with the combinator to its left.
+    first: Compound,
+    rest: Vec<(Combinator, Compound)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Combinator {
+    Descendant,
+    Child,
+    NextSibling,
+    LaterSibling,
+    Section,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+struct Compound {
+    /// [`None`] for `*`.
+    kind: Option<String>,
+    attrs: Vec<Attr>,
+    pseudos: Vec<Pseudo>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Attr {
+    name: String,
+    op: Option<(Op, String)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Equals,
+    Prefix,
+    Suffix,
+    Substring,
+    Word,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Pseudo {
+    FirstChild,
+    LastChild,
+    OnlyChild,
+    Empty,
+    Contains(String),
+}
+
+impl Selector {
+    pub fn parse(s: &str) -> Result<Self, ParseError> {
+        Parser { s, pos: 0 }.selector()
+    }
+    /// All the nodes in `ast` matched by this selector, in document order.
+    pub fn matches<'ast, 'a>(&self, ast: &'ast Ast<'a>) -> Vec<Match<'ast, 'a>> {
+        let mut matches = vec![];
+        let mut stack = vec![];
+        let mut path = vec![];
+        self.walk(&ast.0, &mut stack, &mut path, &mut matches);
+        matches
+    }
+    /// Whether the node at `path` is matched by this selector.
+    pub fn is_match(&self, ast: &Ast<'_>, path: &[usize]) -> bool {
+        let mut stack = vec![];
+        let mut siblings = ast.0.as_slice();
+        for ix in path {
+            let Some(tree) = siblings.get(*ix) else {
+                return false;
+            };
+            stack.push(Node {
+                tree,
+                siblings,
+                ix: *ix,
+            });
+            siblings = match tree.stream() {
+                Some(Ast(trees)) => trees,
+                None => &[],
+            };
+        }
+        match stack.split_last() {
+            Some((node, ancestors)) => self
+                .alternatives
+                .iter()
+                .any(|it| it.is_match(node, ancestors)),
+            None => false,
+        }
+    }
+    fn walk<'ast, 'a>(
+        &self,
+        siblings: &'ast [Tree<'a>],
+        stack: &mut Vec<Node<'ast, 'a>>,
+        path: &mut Vec<usize>,
+        matches: &mut Vec<Match<'ast, 'a>>,
+    ) {
+        for (ix, tree) in siblings.iter().enumerate() {
+            let node = Node { tree, siblings, ix };
+            path.push(ix);
+            if self.alternatives.iter().any(|it| it.is_match(&node, stack)) {
+                matches.push(Match {
+                    tree,
+                    path: path.clone(),
+                    span: tree.span(),
+                })
+            }
+            if let Some(Ast(children)) = tree.stream() {
+                stack.push(node);
+                self.walk(children, stack, path, matches);
+                stack.pop();
+            }
+            path.pop();
+        }
+    }
+}
+
+impl std::str::FromStr for Selector {
+    type Err = ParseError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl<'a> Ast<'a> {
+    /// Compile `selector` and return all matches.
+    ///
+    /// See the [`query`](crate::query) module for the syntax.
+    pub fn query(&self, selector: &str) -> Result<Vec<Match<'_, 'a>>, ParseError> {
+        Ok(Selector::parse(selector)?.matches(self))
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Node<'ast, 'a> {
+    tree: &'ast Tree<'a>,
+    siblings: &'ast [Tree<'a>],
+    ix: usize,
+}
+
+impl Complex {
+    fn is_match(&self, node: &Node, ancestors: &[Node]) -> bool {
+        self.is_match_at(self.rest.len(), node, ancestors)
+    }
+    /// Whether the compound at `depth` (and everything to its left) matches `node`.
+    fn is_match_at(&self, depth: usize, node: &Node, ancestors: &[Node]) -> bool {
+        let (combinator, compound) = match depth.checked_sub(1) {
+            None => return self.first.is_match(node),
+            Some(ix) => {
+                let (combinator, compound) = &self.rest[ix];
+                (*combinator, compound)
+            }
+        };
+        if !compound.is_match(node) {
+            return false;
+        }
+        let left = depth - 1;
+        match combinator {
+            Combinator::Child => match ancestors.split_last() {
+                Some((parent, rest)) => self.is_match_at(left, parent, rest),
+                None => false,
+            },
+            Combinator::Descendant => (0..ancestors.len())
+                .rev()
+                .any(|ix| self.is_match_at(left, &ancestors[ix], &ancestors[..ix])),
+            Combinator::NextSibling => match node.ix.checked_sub(1) {
+                Some(ix) => self.is_match_at(left, &node.sibling(ix), ancestors),
+                None => false,
+            },
+            Combinator::LaterSibling => {
+                (0..node.ix).any(|ix| self.is_match_at(left, &node.sibling(ix), ancestors))
+            }
+            Combinator::Section => {
+                // the highest level of the headings between a heading and `node`
+                let mut closest = heading_level(node.tree).unwrap_or(usize::MAX);
+                (0..node.ix).rev().any(|ix| {
+                    let sibling = node.sibling(ix);
+                    let Some(level) = heading_level(sibling.tree) else {
+                        return false;
+                    };
+                    let found = level < closest && self.is_match_at(left, &sibling, ancestors);
+                    closest = closest.min(level);
+                    found
+                })
+            }
+        }
+    }
+}
+
+impl<'ast, 'a> Node<'ast, 'a> {
+    fn sibling(&self, ix: usize) -> Self {
+        Self {
+            tree: &self.siblings[ix],
+            siblings: self.siblings,
+            ix,
+        }
+    }
+}
+
+fn heading_level(tree: &Tree<'_>) -> Option<usize> {
+    match tree {
+        Tree::Group(Group {
+            tag:
+                Spanned {
+                    item: Tag::Heading { level, .. },
+                    ..
+                },
+            ..
+        }) => Some(*level as usize),
+        _ => None,
+    }
+}
+
+fn normalize_kind(s: &str) -> String {
+    s.chars()
+        .filter(|it| *it != '-' && *it != '_')
+        .map(|it| it.to_ascii_lowercase())
+        .collect()
+}
+
+impl Compound {
+    fn is_match(&self, node: &Node) -> bool {
+        if let Some(kind) = &self.kind {
+            if normalize_kind(node.tree.kind()) != *kind {
+                return false;
+            }
+        }
+        self.attrs.iter().all(|attr| {
+            let Some(value) = attribute(node.tree, &attr.name) else {
+                return false;
+            };
+            match &attr.op {
+                None => true,
+                Some((Op::Equals, it)) => value == *it,
+                Some((Op::Prefix, it)) => value.starts_with(it.as_str()),
+                Some((Op::Suffix, it)) => value.ends_with(it.as_str()),
+                Some((Op::Substring, it)) => value.contains(it.as_str()),
+                Some((Op::Word, it)) => value.split_whitespace().any(|word| word == it),
+            }
+        }) && self.pseudos.iter().all(|pseudo| match pseudo {
+            Pseudo::FirstChild => node.ix == 0,
+            Pseudo::LastChild => node.ix + 1 == node.siblings.len(),
+            Pseudo::OnlyChild => node.siblings.len() == 1,
+            Pseudo::Empty => node.tree.stream().is_some_and(|it| it.0.is_empty()),
+            Pseudo::Contains(it) => node.tree.plain_text().contains(it.as_str()),
+        })
+    }
+}
+
+pub(crate) fn link_type(it: LinkType) -> &'static str {
+    match it {
+        LinkType::Inline => "inline",
+        LinkType::Reference => "reference",
+        LinkType::ReferenceUnknown => "reference-unknown",
+        LinkType::Collapsed => "collapsed",
+        LinkType::CollapsedUnknown => "collapsed-unknown",
+        LinkType::Shortcut => "shortcut",
+        LinkType::ShortcutUnknown => "shortcut-unknown",
+        LinkType::Autolink => "autolink",
+        LinkType::Email => "email",
+    }
+}
+
+pub(crate) fn attribute<'t>(tree: &'t Tree, name: &str) -> Option<Cow<'t, str>> {
+    let borrowed = |it: &'t str| Some(Cow::Borrowed(it));
+    match tree {
+        Tree::Group(Group {
+            tag: Spanned { item: tag, .. },
+            ..
+        }) => match (tag, name) {
+            (Tag::Heading { level, .. }, "level") => {
+                Some(Cow::Owned((*level as usize).to_string()))
+            }
+            (Tag::Heading { id: Some(id), .. }, "id") => borrowed(id),
+            (Tag::Heading { classes, .. }, "class") if !classes.is_empty() => Some(Cow::Owned(
+                classes.iter().map(|it| &**it).collect::<Vec<_>>().join(" "),
+            )),
+            (Tag::Heading { attrs, .. }, _) => attrs
+                .iter()
+                .find(|(k, _)| **k == *name)
+                .map(|(_, v)| Cow::Borrowed(v.as_deref().unwrap_or_default())),
+            (Tag::CodeBlock(CodeBlockKind::Fenced(info)), "lang") => {
+                info.split_whitespace().next().and_then(borrowed)
+            }
+            (Tag::CodeBlock(CodeBlockKind::Fenced(info)), "info") => borrowed(info),
+            (Tag::CodeBlock(kind), "fenced") => borrowed(match kind.is_fenced() {
+                true => "true",
+                false => "false",
+            }),
+            (Tag::List(start), "ordered") => borrowed(match start.is_some() {
+                true => "true",
+                false => "false",
+            }),
+            (Tag::List(Some(start)), "start") => Some(Cow::Owned(start.to_string())),
+            (
+                Tag::Link {
+                    link_type,
+                    dest_url,
+                    title,
+                    id,
+                }
+                | Tag::Image {
+                    link_type,
+                    dest_url,
+                    title,
+                    id,
+                },
+                _,
+            ) => match name {
+                "href" => borrowed(dest_url),
+                "title" => borrowed(title),
+                "id" => borrowed(id),
+                "type" => borrowed(self::link_type(*link_type)),
+                _ => None,
+            },
+            (Tag::FootnoteDefinition(label), "label") => borrowed(label),
+            (Tag::BlockQuote(Some(kind)), "kind") => borrowed(match kind {
+                BlockQuoteKind::Note => "note",
+                BlockQuoteKind::Tip => "tip",
+                BlockQuoteKind::Important => "important",
+                BlockQuoteKind::Warning => "warning",
+                BlockQuoteKind::Caution => "caution",
+            }),
+            (Tag::MetadataBlock(kind), "kind") => borrowed(match kind {
+                MetadataBlockKind::YamlStyle => "yaml",
+                MetadataBlockKind::PlusesStyle => "toml",
+            }),
+            _ => None,
+        },
+        Tree::FootnoteReference(Spanned { item, .. }) if name == "label" => borrowed(item),
+        Tree::BrokenLink(
+            BrokenLink {
+                reference,
+                link_type,
+                ..
+            },
+            _,
+        ) => match name {
+            "reference" => borrowed(reference),
+            "type" => borrowed(self::link_type(*link_type)),
+            _ => None,
+        },
+        Tree::TaskListMarker(Spanned { item, .. }) if name == "checked" => borrowed(match item {
+            true => "true",
+            false => "false",
+        }),
+        Tree::Text(Spanned { item, .. })
+        | Tree::Code(Spanned { item, .. })
+        | Tree::Html(Spanned { item, .. })
+        | Tree::InlineHtml(Spanned { item, .. })
+        | Tree::InlineMath(Spanned { item, .. })
+        | Tree::DisplayMath(Spanned { item, .. })
+            if name == "content" =>
+        {
+            borrowed(item)
+        }
+        _ => None,
+    }
+}
+
+struct Parser<'s> {
+    s: &'s str,
+    pos: usize,
+}
+
+impl<'s> Parser<'s> {
+    fn err<T>(&self, message: impl Into<String>) -> Result<T, ParseError> {
+        Err(ParseError {
+            offset: self.pos,
+            message: message.into(),
+        })
+    }
+    fn peek(&self) -> Option<char> {
+        self.s[self.pos..].chars().next()
+    }
+    fn eat(&mut self, c: char) -> bool {
+        match self.peek() == Some(c) {
+            true => {
+                self.pos += c.len_utf8();
+                true
+            }
+            false => false,
+        }
+    }
+    fn expect(&mut self, c: char) -> Result<(), ParseError> {
+        match self.eat(c) {
+            true => Ok(()),
+            false => self.err(format!("expected `{c}`")),
+        }
+    }
+    /// Returns whether any whitespace was skipped.
+    fn whitespace(&mut self) -> bool {
+        let start = self.pos;
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.pos += self.peek().unwrap().len_utf8();
+        }
+        self.pos != start
+    }
+    fn ident(&mut self) -> &'s str {
+        let start = self.pos;
+        while self
+            .peek()
+            .is_some_and(|it| it.is_alphanumeric() || it == '-' || it == '_')
+        {
+            self.pos += self.peek().unwrap().len_utf8();
+        }
+        &self.s[start..self.pos]
+    }
+    fn selector(mut self) -> Result<Selector, ParseError> {
+        let mut alternatives = vec![];
+        loop {
+            self.whitespace();
+            alternatives.push(self.complex()?);
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                None => break,
+                Some(c) => return self.err(format!("unexpected `{c}`")),
+            }
+        }
+        Ok(Selector { alternatives })
+    }
+    fn complex(&mut self) -> Result<Complex, ParseError> {
+        let first = self.compound()?;
+        let mut rest = vec![];
+        loop {
+            let ws = self.whitespace();
+            let combinator = match self.peek() {
+                Some('>') => Combinator::Child,
+                Some('+') => Combinator::NextSibling,
+                Some('~') => Combinator::LaterSibling,
+                Some('|') => Combinator::Section,
+                Some(',') | None => break,
+                Some(_) if ws => {
+                    rest.push((Combinator::Descendant, self.compound()?));
+                    continue;
+                }
+                Some(c) => return self.err(format!("unexpected `{c}`")),
+            };
+            self.pos += 1;
+            if combinator == Combinator::Section {
+                self.expect('|')?
+            }
+            self.whitespace();
+            rest.push((combinator, self.compound()?));
+        }
+        Ok(Complex { first, rest })
+    }
+    fn compound(&mut self) -> Result<Compound, ParseError> {
+        let mut compound = Compound::default();
+        let start = self.pos;
+        if !self.eat('*') {
+            let kind = self.ident();
+            if !kind.is_empty() {
+                compound.kind = Some(normalize_kind(kind))
+            }
+        }
+        loop {
+            match self.peek() {
+                Some('[') => {
+                    self.pos += 1;
+                    compound.attrs.push(self.attr()?)
+                }
+                Some(':') => {
+                    self.pos += 1;
+                    compound.pseudos.push(self.pseudo()?)
+                }
+                _ => break,
+            }
+        }
+        match self.pos == start {
+            true => self.err("expected a selector"),
+            false => Ok(compound),
+        }
+    }
+    fn attr(&mut self) -> Result<Attr, ParseError> {
+        self.whitespace();
+        let name = self.ident();
+        if name.is_empty() {
+            return self.err("expected an attribute name");
+        }
+        self.whitespace();
+        let op = match self.peek() {
+            Some(']') => None,
+            Some('=') => Some(Op::Equals),
+            Some('^') => Some(Op::Prefix),
+            Some('$') => Some(Op::Suffix),
+            Some('*') => Some(Op::Substring),
+            Some('~') => Some(Op::Word),
+            _ => return self.err("expected an attribute operator or `]`"),
+        };
+        let op = match op {
+            None => None,
+            Some(op) => {
+                self.pos += 1;
+                if op != Op::Equals {
+                    self.expect('=')?
+                }
+                self.whitespace();
+                let value = self.value(|c| c == ']' || c.is_whitespace())?;
+                self.whitespace();
+                Some((op, value))
+            }
+        };
+        self.expect(']')?;
+        Ok(Attr {
+            name: name.into(),
+            op,
+        })
+    }
+    fn pseudo(&mut self) -> Result<Pseudo, ParseError> {
+        let start = self.pos;
+        match self.ident() {
+            "first-child" => Ok(Pseudo::FirstChild),
+            "last-child" => Ok(Pseudo::LastChild),
+            "only-child" => Ok(Pseudo::OnlyChild),
+            "empty" => Ok(Pseudo::Empty),
+            "contains" => {
+                self.expect('(')?;
+                self.whitespace();
+                let value = self.value(|c| c == ')')?;
+                self.whitespace();
+                self.expect(')')?;
+                Ok(Pseudo::Contains(value))
+            }
+            other => {
+                self.pos = start;
+                self.err(format!("unknown pseudo-class `:{other}`"))
+            }
+        }
+    }
+    /// A quoted string, or everything until `end`.
+    fn value(&mut self, end: impl Fn(char) -> bool) -> Result<String, ParseError> {
+        match self.peek() {
+            Some(quote @ ('"' | '\'')) => {
+                self.pos += 1;
+                let mut value = String::new();
+                loop {
+                    match self.peek() {
+                        None => return self.err("unterminated string"),
+                        Some(c) if c == quote => {
+                            self.pos += 1;
+                            return Ok(value);
+                        }
+                        Some('\\') => {
+                            self.pos += 1;
+                            match self.peek() {
+                                Some(c) => {
+                                    value.push(c);
+                                    self.pos += c.len_utf8()
+                                }
+                                None => return self.err("unterminated string"),
+                            }
+                        }
+                        Some(c) => {
+                            value.push(c);
+                            self.pos += c.len_utf8()
+                        }
+                    }
+                }
+            }
+            _ => {
+                let start = self.pos;
+                while self.peek().is_some_and(|c| !end(c)) {
+                    self.pos += self.peek().unwrap().len_utf8();
+                }
+                match self.pos == start {
+                    true => self.err("expected a value"),
+                    false => Ok(self.s[start..self.pos].trim_end().into()),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pulldown_cmark::Options;
+
+    use super::*;
+
+    const DOC: &str = "\
+# Intro {#intro .lead}
+
+Some [docs](https://example.com \"Docs\") and [more](./more.md).
+
+## Usage
+
+```rust
+fn main() {}
+```
+
+- one
+- two TODO
+- three
+
+> quote with [ref]
+
+[ref]: http://ref
+";
+
+    fn texts(selector: &str) -> Vec<String> {
+        let ast = Ast::new_ext(DOC, Options::ENABLE_HEADING_ATTRIBUTES);
+        ast.query(selector)
+            .unwrap()
+            .iter()
+            .map(|it| it.tree.plain_text())
+            .collect()
+    }
+
+    #[test]
+    fn names() {
+        assert_eq!(texts("heading"), ["Intro", "Usage"]);
+        assert_eq!(texts("CodeBlock"), texts("code-block"));
+        assert_eq!(texts("code-block"), ["fn main() {}\n"]);
+        assert_eq!(
+            texts("*").len(),
+            Ast::new_ext(DOC, Options::ENABLE_HEADING_ATTRIBUTES)
+                .index()
+                .len()
+        );
+    }
+
+    #[test]
+    fn attributes() {
+        assert_eq!(texts("heading[level=2]"), ["Usage"]);
+        assert_eq!(texts("heading[id=intro]"), ["Intro"]);
+        assert_eq!(texts("heading[class~=lead]"), ["Intro"]);
+        assert_eq!(texts("heading[id]"), ["Intro"]);
+        assert_eq!(texts("link[href^=http]"), ["docs", "ref"]);
+        assert_eq!(texts("link[href$='.md']"), ["more"]);
+        assert_eq!(texts("link[href*=example]"), ["docs"]);
+        assert_eq!(texts("link[title=Docs]"), ["docs"]);
+        assert_eq!(texts("code-block[lang=rust]").len(), 1);
+        assert_eq!(texts("code-block[lang=python]").len(), 0);
+        assert_eq!(texts("text[content*=TODO]"), ["two TODO"]);
+    }
+
+    #[test]
+    fn combinators() {
+        assert_eq!(texts("list > item"), ["one", "two TODO", "three"]);
+        assert_eq!(texts("block-quote link"), ["ref"]);
+        assert_eq!(texts("block-quote > link"), Vec::<String>::new());
+        assert_eq!(texts("heading + paragraph > link"), ["docs", "more"]);
+        assert_eq!(texts("heading[level=2] ~ block-quote"), ["quote with ref"]);
+        assert_eq!(texts("heading[level=2] + block-quote").len(), 0);
+    }
+
+    #[test]
+    fn sections() {
+        let ast = Ast::new(
+            "\
+# A
+
+```rust
+a
+```
+
+## B
+
+```rust
+b
+```
+
+- ```rust
+  nested
+  ```
+
+### C
+
+```rust
+c
+```
+
+```sh
+d
+```
+
+## E
+
+```rust
+e
+```
+",
+        );
+        let texts = |selector: &str| {
+            ast.query(selector)
+                .unwrap()
+                .iter()
+                .map(|it| it.tree.plain_text().trim_end().to_owned())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            texts("heading[level=2] || code-block[lang=rust]"),
+            ["b", "c", "e"]
+        );
+        assert_eq!(texts("heading:contains(B) || code-block"), ["b", "c", "d"]);
+        assert_eq!(texts("heading[level=3] || code-block"), ["c", "d"]);
+        assert_eq!(texts("heading[level=1] || heading"), ["B", "C", "E"]);
+        assert_eq!(texts("heading[level=2] || heading"), ["C"]);
+        assert_eq!(texts("heading || * code-block"), ["nested"]);
+        assert_eq!(texts("heading:contains(E) || list").len(), 0);
+        // not a heading, so no section
+        assert_eq!(texts("list || code-block").len(), 0);
+    }
+
+    #[test]
+    fn pseudo_classes() {
+        assert_eq!(texts("item:first-child"), ["one"]);
+        assert_eq!(texts("item:last-child"), ["three"]);
+        assert_eq!(texts("link > text:only-child").len(), 3);
+        assert_eq!(texts("item:contains(TODO)"), ["two TODO"]);
+        assert_eq!(
+            texts("heading, item:contains(\"one\")"),
+            ["Intro", "Usage", "one"]
+        );
+        assert_eq!(Ast::new("- \n").query("item:empty").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn matches_are_in_document_order_with_paths() {
+        let ast = Ast::new("a *b*\n\n*c*\n");
+        let matches = ast.query("emphasis").unwrap();
+        let paths = matches.iter().map(|it| it.path.clone()).collect::<Vec<_>>();
+        assert_eq!(paths, [vec![0, 1], vec![1, 0]]);
+        assert_eq!(matches[1].span, Span(7..10));
+        let selector = Selector::parse("emphasis").unwrap();
+        assert!(selector.is_match(&ast, &[1, 0]));
+        assert!(!selector.is_match(&ast, &[1]));
+        assert!(!selector.is_match(&ast, &[5]));
+    }
+
+    #[test]
+    fn errors() {
+        for selector in [
+            "",
+            "heading[",
+            "heading[level=",
+            "list >",
+            ":nope",
+            "a,",
+            "a | b",
+        ] {
+            assert!(Selector::parse(selector).is_err(), "{selector:?}");
+        }
+        let err = Selector::parse("heading[level=2").unwrap_err();
+        assert_eq!(err.offset, 15);
+    }
+}
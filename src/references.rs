@@ -0,0 +1,440 @@
+//! Link reference definitions, like `[label]: https://example.com "title"`.
+//!
+//! [`pulldown_cmark`] resolves reference links while parsing, so the definitions
+//! don't appear in the [`Ast`].
+//! Collect them with [`Ast::new_with_reference_definitions`].
+
+use std::{collections::HashSet, ops::Range};
+
+use pulldown_cmark::{CowStr, LinkType, RefDefs, Tag};
+
+use crate::{Ast, Group, Span, Spanned, Tree};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReferenceDefinition<'a> {
+    /// The label as written in the source, without the brackets.
+    pub label: CowStr<'a>,
+    pub dest_url: CowStr<'a>,
+    pub title: Option<CowStr<'a>>,
+    pub span: Span,
+    /// Where the destination is written, inside any `<...>`.
+    pub dest_span: Option<Span>,
+}
+
+impl ReferenceDefinition<'_> {
+    /// The label, normalized for matching against the `id` of links.
+    pub fn key(&self) -> String {
+        normalize(&self.label)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ReferenceDefinitions<'a> {
+    /// In document order.
+    definitions: Vec<ReferenceDefinition<'a>>,
+    duplicates: Vec<ReferenceDefinition<'a>>,
+}
+
+impl<'a> ReferenceDefinitions<'a> {
+    pub(crate) fn from_parser(text: &'a str, refdefs: &RefDefs<'_>) -> Self {
+        let mut definitions = refdefs
+            .iter()
+            .map(|(key, def)| ReferenceDefinition {
+                label: text
+                    .get(def.span.clone())
+                    .and_then(|it| Some(&it[1..label_end(it)?]))
+                    .map(CowStr::Borrowed)
+                    .unwrap_or_else(|| key.to_owned().into()),
+                dest_url: reborrow(text, &def.dest),
+                title: def.title.as_deref().map(|it| reborrow(text, it)),
+                span: Span(def.span.clone()),
+                dest_span: text
+                    .get(def.span.clone())
+                    .and_then(label_end)
+                    .and_then(|end| destination(text, def.span.start + end))
+                    .map(|(dest, _)| Span(dest)),
+            })
+            .collect::<Vec<_>>();
+        definitions.sort_by_key(|it| it.span.0.start);
+        Self {
+            definitions,
+            duplicates: vec![],
+        }
+    }
+    /// [`pulldown_cmark`] only keeps the first definition of each label,
+    /// so find the others in the source.
+    pub(crate) fn with_duplicates(mut self, text: &'a str, ast: &Ast<'a>) -> Self {
+        let keys = self
+            .definitions
+            .iter()
+            .map(|it| it.key())
+            .collect::<HashSet<_>>();
+        let mut occupied = vec![];
+        occupied_spans(ast, &mut occupied);
+        occupied.extend(self.definitions.iter().map(|it| it.span.clone()));
+
+        let mut pos = 0;
+        while pos < text.len() {
+            let line_end = text[pos..].find('\n').map_or(text.len(), |it| pos + it + 1);
+            let start = pos + container_prefix_len(&text[pos..line_end]);
+            pos = line_end;
+            if !text[start..].starts_with('[')
+                || occupied.iter().any(|Span(it)| it.contains(&start))
+            {
+                continue;
+            }
+            if let Some(def) = parse_definition(text, start) {
+                if keys.contains(&def.key()) {
+                    pos = pos.max(def.span.0.end);
+                    self.duplicates.push(def)
+                }
+            }
+        }
+        self
+    }
+    /// The effective definitions, in document order.
+    pub fn iter(&self) -> impl Iterator<Item = &ReferenceDefinition<'a>> {
+        self.definitions.iter()
+    }
+    pub fn len(&self) -> usize {
+        self.definitions.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.definitions.is_empty()
+    }
+    /// Look up a definition, matching labels case-insensitively.
+    pub fn get(&self, label: &str) -> Option<&ReferenceDefinition<'a>> {
+        let key = normalize(label);
+        self.definitions.iter().find(|it| it.key() == key)
+    }
+    /// Definitions which were ignored because an earlier definition had the same label.
+    pub fn duplicates(&self) -> &[ReferenceDefinition<'a>] {
+        &self.duplicates
+    }
+    /// Definitions which no link or image in `ast` refers to.
+    pub fn unused(&self, ast: &Ast<'_>) -> Vec<&ReferenceDefinition<'a>> {
+        let mut used = HashSet::new();
+        used_keys(ast, &mut used);
+        self.definitions
+            .iter()
+            .filter(|it| !used.contains(&it.key()))
+            .collect()
+    }
+}
+
+impl<'a> IntoIterator for ReferenceDefinitions<'a> {
+    type Item = ReferenceDefinition<'a>;
+    type IntoIter = std::vec::IntoIter<ReferenceDefinition<'a>>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.definitions.into_iter()
+    }
+}
+
+/// Link labels match case-insensitively, with runs of whitespace collapsed.
+pub(crate) fn normalize(label: &str) -> String {
+    label
+        .lines()
+        .enumerate()
+        .map(|(ix, line)| match ix {
+            // continuation lines in block quotes
+            0 => line,
+            _ => line.trim_start_matches(|c: char| c.is_whitespace() || c == '>'),
+        })
+        .flat_map(str::split_whitespace)
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+/// Borrow `s` from `text` if it's a slice of it.
+fn reborrow<'a>(text: &'a str, s: &str) -> CowStr<'a> {
+    let start = (s.as_ptr() as usize).wrapping_sub(text.as_ptr() as usize);
+    match text.get(start..start.wrapping_add(s.len())) {
+        Some(it) if it.as_ptr() == s.as_ptr() => CowStr::Borrowed(it),
+        _ => s.to_owned().into(),
+    }
+}
+
+fn used_keys(ast: &Ast<'_>, used: &mut HashSet<String>) {
+    for tree in &ast.0 {
+        if let Tree::Group(Group {
+            tag:
+                Spanned {
+                    item: Tag::Link { link_type, id, .. } | Tag::Image { link_type, id, .. },
+                    ..
+                },
+            ..
+        }) = tree
+        {
+            if let LinkType::Reference | LinkType::Collapsed | LinkType::Shortcut = link_type {
+                used.insert(normalize(id));
+            }
+        }
+        if let Some(stream) = tree.stream() {
+            used_keys(stream, used)
+        }
+    }
+}
+
+/// Spans of nodes which can't contain a definition.
+fn occupied_spans(ast: &Ast<'_>, spans: &mut Vec<Span>) {
+    for tree in &ast.0 {
+        match tree {
+            Tree::Group(Group {
+                tag:
+                    Spanned {
+                        item:
+                            Tag::BlockQuote(_) | Tag::List(_) | Tag::Item | Tag::FootnoteDefinition(_),
+                        ..
+                    },
+                stream,
+                ..
+            }) => occupied_spans(stream, spans),
+            _ => spans.push(tree.span()),
+        }
+    }
+}
+
+/// Block quote markers, list markers and indentation at the start of a line.
+fn container_prefix_len(line: &str) -> usize {
+    let mut rest = line;
+    loop {
+        let trimmed = rest.trim_start_matches([' ', '\t', '>']);
+        let digits = trimmed.len()
+            - trimmed
+                .trim_start_matches(|c: char| c.is_ascii_digit())
+                .len();
+        let marker = match trimmed.as_bytes() {
+            [b'-' | b'*' | b'+', b' ' | b'\t', ..] => 1,
+            _ if digits > 0 => match trimmed.as_bytes()[digits..] {
+                [b'.' | b')', b' ' | b'\t', ..] => digits + 1,
+                _ => 0,
+            },
+            _ => 0,
+        };
+        if marker == 0 {
+            return line.len() - trimmed.len();
+        }
+        rest = &trimmed[marker..];
+    }
+}
+
+/// Index of the `]` ending the label which `s` starts with, if it is followed by `:`.
+fn label_end(s: &str) -> Option<usize> {
+    let mut escaped = false;
+    for (ix, c) in s.char_indices().skip(1) {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '[' => return None,
+            ']' => {
+                return match s[ix + 1..].starts_with(':') {
+                    true => Some(ix),
+                    false => None,
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Skip spaces, and at most one line ending (with any block quote markers).
+fn skip_whitespace(text: &str, mut pos: usize) -> (usize, bool) {
+    let mut newline = false;
+    loop {
+        match text[pos..].chars().next() {
+            Some(' ' | '\t') => pos += 1,
+            Some('>') if newline => pos += 1,
+            Some('\n') if !newline => {
+                newline = true;
+                pos += 1
+            }
+            _ => return (pos, newline),
+        }
+    }
+}
+
+fn parse_definition(text: &str, start: usize) -> Option<ReferenceDefinition<'_>> {
+    let label_end = start + label_end(&text[start..])?;
+    let label = &text[start + 1..label_end];
+    if label.trim().is_empty() {
+        return None;
+    }
+    let (dest, mut end) = destination(text, label_end)?;
+    let (title_start, newline) = skip_whitespace(text, end);
+    let mut title = None;
+    if title_start != end || newline {
+        let close = match text[title_start..].chars().next() {
+            Some('"') => Some('"'),
+            Some('\'') => Some('\''),
+            Some('(') => Some(')'),
+            _ => None,
+        };
+        if let Some(close) = close {
+            if let Some(len) = text[title_start + 1..].find(close) {
+                let title_end = title_start + 1 + len + 1;
+                let trailing = text[title_end..].split('\n').next().unwrap_or_default();
+                if trailing.trim().is_empty() {
+                    title = Some(CowStr::Borrowed(&text[title_start + 1..title_end - 1]));
+                    end = title_end;
+                }
+            }
+        }
+    }
+    Some(ReferenceDefinition {
+        label: CowStr::Borrowed(label),
+        dest_url: CowStr::Borrowed(&text[dest.clone()]),
+        title,
+        span: Span(start..end),
+        dest_span: Some(Span(dest)),
+    })
+}
+
+/// The destination following the label ending at `label_end`, inside any `<...>`,
+/// and where the destination ends.
+fn destination(text: &str, label_end: usize) -> Option<(Range<usize>, usize)> {
+    let (start, _) = skip_whitespace(text, label_end + 2);
+    let rest = &text[start..];
+    match rest.strip_prefix('<') {
+        Some(inner) => {
+            let len = inner.find(['>', '\n'])?;
+            if !inner[len..].starts_with('>') {
+                return None;
+            }
+            Some((start + 1..start + 1 + len, start + len + 2))
+        }
+        None => {
+            let len = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            if len == 0 {
+                return None;
+            }
+            Some((start..start + len, start + len))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pulldown_cmark::Options;
+
+    use super::*;
+
+    fn definitions(source: &str) -> ReferenceDefinitions<'_> {
+        Ast::new_with_reference_definitions(source, Options::empty()).1
+    }
+
+    fn parsed(source: &str) -> Option<(&str, &str, Option<&str>, &str)> {
+        let def = parse_definition(source, 0)?;
+        let dest = &source[def.dest_span.clone()?.0];
+        Some((
+            match def.label {
+                CowStr::Borrowed(it) => it,
+                _ => unreachable!(),
+            },
+            dest,
+            def.title.map(|it| match it {
+                CowStr::Borrowed(it) => it,
+                _ => unreachable!(),
+            }),
+            &source[def.span.0],
+        ))
+    }
+
+    #[test]
+    fn collect() {
+        let source = "[a]\n\n[a]: /u \"t\"\n[B]: <b c>\n";
+        let definitions = definitions(source);
+        assert_eq!(definitions.len(), 2);
+        let a = definitions.get("A").unwrap();
+        assert_eq!((&*a.label, &*a.dest_url), ("a", "/u"));
+        assert_eq!(a.title.as_deref(), Some("t"));
+        assert_eq!(&source[a.span.0.clone()], "[a]: /u \"t\"");
+        assert_eq!(&source[a.dest_span.clone().unwrap().0], "/u");
+        let b = definitions.get("b").unwrap();
+        assert_eq!(&*b.dest_url, "b c");
+        assert_eq!(&source[b.dest_span.clone().unwrap().0], "b c");
+        assert_eq!(
+            definitions.unused(&Ast::new(source)),
+            [definitions.get("b").unwrap()]
+        );
+    }
+
+    #[test]
+    fn labels_match_ignoring_case_and_whitespace() {
+        let definitions = definitions("[Foo\n  Bar]: /u\n");
+        assert!(definitions.get("foo bar").is_some());
+        assert!(definitions.get("FOO   BAR").is_some());
+        assert!(definitions.get("foobar").is_none());
+        assert_eq!(normalize("> a\n> b"), "> a b");
+        assert_eq!(normalize("a\n>  b"), "a b");
+    }
+
+    #[test]
+    fn duplicates() {
+        let source = "\
+[a]: /first
+[A]: /second
+
+> [a]: /quoted
+
+- [a]: /listed
+
+`[a]: /code`
+
+    [a]: /indented
+";
+        let definitions = definitions(source);
+        assert_eq!(&*definitions.get("a").unwrap().dest_url, "/first");
+        let duplicates = definitions
+            .duplicates()
+            .iter()
+            .map(|it| &*it.dest_url)
+            .collect::<Vec<_>>();
+        assert_eq!(duplicates, ["/second", "/quoted", "/listed"]);
+    }
+
+    #[test]
+    fn container_prefixes() {
+        assert_eq!(container_prefix_len("[a]: b"), 0);
+        assert_eq!(container_prefix_len("  > [a]: b"), 4);
+        assert_eq!(container_prefix_len("> > [a]: b"), 4);
+        assert_eq!(container_prefix_len("- [a]: b"), 2);
+        assert_eq!(container_prefix_len("> 1. - [a]: b"), 7);
+        assert_eq!(container_prefix_len("10) [a]: b"), 4);
+        assert_eq!(container_prefix_len("-[a]: b"), 0);
+        assert_eq!(container_prefix_len("1.5 [a]"), 0);
+    }
+
+    #[test]
+    fn labels() {
+        assert_eq!(label_end("[a]: b"), Some(2));
+        assert_eq!(label_end("[a\\]b]: c"), Some(5));
+        assert_eq!(label_end("[a\\[b]: c"), Some(5));
+        assert_eq!(label_end("[a [b]]: c"), None);
+        assert_eq!(label_end("[a] : b"), None);
+        assert_eq!(label_end("[a"), None);
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(parsed("[a]: b"), Some(("a", "b", None, "[a]: b")));
+        assert_eq!(
+            parsed("[a]:\n  <b c> 't'\nd"),
+            Some(("a", "b c", Some("t"), "[a]:\n  <b c> 't'"))
+        );
+        assert_eq!(
+            parsed("[a]: b\n(t)\n"),
+            Some(("a", "b", Some("t"), "[a]: b\n(t)"))
+        );
+        // a title followed by more text isn't a title
+        assert_eq!(parsed("[a]: b \"t\" c"), Some(("a", "b", None, "[a]: b")));
+        // titles on the next line of a block quote
+        let quoted = parse_definition("> [a]: b\n> \"t\"", 2).unwrap();
+        assert_eq!(quoted.title.as_deref(), Some("t"));
+        assert_eq!(parsed("[a]: <b\nc>"), None);
+        assert_eq!(parsed("[a]:"), None);
+        assert_eq!(parsed("[ ]: b"), None);
+        assert_eq!(parsed("[a] b"), None);
+    }
+}
@@ -0,0 +1,382 @@
+//! Update an [`Ast`] after an [`Edit`] to its source, without parsing the whole document.
+//!
+//! The top-level blocks touching the edit are parsed again,
+//! along with the block on either side of them.
+//! If those neighbours come out the same as before,
+//! the edit didn't change block structure beyond them,
+//! so the new blocks are spliced in and the spans of later blocks are shifted.
+//! Otherwise, like when the edit opens a fenced code block or joins two lists,
+//! the whole document is parsed again.
+//!
+//! Link reference definitions and footnote definitions affect the whole document,
+//! and aren't in the tree, so the whole document is also parsed again when:
+//! - The edit isn't inside a leaf block, like a paragraph or code block,
+//!   and so may have changed a definition.
+//! - The reparsed text contains a definition, or a footnote reference.
+//! - The reparsed text has a reference link to a label which no link in the tree used.
+//! - Metadata blocks are enabled, and the reparsed text starts the document,
+//!   since a metadata block there ends at the first closing fence after it.
+//!
+//! Either way, the result is the same as [`Ast::new_ext`] on the new source.
+
+use std::ops::Range;
+
+use pulldown_cmark::{LinkType, Options, Tag};
+
+use crate::{references, Ast, BrokenLink, Edit, Group, Span, Spanned, Tree};
+
+impl Ast<'_> {
+    /// Update this tree, parsed from the source before `edit`,
+    /// to match `new_source`, which has `edit` applied.
+    ///
+    /// See the [module documentation](crate::reparse).
+    pub fn reparse(&mut self, edit: &Edit, new_source: &str) {
+        self.reparse_ext(edit, new_source, Options::empty())
+    }
+    /// `options` must be the ones this tree was parsed with.
+    pub fn reparse_ext(&mut self, edit: &Edit, new_source: &str, options: Options) {
+        if !reparse(self, edit, new_source, options) {
+            *self = Ast::new_ext(new_source, options).into_static()
+        }
+    }
+}
+
+/// Reparse only the blocks around `edit`, returning `false` if the whole source must be.
+fn reparse(ast: &mut Ast<'_>, edit: &Edit, new_source: &str, options: Options) -> bool {
+    let Span(edited) = &edit.span;
+    if edited.start > edited.end || !in_leaf(&ast.0, edited) {
+        return false;
+    }
+    let blocks = &ast.0;
+    // blocks before `first` and from `after` on don't touch the edit
+    let first = blocks
+        .iter()
+        .take_while(|it| it.span().0.end < edited.start)
+        .count();
+    let after = blocks
+        .iter()
+        .take_while(|it| it.span().0.start <= edited.end)
+        .count();
+    let (leading, trailing) = (first.checked_sub(1), blocks.get(after).map(|_| after));
+    if leading.is_none() && trailing.is_none() {
+        return false;
+    }
+    // offsets after the edit, in the new source
+    let shifted = |offset: usize| offset - edited.len() + edit.replacement.len();
+    let start = leading.map_or(0, |ix| blocks[ix].span().0.start);
+    let end = blocks
+        .get(after + 1)
+        .map_or(new_source.len(), |it| shifted(it.span().0.start));
+    let Some(text) = new_source.get(start..end) else {
+        return false;
+    };
+    if text.contains("]:") || (options.contains(Options::ENABLE_FOOTNOTES) && text.contains("[^")) {
+        return false;
+    }
+    // a metadata block at the start may close anywhere after the region
+    let metadata = Options::ENABLE_YAML_STYLE_METADATA_BLOCKS
+        | Options::ENABLE_PLUSES_DELIMITED_METADATA_BLOCKS;
+    if start == 0 && options.intersects(metadata) {
+        return false;
+    }
+
+    let mut unknown = false;
+    let mut region = Ast::new_with_broken_link_callback(
+        text,
+        options,
+        Some(|link: pulldown_cmark::BrokenLink<'_>| {
+            let found = definition(ast, &references::normalize(&link.reference));
+            unknown |= found.is_none();
+            found.map(|(dest, title)| (dest.into(), title.into()))
+        }),
+    )
+    .into_static();
+    if unknown {
+        return false;
+    }
+    known_link_types(&mut region);
+    shift(&mut region, |offset| offset + start);
+
+    // the neighbours must be unchanged, nested spans included,
+    // since parsing them without what's around them can move those
+    let Ast(new) = &region;
+    if let Some(leading) = leading {
+        if new.first() != Some(&blocks[leading]) {
+            return false;
+        }
+    }
+    if let Some(trailing) = trailing {
+        let mut old = Ast(vec![blocks[trailing].clone()]);
+        shift(&mut old, shifted);
+        if new.last() != old.0.first() {
+            return false;
+        }
+    }
+    // a metadata block is only allowed at the start of the document
+    if start != 0 && new.iter().any(|it| it.kind() == "metadata-block") {
+        return false;
+    }
+
+    let replaced = leading.unwrap_or(0)..trailing.map_or(blocks.len(), |ix| ix + 1);
+    let mut rest = Ast(ast.0.split_off(replaced.end));
+    shift(&mut rest, shifted);
+    ast.0.truncate(replaced.start);
+    ast.0.extend(region.0);
+    ast.0.extend(rest.0);
+    true
+}
+
+/// Whether `range` is inside a block which can't contain link reference definitions.
+fn in_leaf(trees: &[Tree<'_>], range: &Range<usize>) -> bool {
+    let Some(tree) = trees.iter().find(|it| {
+        let Span(it) = it.span();
+        it.start <= range.start && range.end <= it.end
+    }) else {
+        return false;
+    };
+    match tree {
+        Tree::Group(Group { tag, stream, .. })
+            if matches!(
+                tag.item,
+                Tag::BlockQuote(_) | Tag::List(_) | Tag::Item | Tag::FootnoteDefinition(_)
+            ) =>
+        {
+            match stream.0.iter().all(Tree::is_block) {
+                true => in_leaf(&stream.0, range),
+                // the inline content of a tight list item
+                false => match (stream.0.first(), stream.0.last()) {
+                    (Some(first), Some(last)) => {
+                        first.span().0.start <= range.start && range.end <= last.span().0.end
+                    }
+                    _ => false,
+                },
+            }
+        }
+        _ => true,
+    }
+}
+
+/// The destination and title of a reference link to `key` in `ast`.
+fn definition(ast: &Ast<'_>, key: &str) -> Option<(String, String)> {
+    ast.0.iter().find_map(|tree| match tree {
+        Tree::Group(Group {
+            tag:
+                Spanned {
+                    item:
+                        Tag::Link {
+                            link_type:
+                                LinkType::Reference | LinkType::Collapsed | LinkType::Shortcut,
+                            dest_url,
+                            title,
+                            id,
+                        }
+                        | Tag::Image {
+                            link_type:
+                                LinkType::Reference | LinkType::Collapsed | LinkType::Shortcut,
+                            dest_url,
+                            title,
+                            id,
+                        },
+                    ..
+                },
+            ..
+        }) if references::normalize(id) == key => Some((dest_url.to_string(), title.to_string())),
+        tree => definition(tree.stream()?, key),
+    })
+}
+
+/// Links resolved by [`definition`] are reported as unknown, but had a definition in the source.
+fn known_link_types(ast: &mut Ast<'_>) {
+    for tree in &mut ast.0 {
+        if let Tree::Group(Group {
+            tag:
+                Spanned {
+                    item: Tag::Link { link_type, .. } | Tag::Image { link_type, .. },
+                    ..
+                },
+            ..
+        }) = tree
+        {
+            *link_type = match *link_type {
+                LinkType::ReferenceUnknown => LinkType::Reference,
+                LinkType::CollapsedUnknown => LinkType::Collapsed,
+                LinkType::ShortcutUnknown => LinkType::Shortcut,
+                other => other,
+            }
+        }
+        if let Some(stream) = tree.stream_mut() {
+            known_link_types(stream)
+        }
+    }
+}
+
+/// Move every span in `ast` by `f`.
+fn shift(ast: &mut Ast<'_>, f: impl Fn(usize) -> usize + Copy) {
+    let shift_span = |Span(range): &mut Span| *range = f(range.start)..f(range.end);
+    for tree in &mut ast.0 {
+        match tree {
+            Tree::Group(Group {
+                tag,
+                stream,
+                end_span,
+            }) => {
+                shift_span(&mut tag.span);
+                shift_span(end_span);
+                shift(stream, f)
+            }
+            Tree::BrokenLink(BrokenLink { span, .. }, stream) => {
+                shift_span(span);
+                shift(stream, f)
+            }
+            Tree::Text(Spanned { span, .. })
+            | Tree::Code(Spanned { span, .. })
+            | Tree::Html(Spanned { span, .. })
+            | Tree::InlineHtml(Spanned { span, .. })
+            | Tree::FootnoteReference(Spanned { span, .. })
+            | Tree::InlineMath(Spanned { span, .. })
+            | Tree::DisplayMath(Spanned { span, .. })
+            | Tree::TaskListMarker(Spanned { span, .. })
+            | Tree::SoftBreak(span)
+            | Tree::HardBreak(span)
+            | Tree::Rule(span) => shift_span(span),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::panic;
+
+    use super::*;
+    use crate::corpus;
+
+    /// Text which changes block structure, or inline structure across blocks.
+    const INSERTIONS: [&str; 36] = [
+        "\n", "\n\n", "\t", " ", "a", "(", ")", "[", "]", "x]", "<", ">", "!", "-", "+", "=", "*",
+        "_", "`", "~", "|", "$", "#", "\\", "&", ":", "1.", "- ", "> ", "# ", "```", "    ", "[a]",
+        "[a]: b\n", "[^a]", "<div>\n",
+    ];
+
+    fn check(source: &str, edit: Edit, options: Options) {
+        let mut new_source = source.to_string();
+        new_source.replace_range(edit.span.0.clone(), &edit.replacement);
+        let mut ast = Ast::new_ext(source, options);
+        ast.reparse_ext(&edit, &new_source, options);
+        assert_eq!(
+            ast,
+            Ast::new_ext(&new_source, options),
+            "{source:?} {edit:?}"
+        );
+    }
+
+    /// An xorshift generator, so failures are reproducible.
+    struct Rng(u64);
+
+    impl Rng {
+        fn below(&mut self, n: usize) -> usize {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 % n as u64) as usize
+        }
+        /// A char boundary of `s`.
+        fn boundary(&mut self, s: &str) -> usize {
+            let mut ix = self.below(s.len() + 1);
+            while !s.is_char_boundary(ix) {
+                ix -= 1
+            }
+            ix
+        }
+    }
+
+    fn edit(rng: &mut Rng, source: &str) -> Edit {
+        let start = rng.boundary(source);
+        let mut end = (start + rng.below(8)).min(source.len());
+        while !source.is_char_boundary(end) {
+            end += 1
+        }
+        let insertion = INSERTIONS[rng.below(INSERTIONS.len())].to_string();
+        let (span, replacement) = match rng.below(3) {
+            0 => (start..start, insertion),
+            1 => (start..end, String::new()),
+            _ => (start..end, insertion),
+        };
+        Edit {
+            span: Span(span),
+            replacement,
+        }
+    }
+
+    #[test]
+    fn examples() {
+        let source = "# a\n\nb *c*\n\n- d\n- e\n\n```\nf\n```\n";
+        for (span, replacement) in [
+            (7..8, "x"),
+            (5..5, "- "),
+            (12..12, "\n\n"),
+            (27..31, ""),
+            (0..0, "[c]: g\n\n"),
+        ] {
+            let edit = Edit {
+                span: Span(span),
+                replacement: replacement.into(),
+            };
+            check(source, edit, Options::empty())
+        }
+    }
+
+    #[test]
+    fn neighbours_with_moved_nested_spans() {
+        for (source, at, replacement) in [
+            ("H\n\n-\n\n<", 1, "("),
+            (">(\n-\n\n--", 1, ">"),
+            ("\tf\n-\n\n=", 2, "x]"),
+        ] {
+            let edit = Edit {
+                span: Span(at..at),
+                replacement: replacement.into(),
+            };
+            check(source, edit, Options::empty())
+        }
+    }
+
+    #[test]
+    fn metadata_block_closing_after_the_region() {
+        let source = "---    \na: b\nc: d\n---\n\n--- -\ne: f\ng: h\n---\n";
+        let edit = Edit {
+            span: Span(17..19),
+            replacement: String::new(),
+        };
+        let options =
+            Options::ENABLE_YAML_STYLE_METADATA_BLOCKS | Options::ENABLE_HEADING_ATTRIBUTES;
+        check(source, edit, options)
+    }
+
+    #[test]
+    fn random_edits_match_a_full_parse() {
+        const STEPS: usize = 40;
+        let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+        for case in corpus::cases() {
+            let mut source = case.markdown.clone();
+            let parse = |source: &str| {
+                let source = source.to_string();
+                panic::catch_unwind(move || Ast::new_ext(&source, case.options).into_static())
+            };
+            // some inputs panic in pulldown_cmark itself
+            let Ok(mut ast) = parse(&source) else {
+                continue;
+            };
+            for _ in 0..STEPS {
+                let edit = edit(&mut rng, &source);
+                let mut new_source = source.clone();
+                new_source.replace_range(edit.span.0.clone(), &edit.replacement);
+                let Ok(expected) = parse(&new_source) else {
+                    break;
+                };
+                ast.reparse_ext(&edit, &new_source, case.options);
+                assert_eq!(ast, expected, "{}: {source:?} {edit:?}", case.name);
+                source = new_source
+            }
+        }
+    }
+}
@@ -0,0 +1,256 @@
+//! Render [`Diagnostic`]s for people and CI.
+//!
+//! With the `ariadne` feature, diagnostics can be drawn against the source
+//! with [`Diagnostic::to_report`] and [`report`].
+
+use std::fmt::Write as _;
+
+use crate::{
+    front_matter::FrontMatterError,
+    line_index::LineIndex,
+    lint::{Diagnostic, Severity},
+    BrokenLink, Span,
+};
+
+impl From<&BrokenLink<'_>> for Diagnostic {
+    fn from(value: &BrokenLink<'_>) -> Self {
+        Diagnostic::new(
+            "broken-link",
+            value.span.clone(),
+            format!("no definition for `{}`", value.reference),
+        )
+    }
+}
+
+impl From<&FrontMatterError> for Diagnostic {
+    fn from(value: &FrontMatterError) -> Self {
+        Diagnostic {
+            severity: Severity::Error,
+            ..Diagnostic::new(
+                "front-matter",
+                value.span.clone().unwrap_or(Span(0..0)),
+                value.message.clone(),
+            )
+        }
+    }
+}
+
+/// One line per diagnostic, like `README.md:3:1: warning[single-h1]: ...`.
+pub fn plain(diagnostics: &[Diagnostic], path: &str, source: &str) -> String {
+    let lines = LineIndex::new(source);
+    let mut out = String::new();
+    for Diagnostic {
+        rule,
+        severity,
+        message,
+        span,
+        ..
+    } in diagnostics
+    {
+        let start = lines.line_col(span.0.start);
+        let _ = writeln!(
+            out,
+            "{path}:{}:{}: {severity}[{rule}]: {message}",
+            start.line, start.column
+        );
+    }
+    out
+}
+
+/// [GitHub Actions workflow commands] which annotate the diagnostics in pull requests.
+///
+/// [GitHub Actions workflow commands]: https://docs.github.com/en/actions/using-workflows/workflow-commands-for-github-actions
+pub fn github(diagnostics: &[Diagnostic], path: &str, source: &str) -> String {
+    let lines = LineIndex::new(source);
+    let escape_data = |s: &str| {
+        s.replace('%', "%25")
+            .replace('\r', "%0D")
+            .replace('\n', "%0A")
+    };
+    let escape_property = |s: &str| escape_data(s).replace(':', "%3A").replace(',', "%2C");
+    let mut out = String::new();
+    for Diagnostic {
+        rule,
+        severity,
+        message,
+        span,
+        ..
+    } in diagnostics
+    {
+        let command = match severity {
+            Severity::Info => "notice",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        let (start, end) = (lines.line_col(span.0.start), lines.line_col(span.0.end));
+        let _ = writeln!(
+            out,
+            "::{command} file={},line={},col={},endLine={},endColumn={},title={}::{}",
+            escape_property(path),
+            start.line,
+            start.column,
+            end.line,
+            end.column,
+            escape_property(rule),
+            escape_data(message),
+        );
+    }
+    out
+}
+
+#[cfg(feature = "ariadne")]
+pub use ariadne_impl::report;
+
+#[cfg(feature = "ariadne")]
+mod ariadne_impl {
+    use std::ops::Range;
+
+    use ariadne::{Color, Label, Report, ReportKind};
+
+    use super::*;
+
+    /// [`ariadne`] counts chars, not bytes.
+    fn chars(source: &str, Span(range): &Span) -> Range<usize> {
+        let count = |offset: usize| {
+            let mut offset = offset.min(source.len());
+            while !source.is_char_boundary(offset) {
+                offset -= 1
+            }
+            source[..offset].chars().count()
+        };
+        count(range.start)..count(range.end)
+    }
+
+    fn kind(severity: Severity) -> ReportKind<'static> {
+        match severity {
+            Severity::Info => ReportKind::Advice,
+            Severity::Warning => ReportKind::Warning,
+            Severity::Error => ReportKind::Error,
+        }
+    }
+
+    fn color(severity: Severity) -> Color {
+        match severity {
+            Severity::Info => Color::Blue,
+            Severity::Warning => Color::Yellow,
+            Severity::Error => Color::Red,
+        }
+    }
+
+    impl Diagnostic {
+        /// A report labeling this diagnostic in `source`, with any fix as help.
+        ///
+        /// Print it with `(id, ariadne::Source::from(source))`.
+        pub fn to_report(&self, id: &str, source: &str) -> Report<'static, (String, Range<usize>)> {
+            let span = chars(source, &self.span);
+            let mut report = Report::build(kind(self.severity), id.to_owned(), span.start)
+                .with_code(self.rule)
+                .with_message(&self.message)
+                .with_label(
+                    Label::new((id.to_owned(), span))
+                        .with_message(&self.message)
+                        .with_color(color(self.severity)),
+                );
+            if let Some(fix) = &self.fix {
+                report = report.with_help(match fix.replacement.is_empty() {
+                    true => match source.get(fix.span.0.clone()) {
+                        Some(removed) => format!("remove `{removed}`"),
+                        // not a span of `source`
+                        None => String::from("remove it"),
+                    },
+                    false => format!("replace with `{}`", fix.replacement),
+                })
+            }
+            report.finish()
+        }
+    }
+
+    /// A single report with a label for each of `diagnostics`,
+    /// as severe as the most severe of them.
+    ///
+    /// Returns [`None`] if there are no diagnostics.
+    pub fn report(
+        diagnostics: &[Diagnostic],
+        id: &str,
+        source: &str,
+    ) -> Option<Report<'static, (String, Range<usize>)>> {
+        let severity = diagnostics.iter().map(|it| it.severity).max()?;
+        let offset = diagnostics
+            .iter()
+            .map(|it| chars(source, &it.span).start)
+            .min()
+            .unwrap_or_default();
+        let message = match diagnostics.len() {
+            1 => String::from("1 problem"),
+            n => format!("{n} problems"),
+        };
+        Some(
+            Report::build(kind(severity), id.to_owned(), offset)
+                .with_message(message)
+                .with_labels(diagnostics.iter().map(|it| {
+                    Label::new((id.to_owned(), chars(source, &it.span)))
+                        .with_message(format!("{}: {}", it.rule, it.message))
+                        .with_color(color(it.severity))
+                }))
+                .finish(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diagnostics(source: &str) -> Vec<Diagnostic> {
+        let span = |text: &str| {
+            let start = source.find(text).unwrap();
+            Span(start..start + text.len())
+        };
+        vec![
+            Diagnostic::new("single-h1", span("# B"), "multiple top-level headings"),
+            Diagnostic {
+                severity: Severity::Error,
+                ..Diagnostic::new("a,b:c", span("x"), "100%\nsure")
+            },
+        ]
+    }
+
+    #[test]
+    fn plain_and_github() {
+        let source = "# Ä\n\n# B\n\nü x\n";
+        let diagnostics = diagnostics(source);
+        assert_eq!(
+            plain(&diagnostics, "README.md", source),
+            "README.md:3:1: warning[single-h1]: multiple top-level headings\n\
+             README.md:5:3: error[a,b:c]: 100%\nsure\n"
+        );
+        assert_eq!(
+            github(&diagnostics, "a:b.md", source),
+            "::warning file=a%3Ab.md,line=3,col=1,endLine=3,endColumn=4,\
+             title=single-h1::multiple top-level headings\n\
+             ::error file=a%3Ab.md,line=5,col=3,endLine=5,endColumn=4,\
+             title=a%2Cb%3Ac::100%25%0Asure\n"
+        );
+    }
+
+    #[cfg(feature = "ariadne")]
+    #[test]
+    fn fixes_outside_the_source() {
+        let source = "ü x\n";
+        for span in [0..1, 3..100, 100..200] {
+            let diagnostic =
+                Diagnostic::new("rule", Span(span.clone()), "message").with_fix(crate::Edit {
+                    span: Span(span),
+                    replacement: String::new(),
+                });
+            let mut out = vec![];
+            diagnostic
+                .to_report("a.md", source)
+                .write(("a.md".into(), ariadne::Source::from(source)), &mut out)
+                .unwrap();
+            assert!(String::from_utf8(out).unwrap().contains("remove"));
+        }
+        assert!(report(&diagnostics("# B x\n"), "a.md", "# B x\n").is_some());
+        assert!(report(&[], "a.md", "").is_none());
+    }
+}
@@ -0,0 +1,846 @@
+//! The representation documented on [`Tree`].
+
+use std::fmt;
+
+use pulldown_cmark::{
+    Alignment, BlockQuoteKind, CodeBlockKind, CowStr, HeadingLevel, LinkType, MetadataBlockKind,
+    Tag,
+};
+use serde::{
+    de::{self, Visitor},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+
+use crate::{Ast, BrokenLink, Group, Span, Spanned, Tree};
+
+impl Serialize for Span {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (self.0.start, self.0.end).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Span {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let (start, end) = <(usize, usize)>::deserialize(deserializer)?;
+        Ok(Span(start..end))
+    }
+}
+
+impl Serialize for Ast<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.0.iter().map(Node::from))
+    }
+}
+
+impl<'de: 'a, 'a> Deserialize<'de> for Ast<'a> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Node::into_ast(Vec::deserialize(deserializer)?).map_err(de::Error::custom)
+    }
+}
+
+impl Ast<'static> {
+    /// Deserialize a tree which doesn't borrow from the input,
+    /// which [`Ast`]'s [`Deserialize`] can't do, since it borrows where possible.
+    ///
+    /// Use it with `#[serde(deserialize_with = "Ast::deserialize_owned")]`
+    /// to make a type holding an `Ast<'static>` [`DeserializeOwned`](serde::de::DeserializeOwned),
+    /// for `serde_json::from_reader` and the like.
+    pub fn deserialize_owned<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(Ast::deserialize(deserializer)?.into_static())
+    }
+}
+
+impl Serialize for Tree<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        Node::from(self).serialize(serializer)
+    }
+}
+
+impl<'de: 'a, 'a> Deserialize<'de> for Tree<'a> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Node::deserialize(deserializer)?
+            .try_into()
+            .map_err(de::Error::custom)
+    }
+}
+
+impl Serialize for Group<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        Node::group(self).serialize(serializer)
+    }
+}
+
+impl<'de: 'a, 'a> Deserialize<'de> for Group<'a> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match Tree::deserialize(deserializer)? {
+            Tree::Group(it) => Ok(it),
+            other => Err(de::Error::custom(format_args!(
+                "expected a group, not {}",
+                other.kind()
+            ))),
+        }
+    }
+}
+
+/// Borrows from the input where possible, unlike [`CowStr`]'s own implementation.
+struct Str<'a>(CowStr<'a>);
+
+impl Serialize for Str<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+impl<'de: 'a, 'a> Deserialize<'de> for Str<'a> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct StrVisitor;
+        impl<'de> Visitor<'de> for StrVisitor {
+            type Value = Str<'de>;
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a string")
+            }
+            fn visit_borrowed_str<E: de::Error>(self, v: &'de str) -> Result<Self::Value, E> {
+                Ok(Str(CowStr::Borrowed(v)))
+            }
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+                Ok(Str(v.to_owned().into()))
+            }
+            fn visit_string<E: de::Error>(self, v: String) -> Result<Self::Value, E> {
+                Ok(Str(v.into()))
+            }
+        }
+        deserializer.deserialize_str(StrVisitor)
+    }
+}
+
+fn str<'a>(s: &'a CowStr<'_>) -> Str<'a> {
+    Str(CowStr::Borrowed(s))
+}
+
+/// A kebab-case mirror of a fieldless enum from [`pulldown_cmark`].
+macro_rules! mirror {
+    ($ours:ident: $theirs:ident { $($variant:ident => $their_variant:ident),* $(,)? }) => {
+        #[derive(Serialize, Deserialize, Clone, Copy)]
+        #[serde(rename_all = "kebab-case")]
+        enum $ours {
+            $($variant),*
+        }
+        impl From<$theirs> for $ours {
+            fn from(it: $theirs) -> Self {
+                match it {
+                    $($theirs::$their_variant => Self::$variant),*
+                }
+            }
+        }
+        impl From<$ours> for $theirs {
+            fn from(it: $ours) -> Self {
+                match it {
+                    $($ours::$variant => Self::$their_variant),*
+                }
+            }
+        }
+    };
+}
+
+mirror!(Quote: BlockQuoteKind {
+    Note => Note,
+    Tip => Tip,
+    Important => Important,
+    Warning => Warning,
+    Caution => Caution,
+});
+
+mirror!(Align: Alignment {
+    None => None,
+    Left => Left,
+    Center => Center,
+    Right => Right,
+});
+
+mirror!(Link: LinkType {
+    Inline => Inline,
+    Reference => Reference,
+    ReferenceUnknown => ReferenceUnknown,
+    Collapsed => Collapsed,
+    CollapsedUnknown => CollapsedUnknown,
+    Shortcut => Shortcut,
+    ShortcutUnknown => ShortcutUnknown,
+    Autolink => Autolink,
+    Email => Email,
+});
+
+mirror!(Metadata: MetadataBlockKind {
+    Yaml => YamlStyle,
+    Toml => PlusesStyle,
+});
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+enum Node<'a> {
+    Paragraph {
+        span: Span,
+        end_span: Span,
+        #[serde(borrow)]
+        children: Vec<Node<'a>>,
+    },
+    Heading {
+        level: usize,
+        #[serde(borrow)]
+        id: Option<Str<'a>>,
+        #[serde(borrow)]
+        classes: Vec<Str<'a>>,
+        #[serde(borrow)]
+        attrs: Vec<(Str<'a>, Option<Str<'a>>)>,
+        span: Span,
+        end_span: Span,
+        #[serde(borrow)]
+        children: Vec<Node<'a>>,
+    },
+    BlockQuote {
+        kind: Option<Quote>,
+        span: Span,
+        end_span: Span,
+        #[serde(borrow)]
+        children: Vec<Node<'a>>,
+    },
+    CodeBlock {
+        #[serde(borrow)]
+        info: Option<Str<'a>>,
+        span: Span,
+        end_span: Span,
+        #[serde(borrow)]
+        children: Vec<Node<'a>>,
+    },
+    HtmlBlock {
+        span: Span,
+        end_span: Span,
+        #[serde(borrow)]
+        children: Vec<Node<'a>>,
+    },
+    List {
+        start: Option<u64>,
+        span: Span,
+        end_span: Span,
+        #[serde(borrow)]
+        children: Vec<Node<'a>>,
+    },
+    Item {
+        span: Span,
+        end_span: Span,
+        #[serde(borrow)]
+        children: Vec<Node<'a>>,
+    },
+    FootnoteDefinition {
+        #[serde(borrow)]
+        label: Str<'a>,
+        span: Span,
+        end_span: Span,
+        #[serde(borrow)]
+        children: Vec<Node<'a>>,
+    },
+    Table {
+        alignments: Vec<Align>,
+        span: Span,
+        end_span: Span,
+        #[serde(borrow)]
+        children: Vec<Node<'a>>,
+    },
+    TableHead {
+        span: Span,
+        end_span: Span,
+        #[serde(borrow)]
+        children: Vec<Node<'a>>,
+    },
+    TableRow {
+        span: Span,
+        end_span: Span,
+        #[serde(borrow)]
+        children: Vec<Node<'a>>,
+    },
+    TableCell {
+        span: Span,
+        end_span: Span,
+        #[serde(borrow)]
+        children: Vec<Node<'a>>,
+    },
+    Emphasis {
+        span: Span,
+        end_span: Span,
+        #[serde(borrow)]
+        children: Vec<Node<'a>>,
+    },
+    Strong {
+        span: Span,
+        end_span: Span,
+        #[serde(borrow)]
+        children: Vec<Node<'a>>,
+    },
+    Strikethrough {
+        span: Span,
+        end_span: Span,
+        #[serde(borrow)]
+        children: Vec<Node<'a>>,
+    },
+    Link {
+        link_type: Link,
+        #[serde(borrow)]
+        dest_url: Str<'a>,
+        #[serde(borrow)]
+        title: Str<'a>,
+        #[serde(borrow)]
+        id: Str<'a>,
+        span: Span,
+        end_span: Span,
+        #[serde(borrow)]
+        children: Vec<Node<'a>>,
+    },
+    Image {
+        link_type: Link,
+        #[serde(borrow)]
+        dest_url: Str<'a>,
+        #[serde(borrow)]
+        title: Str<'a>,
+        #[serde(borrow)]
+        id: Str<'a>,
+        span: Span,
+        end_span: Span,
+        #[serde(borrow)]
+        children: Vec<Node<'a>>,
+    },
+    MetadataBlock {
+        kind: Metadata,
+        span: Span,
+        end_span: Span,
+        #[serde(borrow)]
+        children: Vec<Node<'a>>,
+    },
+    Text {
+        #[serde(borrow)]
+        value: Str<'a>,
+        span: Span,
+    },
+    Code {
+        #[serde(borrow)]
+        value: Str<'a>,
+        span: Span,
+    },
+    Html {
+        #[serde(borrow)]
+        value: Str<'a>,
+        span: Span,
+    },
+    InlineHtml {
+        #[serde(borrow)]
+        value: Str<'a>,
+        span: Span,
+    },
+    FootnoteReference {
+        #[serde(borrow)]
+        label: Str<'a>,
+        span: Span,
+    },
+    SoftBreak {
+        span: Span,
+    },
+    HardBreak {
+        span: Span,
+    },
+    Rule {
+        span: Span,
+    },
+    TaskListMarker {
+        checked: bool,
+        span: Span,
+    },
+    InlineMath {
+        #[serde(borrow)]
+        value: Str<'a>,
+        span: Span,
+    },
+    DisplayMath {
+        #[serde(borrow)]
+        value: Str<'a>,
+        span: Span,
+    },
+    BrokenLink {
+        #[serde(borrow)]
+        reference: Str<'a>,
+        link_type: Link,
+        span: Span,
+        #[serde(borrow)]
+        children: Vec<Node<'a>>,
+    },
+}
+
+impl<'a> From<&'a Tree<'_>> for Node<'a> {
+    fn from(tree: &'a Tree<'_>) -> Self {
+        let value = |it: &'a Spanned<CowStr<'_>>| (str(&it.item), it.span.clone());
+        match tree {
+            Tree::Group(group) => Node::group(group),
+            Tree::Text(it) => {
+                let (value, span) = value(it);
+                Node::Text { value, span }
+            }
+            Tree::Code(it) => {
+                let (value, span) = value(it);
+                Node::Code { value, span }
+            }
+            Tree::Html(it) => {
+                let (value, span) = value(it);
+                Node::Html { value, span }
+            }
+            Tree::InlineHtml(it) => {
+                let (value, span) = value(it);
+                Node::InlineHtml { value, span }
+            }
+            Tree::FootnoteReference(it) => {
+                let (label, span) = value(it);
+                Node::FootnoteReference { label, span }
+            }
+            Tree::SoftBreak(span) => Node::SoftBreak { span: span.clone() },
+            Tree::HardBreak(span) => Node::HardBreak { span: span.clone() },
+            Tree::Rule(span) => Node::Rule { span: span.clone() },
+            Tree::TaskListMarker(Spanned { item, span }) => Node::TaskListMarker {
+                checked: *item,
+                span: span.clone(),
+            },
+            Tree::InlineMath(it) => {
+                let (value, span) = value(it);
+                Node::InlineMath { value, span }
+            }
+            Tree::DisplayMath(it) => {
+                let (value, span) = value(it);
+                Node::DisplayMath { value, span }
+            }
+            Tree::BrokenLink(
+                BrokenLink {
+                    reference,
+                    link_type,
+                    span,
+                },
+                stream,
+            ) => Node::BrokenLink {
+                reference: str(reference),
+                link_type: (*link_type).into(),
+                span: span.clone(),
+                children: stream.0.iter().map(Node::from).collect(),
+            },
+        }
+    }
+}
+
+impl<'a> Node<'a> {
+    fn group(group: &'a Group<'_>) -> Self {
+        let Group {
+            tag: Spanned { item, span },
+            stream,
+            end_span,
+        } = group;
+        let span = span.clone();
+        let end_span = end_span.clone();
+        let children = stream.0.iter().map(Node::from).collect();
+        match item {
+            Tag::Paragraph => Node::Paragraph {
+                span,
+                end_span,
+                children,
+            },
+            Tag::Heading {
+                level,
+                id,
+                classes,
+                attrs,
+            } => Node::Heading {
+                level: *level as usize,
+                id: id.as_ref().map(str),
+                classes: classes.iter().map(str).collect(),
+                attrs: attrs
+                    .iter()
+                    .map(|(k, v)| (str(k), v.as_ref().map(str)))
+                    .collect(),
+                span,
+                end_span,
+                children,
+            },
+            Tag::BlockQuote(kind) => Node::BlockQuote {
+                kind: kind.map(Into::into),
+                span,
+                end_span,
+                children,
+            },
+            Tag::CodeBlock(kind) => Node::CodeBlock {
+                info: match kind {
+                    CodeBlockKind::Indented => None,
+                    CodeBlockKind::Fenced(info) => Some(str(info)),
+                },
+                span,
+                end_span,
+                children,
+            },
+            Tag::HtmlBlock => Node::HtmlBlock {
+                span,
+                end_span,
+                children,
+            },
+            Tag::List(start) => Node::List {
+                start: *start,
+                span,
+                end_span,
+                children,
+            },
+            Tag::Item => Node::Item {
+                span,
+                end_span,
+                children,
+            },
+            Tag::FootnoteDefinition(label) => Node::FootnoteDefinition {
+                label: str(label),
+                span,
+                end_span,
+                children,
+            },
+            Tag::Table(alignments) => Node::Table {
+                alignments: alignments.iter().map(|it| (*it).into()).collect(),
+                span,
+                end_span,
+                children,
+            },
+            Tag::TableHead => Node::TableHead {
+                span,
+                end_span,
+                children,
+            },
+            Tag::TableRow => Node::TableRow {
+                span,
+                end_span,
+                children,
+            },
+            Tag::TableCell => Node::TableCell {
+                span,
+                end_span,
+                children,
+            },
+            Tag::Emphasis => Node::Emphasis {
+                span,
+                end_span,
+                children,
+            },
+            Tag::Strong => Node::Strong {
+                span,
+                end_span,
+                children,
+            },
+            Tag::Strikethrough => Node::Strikethrough {
+                span,
+                end_span,
+                children,
+            },
+            Tag::Link {
+                link_type,
+                dest_url,
+                title,
+                id,
+            } => Node::Link {
+                link_type: (*link_type).into(),
+                dest_url: str(dest_url),
+                title: str(title),
+                id: str(id),
+                span,
+                end_span,
+                children,
+            },
+            Tag::Image {
+                link_type,
+                dest_url,
+                title,
+                id,
+            } => Node::Image {
+                link_type: (*link_type).into(),
+                dest_url: str(dest_url),
+                title: str(title),
+                id: str(id),
+                span,
+                end_span,
+                children,
+            },
+            Tag::MetadataBlock(kind) => Node::MetadataBlock {
+                kind: (*kind).into(),
+                span,
+                end_span,
+                children,
+            },
+        }
+    }
+    fn into_ast(nodes: Vec<Self>) -> Result<Ast<'a>, InvalidHeadingLevel> {
+        nodes
+            .into_iter()
+            .map(Tree::try_from)
+            .collect::<Result<_, _>>()
+            .map(Ast)
+    }
+}
+
+struct InvalidHeadingLevel(usize);
+
+impl fmt::Display for InvalidHeadingLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid heading level {}, expected 1 to 6", self.0)
+    }
+}
+
+impl<'a> TryFrom<Node<'a>> for Tree<'a> {
+    type Error = InvalidHeadingLevel;
+    fn try_from(node: Node<'a>) -> Result<Self, Self::Error> {
+        let group = |item, span, end_span, children| -> Result<_, InvalidHeadingLevel> {
+            Ok(Tree::Group(Group {
+                tag: Spanned { item, span },
+                stream: Node::into_ast(children)?,
+                end_span,
+            }))
+        };
+        let spanned = |Str(item), span| Spanned { item, span };
+        match node {
+            Node::Paragraph {
+                span,
+                end_span,
+                children,
+            } => group(Tag::Paragraph, span, end_span, children),
+            Node::Heading {
+                level,
+                id,
+                classes,
+                attrs,
+                span,
+                end_span,
+                children,
+            } => group(
+                Tag::Heading {
+                    level: HeadingLevel::try_from(level).map_err(|_| InvalidHeadingLevel(level))?,
+                    id: id.map(|it| it.0),
+                    classes: classes.into_iter().map(|it| it.0).collect(),
+                    attrs: attrs
+                        .into_iter()
+                        .map(|(k, v)| (k.0, v.map(|it| it.0)))
+                        .collect(),
+                },
+                span,
+                end_span,
+                children,
+            ),
+            Node::BlockQuote {
+                kind,
+                span,
+                end_span,
+                children,
+            } => group(
+                Tag::BlockQuote(kind.map(Into::into)),
+                span,
+                end_span,
+                children,
+            ),
+            Node::CodeBlock {
+                info,
+                span,
+                end_span,
+                children,
+            } => group(
+                Tag::CodeBlock(match info {
+                    Some(Str(info)) => CodeBlockKind::Fenced(info),
+                    None => CodeBlockKind::Indented,
+                }),
+                span,
+                end_span,
+                children,
+            ),
+            Node::HtmlBlock {
+                span,
+                end_span,
+                children,
+            } => group(Tag::HtmlBlock, span, end_span, children),
+            Node::List {
+                start,
+                span,
+                end_span,
+                children,
+            } => group(Tag::List(start), span, end_span, children),
+            Node::Item {
+                span,
+                end_span,
+                children,
+            } => group(Tag::Item, span, end_span, children),
+            Node::FootnoteDefinition {
+                label,
+                span,
+                end_span,
+                children,
+            } => group(Tag::FootnoteDefinition(label.0), span, end_span, children),
+            Node::Table {
+                alignments,
+                span,
+                end_span,
+                children,
+            } => group(
+                Tag::Table(alignments.into_iter().map(Into::into).collect()),
+                span,
+                end_span,
+                children,
+            ),
+            Node::TableHead {
+                span,
+                end_span,
+                children,
+            } => group(Tag::TableHead, span, end_span, children),
+            Node::TableRow {
+                span,
+                end_span,
+                children,
+            } => group(Tag::TableRow, span, end_span, children),
+            Node::TableCell {
+                span,
+                end_span,
+                children,
+            } => group(Tag::TableCell, span, end_span, children),
+            Node::Emphasis {
+                span,
+                end_span,
+                children,
+            } => group(Tag::Emphasis, span, end_span, children),
+            Node::Strong {
+                span,
+                end_span,
+                children,
+            } => group(Tag::Strong, span, end_span, children),
+            Node::Strikethrough {
+                span,
+                end_span,
+                children,
+            } => group(Tag::Strikethrough, span, end_span, children),
+            Node::Link {
+                link_type,
+                dest_url,
+                title,
+                id,
+                span,
+                end_span,
+                children,
+            } => group(
+                Tag::Link {
+                    link_type: link_type.into(),
+                    dest_url: dest_url.0,
+                    title: title.0,
+                    id: id.0,
+                },
+                span,
+                end_span,
+                children,
+            ),
+            Node::Image {
+                link_type,
+                dest_url,
+                title,
+                id,
+                span,
+                end_span,
+                children,
+            } => group(
+                Tag::Image {
+                    link_type: link_type.into(),
+                    dest_url: dest_url.0,
+                    title: title.0,
+                    id: id.0,
+                },
+                span,
+                end_span,
+                children,
+            ),
+            Node::MetadataBlock {
+                kind,
+                span,
+                end_span,
+                children,
+            } => group(Tag::MetadataBlock(kind.into()), span, end_span, children),
+            Node::Text { value, span } => Ok(Tree::Text(spanned(value, span))),
+            Node::Code { value, span } => Ok(Tree::Code(spanned(value, span))),
+            Node::Html { value, span } => Ok(Tree::Html(spanned(value, span))),
+            Node::InlineHtml { value, span } => Ok(Tree::InlineHtml(spanned(value, span))),
+            Node::FootnoteReference { label, span } => {
+                Ok(Tree::FootnoteReference(spanned(label, span)))
+            }
+            Node::SoftBreak { span } => Ok(Tree::SoftBreak(span)),
+            Node::HardBreak { span } => Ok(Tree::HardBreak(span)),
+            Node::Rule { span } => Ok(Tree::Rule(span)),
+            Node::TaskListMarker { checked, span } => Ok(Tree::TaskListMarker(Spanned {
+                item: checked,
+                span,
+            })),
+            Node::InlineMath { value, span } => Ok(Tree::InlineMath(spanned(value, span))),
+            Node::DisplayMath { value, span } => Ok(Tree::DisplayMath(spanned(value, span))),
+            Node::BrokenLink {
+                reference,
+                link_type,
+                span,
+                children,
+            } => Ok(Tree::BrokenLink(
+                BrokenLink {
+                    reference: reference.0,
+                    link_type: link_type.into(),
+                    span,
+                },
+                Node::into_ast(children)?,
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pulldown_cmark::Options;
+
+    use super::*;
+
+    macro_rules! fixtures {
+        ($($name:literal),* $(,)?) => {
+            [$((
+                $name,
+                include_str!(concat!("../tests/fixtures/serde/", $name, ".md")),
+                include_str!(concat!("../tests/fixtures/serde/", $name, ".json")),
+            )),*]
+        };
+    }
+
+    #[test]
+    fn golden() {
+        let options =
+            (Options::all() - Options::ENABLE_OLD_FOOTNOTES - Options::ENABLE_SMART_PUNCTUATION)
+                | Options::ENABLE_FOOTNOTES;
+        for (name, markdown, json) in fixtures!("blocks", "inlines", "toml", "broken-link") {
+            let (mut ast, broken) = Ast::new_with_broken_links(markdown, options);
+            ast.mark_broken_links(broken);
+            assert_eq!(
+                serde_json::to_string_pretty(&ast).unwrap(),
+                json.trim_end(),
+                "{name}"
+            );
+            assert_eq!(serde_json::from_str::<Ast>(json).unwrap(), ast, "{name}");
+        }
+    }
+
+    #[test]
+    fn owned() {
+        #[derive(Deserialize)]
+        struct Cached {
+            #[serde(deserialize_with = "Ast::deserialize_owned")]
+            ast: Ast<'static>,
+        }
+        fn load<T: de::DeserializeOwned>(json: &[u8]) -> T {
+            serde_json::from_reader(json).unwrap()
+        }
+        let ast = Ast::new("# a\n\n*b* `c`\n");
+        let json = format!("{{\"ast\": {}}}", serde_json::to_string(&ast).unwrap());
+        let cached = load::<Cached>(json.as_bytes());
+        assert_eq!(cached.ast, ast);
+        let json = serde_json::to_vec(&ast).unwrap();
+        let mut deserializer = serde_json::Deserializer::from_reader(&*json);
+        assert_eq!(Ast::deserialize_owned(&mut deserializer).unwrap(), ast);
+    }
+}
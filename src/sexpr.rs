@@ -0,0 +1,804 @@
+//! A compact S-expression notation for [`Ast`]s, for debugging and test fixtures.
+//!
+//! ```text
+//! (heading :level 2 (text "Intro"))
+//! (list :start 1
+//!   (item (text "one"))
+//!   (item (text "two") (soft-break) (emphasis (text "three"))))
+//! ```
+//!
+//! Each node is a list of its [`Tree::kind`], any attributes as `:key value`
+//! pairs, and then its contents:
+//! - `heading`: `:level`, `:id`, `:classes ("a" "b")` and `:attrs (("key" "value") ("key" nil))`
+//! - `block-quote`: `:kind`, e.g `note`
+//! - `code-block`: `:info`, which is absent for indented code blocks
+//! - `list`: `:start`, which is absent for unordered lists
+//! - `footnote-definition`: `:label`
+//! - `table`: `:align (none left center right)`
+//! - `link` and `image`: `:url`, `:title`, `:id`, and `:type` if it isn't `inline`
+//! - `metadata-block`: `:kind`, either `yaml` or `toml`
+//! - `broken-link`: `:reference` and `:type`
+//! - `text`, `code`, `html`, `inline-html`, `inline-math` and `display-math`
+//!   contain a string, `footnote-reference` contains its label,
+//!   and `task-list-marker` contains `true` or `false`.
+//!
+//! Spans are written as `@start..end` after the kind, followed by the end span
+//! of groups if it is different.
+//! Strings are double-quoted, with `\"`, `\\`, `\n`, `\r` and `\t` escapes,
+//! and `;` starts a comment.
+
+use std::fmt::{self, Write as _};
+
+use pulldown_cmark::{
+    Alignment, BlockQuoteKind, CodeBlockKind, CowStr, HeadingLevel, LinkType, MetadataBlockKind,
+    Tag,
+};
+
+use crate::{query::link_type, Ast, BrokenLink, Group, Span, Spanned, Tree};
+
+impl Ast<'_> {
+    /// One line per top-level node, with blocks indented on their own lines.
+    pub fn to_sexpr(&self) -> String {
+        self.write_sexpr(false)
+    }
+    /// Like [`Ast::to_sexpr`], but including [`Span`]s.
+    pub fn to_sexpr_with_spans(&self) -> String {
+        self.write_sexpr(true)
+    }
+    fn write_sexpr(&self, spans: bool) -> String {
+        let mut buf = String::new();
+        for (ix, tree) in self.0.iter().enumerate() {
+            if ix > 0 {
+                buf.push('\n')
+            }
+            write_tree(&mut buf, tree, spans, 0)
+        }
+        buf
+    }
+}
+
+impl Ast<'static> {
+    /// Parse the notation written by [`Ast::to_sexpr`].
+    ///
+    /// Missing spans are empty.
+    pub fn from_sexpr(text: &str) -> Result<Self, ParseError> {
+        let mut parser = Parser { text, pos: 0 };
+        let mut trees = vec![];
+        while parser.skip_whitespace() {
+            trees.push(parser.tree()?)
+        }
+        Ok(Ast(trees))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    /// Byte offset into the text.
+    pub offset: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at offset {}", self.message, self.offset)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+fn write_str(buf: &mut String, s: &str) {
+    buf.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => buf.push_str("\\\""),
+            '\\' => buf.push_str("\\\\"),
+            '\n' => buf.push_str("\\n"),
+            '\r' => buf.push_str("\\r"),
+            '\t' => buf.push_str("\\t"),
+            c => buf.push(c),
+        }
+    }
+    buf.push('"')
+}
+
+fn write_span(buf: &mut String, span: &Span) {
+    let _ = write!(buf, " @{}..{}", span.0.start, span.0.end);
+}
+
+fn block_quote_kind(kind: BlockQuoteKind) -> &'static str {
+    match kind {
+        BlockQuoteKind::Note => "note",
+        BlockQuoteKind::Tip => "tip",
+        BlockQuoteKind::Important => "important",
+        BlockQuoteKind::Warning => "warning",
+        BlockQuoteKind::Caution => "caution",
+    }
+}
+
+fn alignment(it: Alignment) -> &'static str {
+    match it {
+        Alignment::None => "none",
+        Alignment::Left => "left",
+        Alignment::Center => "center",
+        Alignment::Right => "right",
+    }
+}
+
+fn write_tree(buf: &mut String, tree: &Tree<'_>, spans: bool, depth: usize) {
+    buf.push('(');
+    buf.push_str(tree.kind());
+    let value = |buf: &mut String, Spanned { item, span }: &Spanned<CowStr<'_>>| {
+        if spans {
+            write_span(buf, span)
+        }
+        buf.push(' ');
+        write_str(buf, item)
+    };
+    let stream = match tree {
+        Tree::Group(Group {
+            tag: Spanned { item, span },
+            stream,
+            end_span,
+        }) => {
+            if spans {
+                write_span(buf, span);
+                if end_span != span {
+                    write_span(buf, end_span)
+                }
+            }
+            write_tag(buf, item);
+            stream
+        }
+        Tree::Text(it)
+        | Tree::Code(it)
+        | Tree::Html(it)
+        | Tree::InlineHtml(it)
+        | Tree::FootnoteReference(it)
+        | Tree::InlineMath(it)
+        | Tree::DisplayMath(it) => {
+            value(buf, it);
+            buf.push(')');
+            return;
+        }
+        Tree::SoftBreak(span) | Tree::HardBreak(span) | Tree::Rule(span) => {
+            if spans {
+                write_span(buf, span)
+            }
+            buf.push(')');
+            return;
+        }
+        Tree::TaskListMarker(Spanned { item, span }) => {
+            if spans {
+                write_span(buf, span)
+            }
+            let _ = write!(buf, " {item})");
+            return;
+        }
+        Tree::BrokenLink(
+            BrokenLink {
+                reference,
+                link_type: ty,
+                span,
+            },
+            stream,
+        ) => {
+            if spans {
+                write_span(buf, span)
+            }
+            write_attr(buf, "reference", reference);
+            let _ = write!(buf, " :type {}", link_type(*ty));
+            stream
+        }
+    };
+    let blocks = stream.0.iter().any(Tree::is_block);
+    for child in &stream.0 {
+        match blocks {
+            true => {
+                buf.push('\n');
+                buf.push_str(&"  ".repeat(depth + 1))
+            }
+            false => buf.push(' '),
+        }
+        write_tree(buf, child, spans, depth + 1)
+    }
+    buf.push(')')
+}
+
+fn write_attr(buf: &mut String, key: &str, value: &str) {
+    let _ = write!(buf, " :{key} ");
+    write_str(buf, value)
+}
+
+fn write_tag(buf: &mut String, tag: &Tag<'_>) {
+    match tag {
+        Tag::Heading {
+            level,
+            id,
+            classes,
+            attrs,
+        } => {
+            let _ = write!(buf, " :level {}", *level as usize);
+            if let Some(id) = id {
+                write_attr(buf, "id", id)
+            }
+            if !classes.is_empty() {
+                buf.push_str(" :classes (");
+                for (ix, class) in classes.iter().enumerate() {
+                    if ix > 0 {
+                        buf.push(' ')
+                    }
+                    write_str(buf, class)
+                }
+                buf.push(')')
+            }
+            if !attrs.is_empty() {
+                buf.push_str(" :attrs (");
+                for (ix, (k, v)) in attrs.iter().enumerate() {
+                    if ix > 0 {
+                        buf.push(' ')
+                    }
+                    buf.push('(');
+                    write_str(buf, k);
+                    match v {
+                        Some(v) => {
+                            buf.push(' ');
+                            write_str(buf, v)
+                        }
+                        None => buf.push_str(" nil"),
+                    }
+                    buf.push(')')
+                }
+                buf.push(')')
+            }
+        }
+        Tag::BlockQuote(Some(kind)) => {
+            let _ = write!(buf, " :kind {}", block_quote_kind(*kind));
+        }
+        Tag::CodeBlock(CodeBlockKind::Fenced(info)) => write_attr(buf, "info", info),
+        Tag::List(Some(start)) => {
+            let _ = write!(buf, " :start {start}");
+        }
+        Tag::FootnoteDefinition(label) => write_attr(buf, "label", label),
+        Tag::Table(alignments) => {
+            buf.push_str(" :align (");
+            for (ix, it) in alignments.iter().enumerate() {
+                if ix > 0 {
+                    buf.push(' ')
+                }
+                buf.push_str(alignment(*it))
+            }
+            buf.push(')')
+        }
+        Tag::Link {
+            link_type: ty,
+            dest_url,
+            title,
+            id,
+        }
+        | Tag::Image {
+            link_type: ty,
+            dest_url,
+            title,
+            id,
+        } => {
+            if *ty != LinkType::Inline {
+                let _ = write!(buf, " :type {}", link_type(*ty));
+            }
+            write_attr(buf, "url", dest_url);
+            if !title.is_empty() {
+                write_attr(buf, "title", title)
+            }
+            if !id.is_empty() {
+                write_attr(buf, "id", id)
+            }
+        }
+        Tag::MetadataBlock(kind) => buf.push_str(match kind {
+            MetadataBlockKind::YamlStyle => " :kind yaml",
+            MetadataBlockKind::PlusesStyle => " :kind toml",
+        }),
+        Tag::Paragraph
+        | Tag::BlockQuote(None)
+        | Tag::CodeBlock(CodeBlockKind::Indented)
+        | Tag::HtmlBlock
+        | Tag::List(None)
+        | Tag::Item
+        | Tag::TableHead
+        | Tag::TableRow
+        | Tag::TableCell
+        | Tag::Emphasis
+        | Tag::Strong
+        | Tag::Strikethrough => {}
+    }
+}
+
+/// A parsed value, before it is interpreted as a node.
+enum Value {
+    List(Vec<(usize, Value)>),
+    Atom(String),
+    Str(String),
+    Span(Span),
+}
+
+struct Parser<'t> {
+    text: &'t str,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn error<T>(&self, offset: usize, message: impl fmt::Display) -> Result<T, ParseError> {
+        Err(ParseError {
+            offset,
+            message: message.to_string(),
+        })
+    }
+    /// Skip whitespace and comments, returning whether there is more text.
+    fn skip_whitespace(&mut self) -> bool {
+        loop {
+            let rest = &self.text[self.pos..];
+            let trimmed = rest.trim_start();
+            self.pos += rest.len() - trimmed.len();
+            match trimmed.starts_with(';') {
+                true => self.pos += trimmed.find('\n').unwrap_or(trimmed.len()),
+                false => return !trimmed.is_empty(),
+            }
+        }
+    }
+    fn value(&mut self) -> Result<(usize, Value), ParseError> {
+        if !self.skip_whitespace() {
+            return self.error(self.pos, "unexpected end of input");
+        }
+        let start = self.pos;
+        let rest = &self.text[start..];
+        match rest.chars().next() {
+            Some('(') => {
+                self.pos += 1;
+                let mut items = vec![];
+                loop {
+                    if !self.skip_whitespace() {
+                        return self.error(start, "unclosed `(`");
+                    }
+                    if self.text[self.pos..].starts_with(')') {
+                        self.pos += 1;
+                        return Ok((start, Value::List(items)));
+                    }
+                    items.push(self.value()?)
+                }
+            }
+            Some(')') => self.error(start, "unexpected `)`"),
+            Some('"') => {
+                let mut s = String::new();
+                let mut chars = rest.char_indices().skip(1);
+                while let Some((ix, c)) = chars.next() {
+                    match c {
+                        '"' => {
+                            self.pos += ix + 1;
+                            return Ok((start, Value::Str(s)));
+                        }
+                        '\\' => match chars.next() {
+                            Some((_, '"')) => s.push('"'),
+                            Some((_, '\\')) => s.push('\\'),
+                            Some((_, 'n')) => s.push('\n'),
+                            Some((_, 'r')) => s.push('\r'),
+                            Some((_, 't')) => s.push('\t'),
+                            Some((ix, c)) => {
+                                return self
+                                    .error(start + ix, format_args!("unknown escape `\\{c}`"))
+                            }
+                            None => break,
+                        },
+                        c => s.push(c),
+                    }
+                }
+                self.error(start, "unclosed string")
+            }
+            _ => {
+                let len = rest
+                    .find(|c: char| c.is_whitespace() || matches!(c, '(' | ')' | '"' | ';'))
+                    .unwrap_or(rest.len());
+                self.pos += len;
+                let atom = &rest[..len];
+                match atom.strip_prefix('@') {
+                    Some(span) => {
+                        let parsed = span
+                            .split_once("..")
+                            .and_then(|(s, e)| Some(Span(s.parse().ok()?..e.parse().ok()?)));
+                        match parsed {
+                            Some(span) => Ok((start, Value::Span(span))),
+                            None => self.error(start, format_args!("invalid span `{atom}`")),
+                        }
+                    }
+                    None => Ok((start, Value::Atom(atom.into()))),
+                }
+            }
+        }
+    }
+    fn tree(&mut self) -> Result<Tree<'static>, ParseError> {
+        let (offset, value) = self.value()?;
+        self.interpret(offset, value)
+    }
+    fn interpret(&self, offset: usize, value: Value) -> Result<Tree<'static>, ParseError> {
+        let Value::List(items) = value else {
+            return self.error(offset, "expected a node");
+        };
+        let mut items = items.into_iter().peekable();
+        let kind = match items.next() {
+            Some((_, Value::Atom(kind))) => kind,
+            _ => return self.error(offset, "expected a node kind"),
+        };
+        let mut node = Node {
+            parser: self,
+            offset,
+            spans: vec![],
+            attrs: vec![],
+            args: vec![],
+        };
+        while let Some((offset, value)) = items.next() {
+            match value {
+                Value::Span(span) => node.spans.push(span),
+                Value::Atom(key) if key.starts_with(':') => match items.next() {
+                    Some((_, value)) => node.attrs.push((offset, key[1..].into(), value)),
+                    None => return self.error(offset, format_args!("missing value for `{key}`")),
+                },
+                value => node.args.push((offset, value)),
+            }
+        }
+        node.finish(&kind)
+    }
+}
+
+struct Node<'p, 't> {
+    parser: &'p Parser<'t>,
+    offset: usize,
+    spans: Vec<Span>,
+    attrs: Vec<(usize, String, Value)>,
+    args: Vec<(usize, Value)>,
+}
+
+impl Node<'_, '_> {
+    fn span(&self) -> Span {
+        self.spans.first().cloned().unwrap_or(Span(0..0))
+    }
+    fn take(&mut self, key: &str) -> Option<(usize, Value)> {
+        let ix = self.attrs.iter().position(|(_, k, _)| k == key)?;
+        let (offset, _, value) = self.attrs.remove(ix);
+        Some((offset, value))
+    }
+    fn string(&mut self, key: &str) -> Result<Option<CowStr<'static>>, ParseError> {
+        match self.take(key) {
+            Some((_, Value::Str(s))) => Ok(Some(s.into())),
+            Some((offset, _)) => self
+                .parser
+                .error(offset, format_args!("expected a string for `:{key}`")),
+            None => Ok(None),
+        }
+    }
+    fn atom(&mut self, key: &str) -> Result<Option<(usize, String)>, ParseError> {
+        match self.take(key) {
+            Some((offset, Value::Atom(s))) => Ok(Some((offset, s))),
+            Some((offset, _)) => self
+                .parser
+                .error(offset, format_args!("expected a symbol for `:{key}`")),
+            None => Ok(None),
+        }
+    }
+    fn list(&mut self, key: &str) -> Result<Vec<(usize, Value)>, ParseError> {
+        match self.take(key) {
+            Some((_, Value::List(items))) => Ok(items),
+            Some((offset, _)) => self
+                .parser
+                .error(offset, format_args!("expected a list for `:{key}`")),
+            None => Ok(vec![]),
+        }
+    }
+    fn link_type(&mut self) -> Result<LinkType, ParseError> {
+        use LinkType::*;
+        match self.atom("type")? {
+            None => Ok(Inline),
+            Some((offset, ty)) => [
+                Inline,
+                Reference,
+                ReferenceUnknown,
+                Collapsed,
+                CollapsedUnknown,
+                Shortcut,
+                ShortcutUnknown,
+                Autolink,
+                Email,
+            ]
+            .into_iter()
+            .find(|it| link_type(*it) == ty)
+            .map_or_else(
+                || {
+                    self.parser
+                        .error(offset, format_args!("unknown link type `{ty}`"))
+                },
+                Ok,
+            ),
+        }
+    }
+    /// The single string argument of a leaf node.
+    fn value(&mut self) -> Result<Spanned<CowStr<'static>>, ParseError> {
+        match self.args.pop() {
+            Some((_, Value::Str(s))) if self.args.is_empty() => Ok(Spanned {
+                item: s.into(),
+                span: self.span(),
+            }),
+            _ => self.parser.error(self.offset, "expected a single string"),
+        }
+    }
+    fn stream(&mut self) -> Result<Ast<'static>, ParseError> {
+        std::mem::take(&mut self.args)
+            .into_iter()
+            .map(|(offset, value)| self.parser.interpret(offset, value))
+            .collect::<Result<_, _>>()
+            .map(Ast)
+    }
+    fn group(&mut self, tag: Tag<'static>) -> Result<Tree<'static>, ParseError> {
+        let span = self.span();
+        Ok(Tree::Group(Group {
+            tag: Spanned {
+                item: tag,
+                span: span.clone(),
+            },
+            stream: self.stream()?,
+            end_span: self.spans.get(1).cloned().unwrap_or(span),
+        }))
+    }
+    fn finish(mut self, kind: &str) -> Result<Tree<'static>, ParseError> {
+        let tree = match kind {
+            "paragraph" => self.group(Tag::Paragraph)?,
+            "heading" => {
+                let level = match self.atom("level")? {
+                    Some((offset, level)) => {
+                        match level.parse::<usize>().map(HeadingLevel::try_from) {
+                            Ok(Ok(level)) => level,
+                            _ => {
+                                return self
+                                    .parser
+                                    .error(offset, format_args!("invalid heading level `{level}`"))
+                            }
+                        }
+                    }
+                    None => HeadingLevel::H1,
+                };
+                let id = self.string("id")?;
+                let mut classes = vec![];
+                for (offset, class) in self.list("classes")? {
+                    match class {
+                        Value::Str(it) => classes.push(it.into()),
+                        _ => return self.parser.error(offset, "expected a string"),
+                    }
+                }
+                let mut attrs = vec![];
+                for (offset, attr) in self.list("attrs")? {
+                    let mut kv = match attr {
+                        Value::List(kv) => kv.into_iter().map(|(_, it)| it),
+                        _ => return self.parser.error(offset, "expected a list"),
+                    };
+                    match (kv.next(), kv.next(), kv.next()) {
+                        (Some(Value::Str(k)), Some(Value::Str(v)), None) => {
+                            attrs.push((k.into(), Some(v.into())))
+                        }
+                        (Some(Value::Str(k)), Some(Value::Atom(nil)), None) if nil == "nil" => {
+                            attrs.push((k.into(), None))
+                        }
+                        _ => {
+                            return self
+                                .parser
+                                .error(offset, "expected a key and a value, or nil")
+                        }
+                    }
+                }
+                self.group(Tag::Heading {
+                    level,
+                    id,
+                    classes,
+                    attrs,
+                })?
+            }
+            "block-quote" => {
+                let kind = match self.atom("kind")? {
+                    Some((offset, kind)) => Some(
+                        [
+                            BlockQuoteKind::Note,
+                            BlockQuoteKind::Tip,
+                            BlockQuoteKind::Important,
+                            BlockQuoteKind::Warning,
+                            BlockQuoteKind::Caution,
+                        ]
+                        .into_iter()
+                        .find(|it| block_quote_kind(*it) == kind)
+                        .map_or_else(
+                            || {
+                                self.parser.error(
+                                    offset,
+                                    format_args!("unknown block quote kind `{kind}`"),
+                                )
+                            },
+                            Ok,
+                        )?,
+                    ),
+                    None => None,
+                };
+                self.group(Tag::BlockQuote(kind))?
+            }
+            "code-block" => {
+                let kind = match self.string("info")? {
+                    Some(info) => CodeBlockKind::Fenced(info),
+                    None => CodeBlockKind::Indented,
+                };
+                self.group(Tag::CodeBlock(kind))?
+            }
+            "html-block" => self.group(Tag::HtmlBlock)?,
+            "list" => {
+                let start = match self.atom("start")? {
+                    Some((offset, start)) => match start.parse() {
+                        Ok(it) => Some(it),
+                        Err(_) => {
+                            return self
+                                .parser
+                                .error(offset, format_args!("invalid start `{start}`"))
+                        }
+                    },
+                    None => None,
+                };
+                self.group(Tag::List(start))?
+            }
+            "item" => self.group(Tag::Item)?,
+            "footnote-definition" => {
+                let label = self.string("label")?.unwrap_or(CowStr::Borrowed(""));
+                self.group(Tag::FootnoteDefinition(label))?
+            }
+            "table" => {
+                let mut alignments = vec![];
+                for (offset, it) in self.list("align")? {
+                    let all = [
+                        Alignment::None,
+                        Alignment::Left,
+                        Alignment::Center,
+                        Alignment::Right,
+                    ];
+                    match it {
+                        Value::Atom(it) => match all.into_iter().find(|a| alignment(*a) == it) {
+                            Some(it) => alignments.push(it),
+                            None => {
+                                return self
+                                    .parser
+                                    .error(offset, format_args!("unknown alignment `{it}`"))
+                            }
+                        },
+                        _ => return self.parser.error(offset, "expected an alignment"),
+                    }
+                }
+                self.group(Tag::Table(alignments))?
+            }
+            "table-head" => self.group(Tag::TableHead)?,
+            "table-row" => self.group(Tag::TableRow)?,
+            "table-cell" => self.group(Tag::TableCell)?,
+            "emphasis" => self.group(Tag::Emphasis)?,
+            "strong" => self.group(Tag::Strong)?,
+            "strikethrough" => self.group(Tag::Strikethrough)?,
+            "link" | "image" => {
+                let link_type = self.link_type()?;
+                let dest_url = self.string("url")?.unwrap_or(CowStr::Borrowed(""));
+                let title = self.string("title")?.unwrap_or(CowStr::Borrowed(""));
+                let id = self.string("id")?.unwrap_or(CowStr::Borrowed(""));
+                self.group(match kind {
+                    "link" => Tag::Link {
+                        link_type,
+                        dest_url,
+                        title,
+                        id,
+                    },
+                    _ => Tag::Image {
+                        link_type,
+                        dest_url,
+                        title,
+                        id,
+                    },
+                })?
+            }
+            "metadata-block" => {
+                let kind = match self.atom("kind")? {
+                    Some((_, kind)) if kind == "toml" => MetadataBlockKind::PlusesStyle,
+                    Some((_, kind)) if kind == "yaml" => MetadataBlockKind::YamlStyle,
+                    None => MetadataBlockKind::YamlStyle,
+                    Some((offset, kind)) => {
+                        return self
+                            .parser
+                            .error(offset, format_args!("unknown metadata kind `{kind}`"))
+                    }
+                };
+                self.group(Tag::MetadataBlock(kind))?
+            }
+            "text" => Tree::Text(self.value()?),
+            "code" => Tree::Code(self.value()?),
+            "html" => Tree::Html(self.value()?),
+            "inline-html" => Tree::InlineHtml(self.value()?),
+            "footnote-reference" => Tree::FootnoteReference(self.value()?),
+            "inline-math" => Tree::InlineMath(self.value()?),
+            "display-math" => Tree::DisplayMath(self.value()?),
+            "soft-break" | "hard-break" | "rule" => {
+                if let Some((offset, _)) = self.args.first() {
+                    return self
+                        .parser
+                        .error(*offset, format_args!("unexpected argument to {kind}"));
+                }
+                match kind {
+                    "soft-break" => Tree::SoftBreak(self.span()),
+                    "hard-break" => Tree::HardBreak(self.span()),
+                    _ => Tree::Rule(self.span()),
+                }
+            }
+            "task-list-marker" => {
+                let checked = match self.args.pop() {
+                    Some((_, Value::Atom(it))) if self.args.is_empty() && it == "true" => true,
+                    Some((_, Value::Atom(it))) if self.args.is_empty() && it == "false" => false,
+                    _ => return self.parser.error(self.offset, "expected true or false"),
+                };
+                Tree::TaskListMarker(Spanned {
+                    item: checked,
+                    span: self.span(),
+                })
+            }
+            "broken-link" => {
+                let link_type = self.link_type()?;
+                let reference = self.string("reference")?.unwrap_or(CowStr::Borrowed(""));
+                Tree::BrokenLink(
+                    BrokenLink {
+                        reference,
+                        link_type,
+                        span: self.span(),
+                    },
+                    self.stream()?,
+                )
+            }
+            other => {
+                return self
+                    .parser
+                    .error(self.offset, format_args!("unknown node kind `{other}`"))
+            }
+        };
+        match self.attrs.first() {
+            Some((offset, key, _)) => self.parser.error(
+                *offset,
+                format_args!("unexpected attribute `:{key}` for {kind}"),
+            ),
+            None => Ok(tree),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::corpus;
+
+    #[test]
+    fn corpus_round_trip() {
+        for case in corpus::cases() {
+            let ast = Ast::new_ext(&case.markdown, case.options);
+            let text = ast.to_sexpr_with_spans();
+            assert_eq!(Ast::from_sexpr(&text).as_ref(), Ok(&ast), "{}", case.name);
+            let text = ast.to_sexpr();
+            assert!(
+                Ast::from_sexpr(&text).unwrap().structurally_eq(&ast),
+                "{}",
+                case.name
+            );
+        }
+    }
+
+    #[test]
+    fn errors() {
+        for text in [
+            "(text",
+            "(text \"a\" :x)",
+            "(nope)",
+            "(heading :level 7)",
+            "\"a",
+        ] {
+            assert!(Ast::from_sexpr(text).is_err(), "{text}");
+        }
+    }
+}
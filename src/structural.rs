@@ -0,0 +1,376 @@
+//! Compare and hash trees ignoring [`Span`](crate::Span)s.
+//!
+//! Strings are compared by value, so borrowed and owned [`CowStr`]s are equal.
+
+use std::{
+    borrow::Cow,
+    hash::{Hash, Hasher},
+    mem,
+};
+
+use pulldown_cmark::{CodeBlockKind, CowStr, Tag};
+
+use crate::{Ast, BrokenLink, Group, Span, Spanned, Tree};
+
+impl Ast<'_> {
+    /// Whether `self` and `other` are equal, ignoring spans.
+    ///
+    /// See [`Structural`] to also ignore how text is split.
+    pub fn structurally_eq(&self, other: &Ast<'_>) -> bool {
+        Structural::new().eq(self, other)
+    }
+}
+
+impl Tree<'_> {
+    /// Whether `self` and `other` are equal, ignoring spans.
+    pub fn structurally_eq(&self, other: &Tree<'_>) -> bool {
+        Structural::new().eq_tree(self, other)
+    }
+}
+
+/// Hashing which ignores spans, consistent with [`Ast::structurally_eq`].
+pub trait StructuralHash {
+    fn structural_hash<H: Hasher>(&self, state: &mut H);
+}
+
+impl StructuralHash for Ast<'_> {
+    fn structural_hash<H: Hasher>(&self, state: &mut H) {
+        Structural::new().hash(self, state)
+    }
+}
+
+impl StructuralHash for Tree<'_> {
+    fn structural_hash<H: Hasher>(&self, state: &mut H) {
+        Structural::new().hash_tree(self, state)
+    }
+}
+
+/// Configurable equality and hashing which ignore spans.
+///
+/// For any configuration, trees which are [`Structural::eq`] have the same [`Structural::hash`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Structural {
+    merge_text: bool,
+    soft_break_as_space: bool,
+}
+
+/// A child of an [`Ast`], after text is merged.
+enum Node<'t, 'a> {
+    Text(Cow<'t, str>),
+    Tree(&'t Tree<'a>),
+}
+
+impl Structural {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Treat adjacent [`Tree::Text`]s as one, and ignore empty ones,
+    /// like `a` `b` and `ab`.
+    ///
+    /// The contents of HTML blocks, which may be split into [`Tree::Html`]s
+    /// and [`Tree::Text`]s of indentation, are also compared as one string.
+    pub fn merge_text(mut self, merge: bool) -> Self {
+        self.merge_text = merge;
+        self
+    }
+    /// Treat [`Tree::SoftBreak`]s as text containing a space.
+    pub fn soft_break_as_space(mut self, space: bool) -> Self {
+        self.soft_break_as_space = space;
+        self
+    }
+    pub fn eq(&self, left: &Ast<'_>, right: &Ast<'_>) -> bool {
+        let (left, right) = (self.nodes(left), self.nodes(right));
+        left.len() == right.len()
+            && left.iter().zip(&right).all(|it| match it {
+                (Node::Text(left), Node::Text(right)) => left == right,
+                (Node::Tree(left), Node::Tree(right)) => self.eq_tree(left, right),
+                _ => false,
+            })
+    }
+    pub fn eq_tree(&self, left: &Tree<'_>, right: &Tree<'_>) -> bool {
+        match (left, right) {
+            (Tree::Group(left), Tree::Group(right)) => {
+                left.tag.item == right.tag.item
+                    && match (self.html(left), self.html(right)) {
+                        (Some(left), Some(right)) => left == right,
+                        _ => self.eq(&left.stream, &right.stream),
+                    }
+            }
+            (Tree::BrokenLink(left, left_stream), Tree::BrokenLink(right, right_stream)) => {
+                left.reference == right.reference
+                    && left.link_type == right.link_type
+                    && self.eq(left_stream, right_stream)
+            }
+            (Tree::Text(left), Tree::Text(right))
+            | (Tree::Code(left), Tree::Code(right))
+            | (Tree::Html(left), Tree::Html(right))
+            | (Tree::InlineHtml(left), Tree::InlineHtml(right))
+            | (Tree::FootnoteReference(left), Tree::FootnoteReference(right))
+            | (Tree::InlineMath(left), Tree::InlineMath(right))
+            | (Tree::DisplayMath(left), Tree::DisplayMath(right)) => left.item == right.item,
+            (Tree::TaskListMarker(left), Tree::TaskListMarker(right)) => left.item == right.item,
+            (Tree::SoftBreak(_), Tree::SoftBreak(_))
+            | (Tree::HardBreak(_), Tree::HardBreak(_))
+            | (Tree::Rule(_), Tree::Rule(_)) => true,
+            _ => false,
+        }
+    }
+    pub fn hash<H: Hasher>(&self, ast: &Ast<'_>, state: &mut H) {
+        let nodes = self.nodes(ast);
+        nodes.len().hash(state);
+        for node in nodes {
+            match node {
+                Node::Text(text) => {
+                    let text = Spanned {
+                        item: CowStr::Borrowed(&text),
+                        span: Span(0..0),
+                    };
+                    self.hash_tree(&Tree::Text(text), state)
+                }
+                Node::Tree(tree) => self.hash_tree(tree, state),
+            }
+        }
+    }
+    pub fn hash_tree<H: Hasher>(&self, tree: &Tree<'_>, state: &mut H) {
+        mem::discriminant(tree).hash(state);
+        match tree {
+            Tree::Group(group @ Group { tag, stream, .. }) => {
+                hash_tag(&tag.item, state);
+                match self.html(group) {
+                    Some(html) => html.hash(state),
+                    None => self.hash(stream, state),
+                }
+            }
+            Tree::BrokenLink(
+                BrokenLink {
+                    reference,
+                    link_type,
+                    ..
+                },
+                stream,
+            ) => {
+                reference.hash(state);
+                mem::discriminant(link_type).hash(state);
+                self.hash(stream, state)
+            }
+            Tree::Text(Spanned { item, .. })
+            | Tree::Code(Spanned { item, .. })
+            | Tree::Html(Spanned { item, .. })
+            | Tree::InlineHtml(Spanned { item, .. })
+            | Tree::FootnoteReference(Spanned { item, .. })
+            | Tree::InlineMath(Spanned { item, .. })
+            | Tree::DisplayMath(Spanned { item, .. }) => item.hash(state),
+            Tree::TaskListMarker(Spanned { item, .. }) => item.hash(state),
+            Tree::SoftBreak(_) | Tree::HardBreak(_) | Tree::Rule(_) => {}
+        }
+    }
+    /// The contents of an HTML block, if they're merged.
+    fn html(&self, group: &Group<'_>) -> Option<String> {
+        if !self.merge_text || group.tag.item != Tag::HtmlBlock {
+            return None;
+        }
+        group
+            .stream
+            .0
+            .iter()
+            .map(|it| match it {
+                Tree::Html(Spanned { item, .. }) | Tree::Text(Spanned { item, .. }) => {
+                    Some(&**item)
+                }
+                _ => None,
+            })
+            .collect()
+    }
+    fn nodes<'t, 'a>(&self, ast: &'t Ast<'a>) -> Vec<Node<'t, 'a>> {
+        let mut nodes = Vec::<Node<'t, 'a>>::new();
+        for tree in &ast.0 {
+            let text = match tree {
+                Tree::Text(Spanned { item, .. }) => &**item,
+                Tree::SoftBreak(_) if self.soft_break_as_space => " ",
+                _ => {
+                    nodes.push(Node::Tree(tree));
+                    continue;
+                }
+            };
+            match nodes.last_mut() {
+                _ if self.merge_text && text.is_empty() => {}
+                Some(Node::Text(last)) if self.merge_text => last.to_mut().push_str(text),
+                _ => nodes.push(Node::Text(Cow::Borrowed(text))),
+            }
+        }
+        nodes
+    }
+}
+
+fn hash_tag<H: Hasher>(tag: &Tag<'_>, state: &mut H) {
+    mem::discriminant(tag).hash(state);
+    match tag {
+        Tag::Heading {
+            level,
+            id,
+            classes,
+            attrs,
+        } => {
+            level.hash(state);
+            id.hash(state);
+            classes.hash(state);
+            attrs.hash(state)
+        }
+        Tag::BlockQuote(kind) => kind.hash(state),
+        Tag::CodeBlock(kind) => match kind {
+            CodeBlockKind::Indented => None::<&CowStr<'_>>.hash(state),
+            CodeBlockKind::Fenced(info) => Some(info).hash(state),
+        },
+        Tag::List(start) => start.hash(state),
+        Tag::FootnoteDefinition(label) => label.hash(state),
+        Tag::Table(alignments) => {
+            alignments.len().hash(state);
+            for alignment in alignments {
+                mem::discriminant(alignment).hash(state)
+            }
+        }
+        Tag::Link {
+            link_type,
+            dest_url,
+            title,
+            id,
+        }
+        | Tag::Image {
+            link_type,
+            dest_url,
+            title,
+            id,
+        } => {
+            mem::discriminant(link_type).hash(state);
+            dest_url.hash(state);
+            title.hash(state);
+            id.hash(state)
+        }
+        Tag::MetadataBlock(kind) => kind.hash(state),
+        Tag::Paragraph
+        | Tag::HtmlBlock
+        | Tag::Item
+        | Tag::TableHead
+        | Tag::TableRow
+        | Tag::TableCell
+        | Tag::Emphasis
+        | Tag::Strong
+        | Tag::Strikethrough => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::hash_map::DefaultHasher;
+
+    use super::*;
+
+    fn text(text: &str) -> Tree<'_> {
+        Tree::Text(Spanned {
+            item: text.into(),
+            span: Span(0..0),
+        })
+    }
+
+    fn paragraph(stream: Vec<Tree<'_>>) -> Ast<'_> {
+        Ast(vec![Tree::Group(Group {
+            tag: Spanned {
+                item: Tag::Paragraph,
+                span: Span(0..0),
+            },
+            stream: Ast(stream),
+            end_span: Span(0..0),
+        })])
+    }
+
+    fn hash(structural: Structural, ast: &Ast<'_>) -> u64 {
+        let mut state = DefaultHasher::new();
+        structural.hash(ast, &mut state);
+        state.finish()
+    }
+
+    #[test]
+    fn spans_are_ignored() {
+        let (left, right) = (Ast::new("# a *b*\n"), Ast::new("\n\n#   a *b*  \n"));
+        assert_ne!(left, right);
+        assert!(left.structurally_eq(&right));
+        assert!(left.0[0].structurally_eq(&right.0[0]));
+        assert!(!left.structurally_eq(&Ast::new("# a _c_\n")));
+        assert!(!left.structurally_eq(&Ast::new("## a *b*\n")));
+        assert_eq!(
+            hash(Structural::new(), &left),
+            hash(Structural::new(), &right)
+        );
+    }
+
+    #[test]
+    fn borrowed_and_owned_strings_are_equal() {
+        let owned = Tree::Text(Spanned {
+            item: CowStr::Boxed("a".into()),
+            span: Span(0..1),
+        });
+        assert!(owned.structurally_eq(&text("a")));
+        let (mut left, mut right) = (DefaultHasher::new(), DefaultHasher::new());
+        owned.structural_hash(&mut left);
+        text("a").structural_hash(&mut right);
+        assert_eq!(left.finish(), right.finish());
+    }
+
+    #[test]
+    fn merge_text() {
+        let split = paragraph(vec![text("a"), text(""), text("b")]);
+        let merged = paragraph(vec![text("ab")]);
+        assert!(!split.structurally_eq(&merged));
+        let structural = Structural::new().merge_text(true);
+        assert!(structural.eq(&split, &merged));
+        assert_eq!(hash(structural, &split), hash(structural, &merged));
+        // only adjacent text is merged
+        let code = Tree::Code(Spanned {
+            item: "c".into(),
+            span: Span(0..0),
+        });
+        let around = paragraph(vec![text("a"), code.clone(), text("b")]);
+        assert!(!structural.eq(&around, &paragraph(vec![text("ab"), code])));
+    }
+
+    #[test]
+    fn soft_break_as_space() {
+        let (left, right) = (Ast::new("a\nb\n"), Ast::new("a b\n"));
+        let structural = Structural::new().merge_text(true);
+        assert!(!structural.eq(&left, &right));
+        let structural = structural.soft_break_as_space(true);
+        assert!(structural.eq(&left, &right));
+        assert_eq!(hash(structural, &left), hash(structural, &right));
+        // without merging, the break is still a separate node
+        assert!(!Structural::new()
+            .soft_break_as_space(true)
+            .eq(&left, &right));
+    }
+
+    #[test]
+    fn html_blocks_are_merged() {
+        let html = |stream| {
+            Ast(vec![Tree::Group(Group {
+                tag: Spanned {
+                    item: Tag::HtmlBlock,
+                    span: Span(0..0),
+                },
+                stream: Ast(stream),
+                end_span: Span(0..0),
+            })])
+        };
+        let fragment = |html: &'static str| {
+            Tree::Html(Spanned {
+                item: html.into(),
+                span: Span(0..0),
+            })
+        };
+        let split = html(vec![fragment("<a>\n"), text("  "), fragment("<b>\n")]);
+        let merged = html(vec![fragment("<a>\n  <b>\n")]);
+        assert!(!split.structurally_eq(&merged));
+        let structural = Structural::new().merge_text(true);
+        assert!(structural.eq(&split, &merged));
+        assert_eq!(hash(structural, &split), hash(structural, &merged));
+        assert!(!structural.eq(&split, &html(vec![fragment("<a>\n<b>\n")])));
+    }
+}
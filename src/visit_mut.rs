@@ -5,7 +5,7 @@ use pulldown_cmark::{
     Tag,
 };
 
-use crate::{Ast, Group, Span, Spanned, Tree};
+use crate::{Ast, BrokenLink, Group, Span, Spanned, Tree};
 
 pub trait VisitMut<'a> {
     fn visit_ast_mut(&mut self, node: &mut Ast<'a>) {
@@ -53,6 +53,9 @@ pub trait VisitMut<'a> {
     fn visit_group_mut(&mut self, node: &mut Group<'a>) {
         visit_group_mut(self, node)
     }
+    fn visit_broken_link_mut(&mut self, node: &mut BrokenLink<'a>, stream: &mut Ast<'a>) {
+        visit_broken_link_mut(self, node, stream)
+    }
 
     fn visit_strikethrough_mut(
         &mut self,
@@ -258,14 +261,23 @@ pub fn visit_group_mut<'a, V: VisitMut<'a> + ?Sized>(v: &mut V, node: &mut Group
             dest_url,
             title,
             id,
-        } => visit_link_mut(v, link_type, dest_url, title, id),
+        } => {
+            v.visit_link_mut(link_type, dest_url, title, id);
+            v.visit_ast_mut(stream)
+        }
         Tag::Image {
             link_type,
             dest_url,
             title,
             id,
-        } => visit_image_mut(v, link_type, dest_url, title, id),
-        Tag::MetadataBlock(node) => visit_metadata_block_mut(v, node),
+        } => {
+            v.visit_image_mut(link_type, dest_url, title, id);
+            v.visit_ast_mut(stream)
+        }
+        Tag::MetadataBlock(node) => {
+            v.visit_metadata_block_mut(node);
+            v.visit_ast_mut(stream)
+        }
     }
 }
 
@@ -556,8 +568,17 @@ pub fn visit_tree_mut<'a, V: VisitMut<'a> + ?Sized>(v: &mut V, node: &mut Tree<'
         Tree::SoftBreak(node) => v.visit_soft_break_mut(node),
         Tree::HardBreak(node) => v.visit_hard_break_mut(node),
         Tree::Rule(node) => v.visit_rule_mut(node),
+        Tree::BrokenLink(node, stream) => v.visit_broken_link_mut(node, stream),
     }
 }
+pub fn visit_broken_link_mut<'a, V: VisitMut<'a> + ?Sized>(
+    v: &mut V,
+    node: &mut BrokenLink<'a>,
+    stream: &mut Ast<'a>,
+) {
+    v.visit_cow_str_mut(&mut node.reference);
+    v.visit_ast_mut(stream)
+}
 pub fn visit_text_mut<'a, V: VisitMut<'a> + ?Sized>(v: &mut V, node: &mut Spanned<CowStr<'a>>) {
     v.visit_cow_str_mut(&mut node.item)
 }
@@ -565,3 +586,66 @@ pub fn visit_cow_str_mut<'a, V: VisitMut<'a> + ?Sized>(v: &mut V, node: &mut Cow
     let _ = v;
     let _ = node;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Record the order nodes are visited in.
+    #[derive(Default)]
+    struct Order(Vec<String>);
+
+    impl<'a> VisitMut<'a> for Order {
+        fn visit_text_mut(&mut self, node: &mut Spanned<CowStr<'a>>) {
+            self.0.push(format!("text {}", node.item))
+        }
+        fn visit_link_mut(
+            &mut self,
+            _: &mut LinkType,
+            dest_url: &mut CowStr<'a>,
+            _: &mut CowStr<'a>,
+            _: &mut CowStr<'a>,
+        ) {
+            self.0.push(format!("link {dest_url}"))
+        }
+        fn visit_image_mut(
+            &mut self,
+            _: &mut LinkType,
+            dest_url: &mut CowStr<'a>,
+            _: &mut CowStr<'a>,
+            _: &mut CowStr<'a>,
+        ) {
+            self.0.push(format!("image {dest_url}"))
+        }
+        fn visit_metadata_block_mut(&mut self, _: &mut MetadataBlockKind) {
+            self.0.push("metadata".into())
+        }
+    }
+
+    /// Links, images and metadata blocks used to be visited through the free functions,
+    /// skipping both these overrides and their children,
+    /// so only `text a ` and `text  d` were recorded.
+    /// Now the overrides are called, followed by the children.
+    #[test]
+    fn links_images_and_metadata_visit_their_children() {
+        let mut ast = Ast::new_ext(
+            "---\nk: v\n---\na [b ![c](i)](l) d\n",
+            pulldown_cmark::Options::ENABLE_YAML_STYLE_METADATA_BLOCKS,
+        );
+        let mut order = Order::default();
+        order.visit_ast_mut(&mut ast);
+        assert_eq!(
+            order.0,
+            [
+                "metadata",
+                "text k: v\n",
+                "text a ",
+                "link l",
+                "text b ",
+                "image i",
+                "text c",
+                "text  d"
+            ]
+        );
+    }
+}
@@ -0,0 +1,146 @@
+//! Run the `md-ast` binary end to end.
+
+use std::{
+    fs,
+    io::Write as _,
+    path::PathBuf,
+    process::{Command, Output, Stdio},
+};
+
+const DOCUMENT: &str = "\
+# Title
+
+Some *text* and [a link](/url).
+
+## Sub
+
+```rust
+fn main() {}
+```
+";
+
+fn md_ast(args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_md-ast"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn stdout(output: &Output) -> &str {
+    std::str::from_utf8(&output.stdout).unwrap()
+}
+
+/// Write `files` to a directory of their own, named for `test`.
+fn files<const N: usize>(test: &str, files: [(&str, &str); N]) -> [String; N] {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(test);
+    fs::create_dir_all(&dir).unwrap();
+    files.map(|(name, text)| {
+        let path = dir.join(name);
+        fs::write(&path, text).unwrap();
+        path.display().to_string()
+    })
+}
+
+#[test]
+fn dump_json() {
+    let output = md_ast(&["dump", "--format", "json"], DOCUMENT);
+    assert!(output.status.success());
+    let json = serde_json::from_str::<serde_json::Value>(stdout(&output)).unwrap();
+    assert_eq!(json[0]["type"], "heading");
+    assert_eq!(json[0]["level"], 1);
+    assert_eq!(json[0]["span"], serde_json::json!([0, 8]));
+}
+
+#[test]
+fn query() {
+    let output = md_ast(&["query", "code-block"], DOCUMENT);
+    assert!(output.status.success());
+    assert_eq!(
+        stdout(&output),
+        "<stdin>:7:1: code-block \"fn main() {}\\n\"\n"
+    );
+}
+
+#[test]
+fn toc() {
+    let output = md_ast(&["toc"], DOCUMENT);
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "- [Title](#title)\n  - [Sub](#sub)\n");
+}
+
+#[test]
+fn links() {
+    let [path] = files("links", [("links.md", DOCUMENT)]);
+    let output = md_ast(&["links", &path], "");
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), format!("{path}:3:17: /url\n"));
+}
+
+#[test]
+fn fmt_check() {
+    let output = md_ast(&["fmt", "--check"], DOCUMENT);
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "");
+
+    let output = md_ast(&["fmt", "--check"], "#  Title\n");
+    assert!(!output.status.success());
+    assert_eq!(stdout(&output), "<stdin>\n");
+}
+
+#[test]
+fn lint() {
+    let output = md_ast(&["lint", "--format", "plain"], DOCUMENT);
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "");
+
+    let output = md_ast(
+        &["lint", "--format", "plain", "--only", "single-h1"],
+        "# A\n# B\n",
+    );
+    assert!(!output.status.success());
+    assert_eq!(
+        stdout(&output),
+        "<stdin>:2:1: warning[single-h1]: multiple top-level headings, the first is on line 1\n"
+    );
+}
+
+#[test]
+fn merge() {
+    let [base, ours, theirs] = files(
+        "merge-clean",
+        [
+            ("base.md", "a\n\nb\n"),
+            ("ours.md", "A\n\nb\n"),
+            ("theirs.md", "a\n\nB\n"),
+        ],
+    );
+    let output = md_ast(&["merge", &base, &ours, &theirs], "");
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "A\n\nB\n");
+
+    let [base, ours, theirs] = files(
+        "merge-conflict",
+        [
+            ("base.md", "a\n\nb\n"),
+            ("ours.md", "A\n\nb\n"),
+            ("theirs.md", "X\n\nb\n"),
+        ],
+    );
+    let output = md_ast(&["merge", &base, &ours, &theirs], "");
+    assert!(!output.status.success());
+    assert!(stdout(&output).starts_with("<<<<<<< ours\n"));
+    assert_eq!(
+        std::str::from_utf8(&output.stderr).unwrap(),
+        format!("{ours}:1:1: conflict\n")
+    );
+}